@@ -2,7 +2,33 @@
 use log::*;
 use polars::prelude::*;
 
-use crate::cleaning::names::clean_name;
+use crate::cleaning::names::{clean_name, name_key};
+use crate::cleaning::strings::norm_title;
+use crate::marc::fixed;
+
+/// Apply a string function to a string column.
+pub fn map_strings<F>(s: Series, f: F) -> PolarsResult<Option<Series>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let res: StringChunked = s.str()?.into_iter().map(|v| v.and_then(&f)).collect();
+    Ok(Some(res.into_series()))
+}
+
+/// Get the language code from MARC fixed fields (008).
+pub fn udf_fixed_language(s: Series) -> PolarsResult<Option<Series>> {
+    map_strings(s, |f| fixed::language(f).map(|l| l.to_string()))
+}
+
+/// Normalize titles for matching, with empty keys as null.
+pub fn udf_title_key(s: Series) -> PolarsResult<Option<Series>> {
+    map_strings(s, |t| Some(norm_title(t)).filter(|k| !k.is_empty()))
+}
+
+/// Normalize author names to surname and initials for matching, with empty keys as null.
+pub fn udf_author_key(s: Series) -> PolarsResult<Option<Series>> {
+    map_strings(s, |n| Some(name_key(n)).filter(|k| !k.is_empty()))
+}
 
 pub fn udf_clean_name(col: Series) -> PolarsResult<Option<Series>> {
     let col = col.str()?;
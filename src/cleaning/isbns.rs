@@ -1,8 +1,9 @@
 //! Code for cleaning up ISBNs.
 //!
-//! This module contains two families of functions:
+//! This module contains three families of functions:
 //!
 //! - The simple character-cleaning functions [clean_isbn_chars] and [clean_asin_chars].
+//! - The [isbn13] conversion to put ISBN-10s in canonical form.
 //! - The full multi-ISBN parser [parse_isbn_string].
 //!
 //! When a string is a relatively well-formed ISBN (or ASIN), the character-cleaning functions
@@ -10,6 +11,8 @@
 //! may have multiple ISBNs in one string, descriptive tags, and all manner of other messes.
 //! The multi-ISBN parser exposed through [parse_isbn_string] supports cleaning these ISBN
 //! strings using a PEG-based parser.
+use std::borrow::Cow;
+
 use lazy_static::lazy_static;
 use regex::RegexSet;
 
@@ -127,6 +130,33 @@ pub fn clean_asin_chars(isbn: &str) -> String {
     }
 }
 
+/// Query whether a (cleaned) ASIN is an ISBN-10.
+pub fn asin_is_isbn(asin: &str) -> bool {
+    let bytes = asin.as_bytes();
+    bytes.len() == 10
+        && bytes[..9].iter().all(|b| b.is_ascii_digit())
+        && (bytes[9].is_ascii_digit() || bytes[9] == b'X')
+}
+
+/// Convert a (cleaned) ISBN-10 to its ISBN-13 form.
+///
+/// Strings that are not ISBN-10s are returned unchanged.
+pub fn isbn13(isbn: &str) -> Cow<'_, str> {
+    if !asin_is_isbn(isbn) {
+        return Cow::Borrowed(isbn);
+    }
+    let mut out = String::with_capacity(13);
+    out.push_str("978");
+    out.push_str(&isbn[..9]);
+    let sum: u32 = out
+        .bytes()
+        .enumerate()
+        .map(|(i, b)| (b - b'0') as u32 * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    out.push(char::from_digit((10 - sum % 10) % 10, 10).unwrap());
+    Cow::Owned(out)
+}
+
 /// Parse an ISBN string.
 pub fn parse_isbn_string(s: &str) -> ParseResult {
     // let mut parser = self.create_parser(s);
@@ -416,3 +446,20 @@ pub fn test_parse_isbn_oh() {
         panic!("failed to parse {}: {:?}", src, isbns);
     }
 }
+
+#[test]
+fn test_asin_is_isbn() {
+    assert!(asin_is_isbn("0553213113"));
+    assert!(asin_is_isbn("349224010X"));
+    assert!(!asin_is_isbn("B000FC1PJI"));
+    assert!(!asin_is_isbn("055321311"));
+    assert!(!asin_is_isbn("9780553213119"));
+}
+
+#[test]
+fn test_isbn13() {
+    assert_eq!(isbn13("0553213113"), "9780553213119");
+    assert_eq!(isbn13("349224010X"), "9783492240109");
+    assert_eq!(isbn13("9780553213119"), "9780553213119");
+    assert_eq!(isbn13("B000FC1PJI"), "B000FC1PJI");
+}
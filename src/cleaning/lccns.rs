@@ -0,0 +1,50 @@
+//! Normalize Library of Congress Control Numbers.
+//!
+//! LCCNs are written in many forms (`85-12345`, `  85012345 `, `sn 85012345 //r86`).
+//! We normalize them following the [LC rules](https://www.loc.gov/marc/lccn-namespace.html#normalization)
+//! so the same number matches across sources.
+
+/// Normalize an LCCN.
+///
+/// This removes blanks and anything after a `/`, and zero-pads the part after a
+/// hyphen to six digits.  Strings that do not look like LCCNs (too short, or with
+/// characters other than a lowercase prefix and digits) yield `None`.
+pub fn norm_lccn(lccn: &str) -> Option<String> {
+    let lccn: String = lccn
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let lccn = match lccn.split_once('-') {
+        Some((pfx, serial)) => format!("{}{:0>6}", pfx, serial),
+        None => lccn,
+    };
+    let digits = lccn.trim_start_matches(|c: char| c.is_ascii_lowercase());
+    if digits.len() >= 8 && digits.bytes().all(|b| b.is_ascii_digit()) {
+        Some(lccn)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_norm_lccn() {
+    assert_eq!(norm_lccn("85012345").as_deref(), Some("85012345"));
+    assert_eq!(norm_lccn("  85012345 ").as_deref(), Some("85012345"));
+    assert_eq!(norm_lccn("85-2").as_deref(), Some("85000002"));
+    assert_eq!(
+        norm_lccn("sn 85-12345 //r86").as_deref(),
+        Some("sn85012345")
+    );
+    assert_eq!(norm_lccn("2001-1114").as_deref(), Some("2001001114"));
+    assert_eq!(norm_lccn("n78-89035").as_deref(), Some("n78089035"));
+}
+
+#[test]
+fn test_norm_lccn_invalid() {
+    assert_eq!(norm_lccn(""), None);
+    assert_eq!(norm_lccn("1234"), None);
+    assert_eq!(norm_lccn("85012345x"), None);
+}
@@ -1,4 +1,5 @@
 //! Utilities for cleaning data.
 pub mod isbns;
+pub mod lccns;
 pub mod names;
 pub mod strings;
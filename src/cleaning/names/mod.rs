@@ -21,7 +21,7 @@
 
 use anyhow::Result;
 
-use super::strings::norm_unicode;
+use super::strings::{fold_text, norm_unicode};
 
 mod parse;
 mod types;
@@ -105,6 +105,46 @@ pub fn clean_name<'a>(name: &'a str) -> String {
     }
 }
 
+/// Extract the folded surname from a name in “Last, First” or “First Last” form.
+pub fn name_surname(name: &str) -> String {
+    let name = clean_name(name);
+    let last = match name.split_once(',') {
+        Some((last, _)) => last,
+        None => name.split_whitespace().last().unwrap_or_default(),
+    };
+    fold_text(last)
+}
+
+/// Make a key for matching names from the surname and given-name initials.
+///
+/// The “Last, First” and “First Last” forms of a name, and forms with full given
+/// names or initials, give the same key: `Tolkien, J. R. R.` and `John Ronald Reuel
+/// Tolkien` are both `tolkien jrr`.
+pub fn name_key(name: &str) -> String {
+    let name = clean_name(name);
+    let (last, given) = match name.split_once(',') {
+        Some((last, given)) => (fold_text(last), fold_text(given)),
+        None => {
+            let folded = fold_text(&name);
+            match folded.rsplit_once(' ') {
+                Some((given, last)) => (last.to_string(), given.to_string()),
+                None => (folded, String::new()),
+            }
+        }
+    };
+    // dates and other numbers after the given names are not initials
+    let initials: String = given
+        .split(' ')
+        .filter_map(|w| w.chars().next())
+        .filter(|c| c.is_alphabetic())
+        .collect();
+    if initials.is_empty() {
+        last
+    } else {
+        format!("{} {}", last, initials)
+    }
+}
+
 /// Extract all variants from a name.
 ///
 /// See the [module documentation][self] for details on this parsing process.
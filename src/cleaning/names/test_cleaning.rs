@@ -1,6 +1,6 @@
 //! Test the [`clean_name`] function.
 
-use super::clean_name;
+use super::{clean_name, name_key, name_surname};
 
 #[test]
 fn test_clean_point() {
@@ -36,3 +36,24 @@ fn test_trim() {
     let clean = clean_name(name);
     assert_eq!(&clean, "Bob, Qbert");
 }
+
+#[test]
+fn test_surname_last_first() {
+    assert_eq!(name_surname("Tolkien, J. R. R."), "tolkien");
+}
+
+#[test]
+fn test_surname_first_last() {
+    assert_eq!(name_surname("J. R. R. Tolkien"), "tolkien");
+    assert_eq!(name_surname("Brontë"), "bronte");
+    assert_eq!(name_surname(""), "");
+}
+
+#[test]
+fn test_name_key_forms() {
+    assert_eq!(name_key("Tolkien, J. R. R."), "tolkien jrr");
+    assert_eq!(name_key("J. R. R. Tolkien"), "tolkien jrr");
+    assert_eq!(name_key("John Ronald Reuel Tolkien"), "tolkien jrr");
+    assert_eq!(name_key("Homer"), "homer");
+    assert_eq!(name_key(""), "");
+}
@@ -2,6 +2,7 @@
 use std::iter::FromIterator;
 
 use std::borrow::Cow;
+use unicode_normalization::char::is_combining_mark;
 use unicode_normalization::*;
 
 /// Normalize Unicode character representations in a string.
@@ -13,6 +14,62 @@ pub fn norm_unicode<'a>(s: &'a str) -> Cow<'a, str> {
     }
 }
 
+/// Fold a string for loose comparison.
+///
+/// This strips accents, lowercases, and collapses each run of punctuation and
+/// whitespace into a single space, so `Le Père  Goriot.` folds to `le pere goriot`.
+pub fn fold_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut space = false;
+    for c in s.nfd().filter(|c| !is_combining_mark(*c)) {
+        if c.is_alphanumeric() {
+            if space && !out.is_empty() {
+                out.push(' ');
+            }
+            space = false;
+            out.extend(c.to_lowercase());
+        } else {
+            space = true;
+        }
+    }
+    out
+}
+
+/// Normalize a book title for blocking.
+///
+/// This drops bracketed annotations (such as `[electronic resource]`) and
+/// everything after the main title (a `:` subtitle, `;` part, or `/` statement of
+/// responsibility), folds the text, and strips a leading English article.
+pub fn norm_title(s: &str) -> String {
+    let mut main = String::with_capacity(s.len());
+    let mut depth = 0;
+    for c in s.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' if depth > 0 => depth -= 1,
+            ':' | ';' | '/' if depth == 0 => break,
+            c if depth == 0 => main.push(c),
+            _ => (),
+        }
+    }
+    let folded = fold_text(&main);
+    for article in ["the ", "a ", "an "] {
+        if let Some(rest) = folded.strip_prefix(article) {
+            return rest.to_string();
+        }
+    }
+    folded
+}
+
+/// Trim whitespace and trailing ISBD punctuation from a title.
+///
+/// MARC title fields end with the punctuation that separates them from the next
+/// subfield, as in `The hobbit :`.
+pub fn trim_title(s: &str) -> &str {
+    s.trim()
+        .trim_end_matches(|c: char| c.is_whitespace() || "/:;,=".contains(c))
+}
+
 #[test]
 fn test_nu_empty() {
     let text = "";
@@ -33,3 +90,48 @@ fn test_nu_metal() {
     let res = norm_unicode(&text);
     assert_eq!(res.as_ref(), "meta\u{0308}l");
 }
+
+#[test]
+fn test_fold_basic() {
+    assert_eq!(fold_text("The Hobbit"), "the hobbit");
+}
+
+#[test]
+fn test_fold_punct() {
+    assert_eq!(
+        fold_text("  Hobbit: or, There and Back Again. "),
+        "hobbit or there and back again"
+    );
+    assert_eq!(fold_text("..."), "");
+}
+
+#[test]
+fn test_fold_accents() {
+    assert_eq!(fold_text("Le Père Goriot"), "le pere goriot");
+    assert_eq!(fold_text("Le Pe\u{0301}re Goriot"), "le pere goriot");
+}
+
+#[test]
+fn test_norm_title() {
+    assert_eq!(
+        norm_title("The Hobbit, or, There and Back Again"),
+        "hobbit or there and back again"
+    );
+    assert_eq!(
+        norm_title("The hobbit : or, There and back again /"),
+        "hobbit"
+    );
+    assert_eq!(
+        norm_title("Hamlet [electronic resource] / William Shakespeare"),
+        "hamlet"
+    );
+    assert_eq!(norm_title("An Essay on Man; Part 1"), "essay on man");
+    assert_eq!(norm_title("Theory of Games"), "theory of games");
+}
+
+#[test]
+fn test_trim_title() {
+    assert_eq!(trim_title("The hobbit :"), "The hobbit");
+    assert_eq!(trim_title(" Hamlet / "), "Hamlet");
+    assert_eq!(trim_title("Dr. No"), "Dr. No");
+}
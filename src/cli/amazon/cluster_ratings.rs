@@ -1,4 +1,7 @@
 //! Cluster Amazon ratings.
+use crate::graph::hierarchy::scan_record_works;
+use crate::graph::linkage::{scan_ns_clusters, LinkageStats};
+use crate::ids::codes::NS_ASIN;
 use crate::prelude::*;
 use polars::prelude::*;
 
@@ -10,6 +13,16 @@ pub struct ClusterRatings {
     #[arg(short = 'o', long = "output", name = "FILE")]
     ratings_out: PathBuf,
 
+    /// Write linkage statistics to FILE
+    #[arg(long = "linkage-stats", name = "STATFILE")]
+    linkage_out: Option<PathBuf>,
+
+    /// Group by the works within book clusters (from the cluster hierarchy).
+    ///
+    /// Products in no work are grouped on their own.
+    #[arg(long = "cluster-works")]
+    cluster_works: bool,
+
     /// Input file to cluster
     #[arg(name = "INPUT")]
     infile: PathBuf,
@@ -17,16 +30,57 @@ pub struct ClusterRatings {
 
 impl Command for ClusterRatings {
     fn exec(&self) -> Result<()> {
-        let isbns = LazyFrame::scan_parquet("book-links/isbn-clusters.parquet", default())?;
-        let isbns = isbns.select(&[col("isbn"), col("cluster")]);
+        let asins = LazyFrame::scan_parquet("book-links/asin-ids.parquet", default())?;
+        let asins = asins.select(&[col("asin"), col("asin_id")]);
+        let clusters = scan_ns_clusters(&NS_ASIN, "")?;
+        let asins = asins.join(
+            clusters,
+            &[col("asin_id")],
+            &[col("id")],
+            JoinType::Inner.into(),
+        );
 
         let ratings = LazyFrame::scan_parquet(&self.infile, default())?;
 
-        let joined = ratings.join(isbns, &[col("asin")], &[col("isbn")], JoinType::Inner.into());
+        let joined = ratings.join(
+            asins,
+            &[col("asin")],
+            &[col("asin")],
+            JoinType::Inner.into(),
+        );
+        let mut joined = joined.cache();
+
+        let stats = LinkageStats::compute(
+            joined
+                .clone()
+                .select([col("asin_id").alias("item"), col("linked")]),
+        )?;
+        stats.log("Amazon");
+        if let Some(path) = &self.linkage_out {
+            stats.save(path)?;
+        }
+
+        let item = if self.cluster_works {
+            info!("grouping by works within clusters");
+            let code = col("asin_id") + lit(NS_ASIN.base());
+            let works = scan_record_works("")?;
+            joined = joined.join(
+                works,
+                [code.clone()],
+                [col("book_code")],
+                JoinType::Left.into(),
+            );
+            when(col("work").is_not_null())
+                .then(col("work"))
+                .otherwise(code)
+        } else {
+            col("cluster")
+        };
+
         let joined = joined
             .select(&[
                 col("user"),
-                col("cluster").alias("item"),
+                item.alias("item"),
                 col("rating"),
                 col("timestamp"),
             ])
@@ -0,0 +1,57 @@
+//! Assign identifiers to Amazon ASINs.
+use crate::prelude::*;
+use polars::prelude::*;
+
+/// Collect the ASINs from Amazon rating files and assign them numeric IDs.
+#[derive(Args, Debug)]
+#[command(name = "collect-asins")]
+pub struct CollectASINs {
+    /// ASIN ID output file
+    #[arg(short = 'o', long = "output", name = "FILE")]
+    outfile: PathBuf,
+
+    /// Rating files to scan for ASINs
+    #[arg(name = "INPUT", required = true)]
+    infiles: Vec<PathBuf>,
+}
+
+impl Command for CollectASINs {
+    fn exec(&self) -> Result<()> {
+        let mut frames = Vec::new();
+        for path in &self.infiles {
+            info!("scanning ASINs from {}", path.display());
+            let df = LazyFrame::scan_parquet(path, default())?;
+            frames.push(df.select([col("asin")]));
+        }
+        let asins = concat(frames, UnionArgs::default())?
+            .drop_nulls(None)
+            .unique(None, UniqueKeepStrategy::Any)
+            .sort("asin", default())
+            .with_row_index("asin_id", Some(1));
+
+        let isbns = LazyFrame::scan_parquet("book-links/all-isbns.parquet", default())?;
+        let isbns = isbns.select([col("isbn"), col("isbn_id")]);
+        let asins = asins.join(isbns, [col("asin")], [col("isbn")], JoinType::Left.into());
+        let asins = asins
+            .select([
+                col("asin_id").cast(DataType::Int32),
+                col("asin"),
+                col("isbn_id"),
+            ])
+            .sort("asin_id", default());
+
+        info!("collecting ASINs");
+        let asins = asins.collect()?;
+        let linked = asins.column("isbn_id")?.is_not_null().sum().unwrap_or(0);
+        info!(
+            "found {} ASINs, {} of which are known ISBNs",
+            asins.height(),
+            linked
+        );
+
+        info!("saving to {}", self.outfile.display());
+        save_df_parquet(asins, &self.outfile)?;
+
+        Ok(())
+    }
+}
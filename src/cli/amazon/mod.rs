@@ -1,8 +1,10 @@
 //! Amazon commands.
 pub mod cluster_ratings;
+pub mod collect_asins;
 pub mod scan_ratings;
 pub mod scan_reviews;
 
 pub use cluster_ratings::ClusterRatings;
+pub use collect_asins::CollectASINs;
 pub use scan_ratings::ScanRatings;
 pub use scan_reviews::ScanReviews;
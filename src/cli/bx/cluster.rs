@@ -1,6 +1,8 @@
 //! BookCrossing interaction clustering.
 use std::path::PathBuf;
 
+use crate::graph::linkage::{scan_ns_clusters, LinkageStats};
+use crate::ids::codes::NS_BX;
 use crate::prelude::*;
 use polars::prelude::*;
 
@@ -18,6 +20,10 @@ pub struct Cluster {
     /// The output file.
     #[arg(short = 'o', long = "output", name = "FILE")]
     outfile: PathBuf,
+
+    /// Write linkage statistics to FILE.
+    #[arg(long = "linkage-stats", name = "STATFILE")]
+    linkage_out: Option<PathBuf>,
 }
 
 impl Command for Cluster {
@@ -28,8 +34,15 @@ impl Command for Cluster {
         }
         require_working_dir("bx")?;
 
-        let isbns = LazyFrame::scan_parquet("../book-links/isbn-clusters.parquet", default())?;
-        let isbns = isbns.select(&[col("isbn"), col("cluster")]);
+        let books = LazyFrame::scan_parquet("bx-book-ids.parquet", default())?;
+        let books = books.select(&[col("isbn"), col("bx_id")]);
+        let clusters = scan_ns_clusters(&NS_BX, "../")?;
+        let books = books.join(
+            clusters,
+            &[col("bx_id")],
+            &[col("id")],
+            JoinType::Inner.into(),
+        );
 
         let ratings = LazyCsvReader::new("cleaned-ratings.csv")
             .has_header(true)
@@ -39,7 +52,24 @@ impl Command for Cluster {
         } else {
             ratings
         };
-        let joined = ratings.join(isbns, &[col("isbn")], &[col("isbn")], JoinType::Inner.into());
+        let joined = ratings.join(
+            books,
+            &[col("isbn")],
+            &[col("isbn")],
+            JoinType::Inner.into(),
+        );
+        let joined = joined.cache();
+
+        let stats = LinkageStats::compute(
+            joined
+                .clone()
+                .select([col("bx_id").alias("item"), col("linked")]),
+        )?;
+        stats.log("BookCrossing");
+        if let Some(path) = &self.linkage_out {
+            stats.save(path)?;
+        }
+
         let grouped = joined.group_by(&[col("user"), col("cluster").alias("item")]);
         let agg = if self.ratings {
             grouped.agg(&[
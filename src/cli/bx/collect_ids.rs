@@ -0,0 +1,50 @@
+//! Assign identifiers to BookCrossing books.
+use std::path::PathBuf;
+
+use crate::prelude::*;
+use polars::prelude::*;
+
+/// Assign numeric IDs to the books (ISBN strings) in the BookCrossing ratings.
+#[derive(Args, Debug)]
+#[command(name = "collect-ids")]
+pub struct CollectIds {
+    /// The output file.
+    #[arg(short = 'o', long = "output", name = "FILE")]
+    outfile: PathBuf,
+}
+
+impl Command for CollectIds {
+    fn exec(&self) -> Result<()> {
+        require_working_dir("bx")?;
+
+        let ratings = LazyCsvReader::new("cleaned-ratings.csv")
+            .has_header(true)
+            .finish()?;
+        let books = ratings
+            .select([col("isbn")])
+            .drop_nulls(None)
+            .unique(None, UniqueKeepStrategy::Any)
+            .sort("isbn", default())
+            .with_row_index("bx_id", Some(1));
+
+        let isbns = LazyFrame::scan_parquet("../book-links/all-isbns.parquet", default())?;
+        let isbns = isbns.select([col("isbn"), col("isbn_id")]);
+        let books = books.join(isbns, [col("isbn")], [col("isbn")], JoinType::Left.into());
+        let books = books
+            .select([
+                col("bx_id").cast(DataType::Int32),
+                col("isbn"),
+                col("isbn_id"),
+            ])
+            .sort("bx_id", default());
+
+        info!("collecting results");
+        let books = books.collect()?;
+        info!("found {} BookCrossing books", books.height());
+
+        info!("writing to {:?}", &self.outfile);
+        save_df_parquet(books, &self.outfile)?;
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 //! BookCrossing commands.
 mod cluster;
+mod collect_ids;
 mod extract;
 
 pub use cluster::Cluster;
+pub use collect_ids::CollectIds;
 pub use extract::Extract;
@@ -0,0 +1,316 @@
+//! Audit large clusters for weak links.
+use std::cmp::Ordering;
+
+use parquet_derive::ParquetRecordWriter;
+use petgraph::algo::kosaraju_scc;
+
+use crate::arrow::*;
+use crate::graph::cuts::{split_balance, CutAnalysis};
+use crate::graph::*;
+use crate::ids::codes::{ns_of_book_code, NS_ISBN};
+use crate::prelude::*;
+
+/// Find the weak links (articulation points and bridges) in large clusters.
+///
+/// A weak link is a single node or edge whose removal splits its cluster; ISBNs
+/// that are weak links in big clusters are the usual culprits when unrelated books
+/// get merged.
+#[derive(Args, Debug)]
+#[command(name = "audit")]
+pub struct AuditClusters {
+    /// Load the saved graph from FILE instead of constructing it.
+    #[arg(long = "graph-file", name = "FILE")]
+    graph_file: Option<PathBuf>,
+
+    /// Apply curation overrides from CURFILE when constructing the graph.
+    #[arg(long = "curation", name = "CURFILE", conflicts_with = "FILE")]
+    curation: Option<PathBuf>,
+
+    /// Only audit clusters with at least N nodes.
+    #[arg(long = "min-size", name = "N", default_value_t = 100)]
+    min_size: u32,
+
+    /// Only report cuts whose split balance is at least B.
+    #[arg(long = "min-balance", name = "B", default_value_t = 0.01)]
+    min_balance: f64,
+
+    /// Report weak links of every node type, not just ISBNs.
+    #[arg(long = "all-types")]
+    all_types: bool,
+
+    /// Write output to FILE.
+    #[arg(
+        short = 'o',
+        long = "output",
+        name = "OUTFILE",
+        default_value = "book-links/cluster-weak-links.parquet"
+    )]
+    output: PathBuf,
+}
+
+/// A weak link in a cluster.
+///
+/// Articulation points have only the `book_*` fields; bridges describe the second
+/// endpoint in the `other_*` fields, with the ISBN (or higher-degree) endpoint first.
+#[derive(ParquetRecordWriter, Debug)]
+struct WeakLink {
+    cluster: i32,
+    cluster_size: u32,
+    link_type: String,
+    book_code: i32,
+    node_type: String,
+    label: Option<String>,
+    degree: u32,
+    other_code: Option<i32>,
+    other_type: Option<String>,
+    other_label: Option<String>,
+    other_degree: Option<u32>,
+    n_pieces: u32,
+    largest_piece: u32,
+    n_detached: u32,
+    balance: f64,
+}
+
+/// Get the name of a node's namespace.
+fn node_type(book: &BookID) -> String {
+    ns_of_book_code(book.code)
+        .map(|ns| ns.name.to_string())
+        .unwrap_or_else(|| "UNKNOWN".into())
+}
+
+/// Check whether a node is an ISBN.
+fn is_isbn(graph: &IdGraph, node: IdNode) -> bool {
+    NS_ISBN
+        .from_code(graph.node_weight(node).unwrap().code)
+        .is_some()
+}
+
+impl WeakLink {
+    fn create(graph: &IdGraph, cluster: i32, size: u32, node: IdNode, pieces: &[u32]) -> WeakLink {
+        let book = graph.node_weight(node).unwrap();
+        let largest = pieces.iter().max().copied().unwrap_or(0);
+        let total: u32 = pieces.iter().sum();
+        WeakLink {
+            cluster,
+            cluster_size: size,
+            link_type: "node".into(),
+            book_code: book.code,
+            node_type: node_type(book),
+            label: book.label.clone(),
+            degree: graph.edges(node).count() as u32,
+            other_code: None,
+            other_type: None,
+            other_label: None,
+            other_degree: None,
+            n_pieces: pieces.len() as u32,
+            largest_piece: largest,
+            n_detached: total - largest,
+            balance: split_balance(pieces),
+        }
+    }
+
+    fn set_other(&mut self, graph: &IdGraph, node: IdNode) {
+        let book = graph.node_weight(node).unwrap();
+        self.link_type = "edge".into();
+        self.other_code = Some(book.code);
+        self.other_type = Some(node_type(book));
+        self.other_label = book.label.clone();
+        self.other_degree = Some(graph.edges(node).count() as u32);
+    }
+}
+
+/// Find the weak links in clusters with at least `min_size` nodes.
+///
+/// Unless `all_types` is set, this only reports ISBN articulation points and bridges
+/// with at least one ISBN endpoint (listed first), since ISBNs are what we can block.
+/// The links are sorted by balance, highest first.
+fn find_weak_links(
+    graph: &IdGraph,
+    min_size: u32,
+    min_balance: f64,
+    all_types: bool,
+) -> Vec<WeakLink> {
+    info!("computing connected components");
+    let clusters = kosaraju_scc(graph);
+    let mut cluster_ids = vec![0; graph.node_count()];
+    let mut n_large = 0;
+    for verts in &clusters {
+        let cluster = verts
+            .iter()
+            .map(|v| graph.node_weight(*v).unwrap().code)
+            .min()
+            .unwrap();
+        for v in verts {
+            cluster_ids[v.index()] = cluster;
+        }
+        if verts.len() >= min_size as usize {
+            n_large += 1;
+        }
+    }
+    info!(
+        "{} of {} clusters have at least {} nodes",
+        n_large,
+        clusters.len(),
+        min_size
+    );
+    drop(clusters);
+
+    info!("searching for cuts");
+    let cuts = CutAnalysis::compute(graph);
+    let large = |v: IdNode| cuts.component_size(v) >= min_size;
+    let reported = |v: IdNode| all_types || is_isbn(graph, v);
+
+    let mut links = Vec::new();
+    for (v, pieces) in cuts.articulation_points(graph) {
+        if large(v) && reported(v) {
+            let size = cuts.component_size(v);
+            links.push(WeakLink::create(
+                graph,
+                cluster_ids[v.index()],
+                size,
+                v,
+                &pieces,
+            ));
+        }
+    }
+    let n_points = links.len();
+    for (e, pieces) in cuts.bridge_splits(graph) {
+        let (a, b) = graph.edge_endpoints(e).unwrap();
+        if !large(a) || !(reported(a) || reported(b)) {
+            continue;
+        }
+        // put the reported endpoint first, or the higher-degree one if both are
+        let (a, b) = if reported(a) != reported(b) {
+            if reported(a) {
+                (a, b)
+            } else {
+                (b, a)
+            }
+        } else if graph.edges(a).count() >= graph.edges(b).count() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let size = cuts.component_size(a);
+        let mut link = WeakLink::create(graph, cluster_ids[a.index()], size, a, &pieces);
+        link.set_other(graph, b);
+        links.push(link);
+    }
+    info!(
+        "found {} articulation points and {} bridges",
+        n_points,
+        links.len() - n_points
+    );
+
+    links.retain(|l| l.balance >= min_balance);
+    links.sort_by(|a, b| {
+        b.balance
+            .partial_cmp(&a.balance)
+            .unwrap_or(Ordering::Equal)
+            .then(b.n_detached.cmp(&a.n_detached))
+    });
+    links
+}
+
+impl Command for AuditClusters {
+    fn exec(&self) -> Result<()> {
+        let graph = if let Some(path) = &self.graph_file {
+            info!("loading graph from {}", path.display());
+            load_graph(path)?
+        } else {
+            let cfg = load_config()?;
+            let curation = match &self.curation {
+                Some(path) => Curation::load(path)?,
+                None => Curation::default(),
+            };
+            construct_graph(&cfg, &curation)?
+        };
+
+        let links = find_weak_links(&graph, self.min_size, self.min_balance, self.all_types);
+
+        info!(
+            "writing {} weak links to {}",
+            links.len(),
+            self.output.display()
+        );
+        let mut writer = TableWriter::open(&self.output)?;
+        for link in links {
+            writer.write_object(link)?;
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use crate::ids::codes::NS_EDITION;
+
+#[test]
+fn test_weak_links_barbell() {
+    // a triangle of editions and a triangle with ISBN 5, joined through the
+    // ISBN chain 3-4-5, with edition 8 hanging off of edition 7
+    let (mut g, n) = crate::graph::test_graph(
+        9,
+        &[
+            (0, 1),
+            (1, 2),
+            (2, 0),
+            (2, 3),
+            (3, 4),
+            (4, 5),
+            (5, 6),
+            (6, 7),
+            (7, 5),
+            (7, 8),
+        ],
+    );
+    for (i, node) in n.iter().enumerate() {
+        let ns = if (3..=5).contains(&i) {
+            &NS_ISBN
+        } else {
+            &NS_EDITION
+        };
+        g.node_weight_mut(*node).unwrap().code = ns.to_code(i as i32);
+    }
+    let isbn = |i| NS_ISBN.to_code(i);
+    let edition = |i| NS_EDITION.to_code(i);
+
+    let links = find_weak_links(&g, 5, 0.0, false);
+    assert!(links.iter().all(|l| l.node_type == "ISBN"));
+    assert!(links.windows(2).all(|w| w[0].balance >= w[1].balance));
+
+    // editions 2 and 7 are also cut points, but they are not ISBNs
+    let mut points: Vec<_> = links
+        .iter()
+        .filter(|l| l.link_type == "node")
+        .map(|l| (l.book_code, l.n_pieces, l.largest_piece, l.n_detached))
+        .collect();
+    points.sort();
+    assert_eq!(
+        points,
+        vec![(isbn(3), 2, 5, 3), (isbn(4), 2, 4, 4), (isbn(5), 2, 5, 3)]
+    );
+
+    // the bridge between editions 7 and 8 is not reported, and ISBNs come first
+    let mut bridges: Vec<_> = links
+        .iter()
+        .filter(|l| l.link_type == "edge")
+        .map(|l| (l.book_code, l.other_code.unwrap()))
+        .collect();
+    bridges.sort();
+    assert_eq!(bridges.len(), 3);
+    assert_eq!(bridges[0], (isbn(3), edition(2)));
+    assert!(bridges.contains(&(isbn(5), isbn(4))));
+    assert!(!bridges.iter().any(|(_, o)| *o == edition(8)));
+
+    // with every type, the edition cut points and bridge show up
+    let all = find_weak_links(&g, 5, 0.0, true);
+    assert_eq!(all.iter().filter(|l| l.link_type == "node").count(), 5);
+    assert_eq!(all.iter().filter(|l| l.link_type == "edge").count(), 4);
+
+    // balance and size limits filter links
+    let balanced = find_weak_links(&g, 5, 0.4, false);
+    assert_eq!(balanced.len(), 3);
+    assert!(find_weak_links(&g, 10, 0.0, true).is_empty());
+}
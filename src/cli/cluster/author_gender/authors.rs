@@ -3,6 +3,7 @@ use std::collections::HashMap;
 
 use parquet_derive::ParquetRecordReader;
 
+use super::super::viaf_ids::viaf_load_ids;
 use crate::arrow::*;
 use crate::gender::*;
 use crate::prelude::*;
@@ -16,6 +17,12 @@ pub struct AuthorInfo {
 
 pub type AuthorTable = HashMap<String, AuthorInfo>;
 
+/// VIAF author information, indexed by author name and by VIAF ID.
+pub struct AuthorTables {
+    pub names: AuthorTable,
+    pub ids: AuthorTable,
+}
+
 #[derive(Debug, ParquetRecordReader)]
 struct NameRow {
     rec_id: u32,
@@ -81,11 +88,12 @@ fn viaf_load_genders() -> Result<HashMap<u32, GenderBag>> {
 
 /// Load the VIAF author gender records.
 #[inline(never)]
-pub fn viaf_author_table() -> Result<AuthorTable> {
+pub fn viaf_author_tables() -> Result<AuthorTables> {
     let mut table = AuthorTable::new();
 
     let rec_names = viaf_load_names()?;
     let rec_genders = viaf_load_genders()?;
+    let rec_ids = viaf_load_ids()?;
 
     info!("merging gender records");
     let pb = item_progress(rec_names.len() as u64, "clusters");
@@ -103,5 +111,15 @@ pub fn viaf_author_table() -> Result<AuthorTable> {
 
     info!("merged {} gender records in {}", table.len(), timer);
 
-    Ok(table)
+    let mut ids = AuthorTable::new();
+    for (rec_id, viaf_id) in rec_ids {
+        let rec = ids.entry(viaf_id).or_default();
+        rec.n_author_recs += 1;
+        if let Some(bag) = rec_genders.get(&rec_id) {
+            rec.genders.merge_from(bag);
+        }
+    }
+    info!("indexed {} VIAF IDs", ids.len());
+
+    Ok(AuthorTables { names: table, ids })
 }
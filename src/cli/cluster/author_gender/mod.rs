@@ -3,6 +3,9 @@
 //! This script reads the cluster author information and author gender
 //! information, in order to aggregate author genders for each cluster.
 //!
+//! Authors with a VIAF ID (from OpenLibrary) are resolved to VIAF records by
+//! that ID; other authors are matched to VIAF records by name.
+//!
 //! We use a lot of left joins so that we can compute statistics across
 //! the integration pipeline.
 use std::path::{Path, PathBuf};
@@ -76,8 +79,8 @@ fn save_genders(clusters: Vec<i32>, genders: clusters::ClusterTable, outf: &Path
 impl Command for AuthorGender {
     fn exec(&self) -> Result<()> {
         let clusters = clusters::all_clusters("book-links/cluster-stats.parquet")?;
-        let name_genders = authors::viaf_author_table()?;
-        let cluster_genders = clusters::read_resolve(&self.author_file, &name_genders)?;
+        let author_genders = authors::viaf_author_tables()?;
+        let cluster_genders = clusters::read_resolve(&self.author_file, &author_genders)?;
         save_genders(clusters, cluster_genders, self.output.as_ref())?;
 
         Ok(())
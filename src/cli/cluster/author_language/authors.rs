@@ -3,6 +3,7 @@ use std::collections::HashMap;
 
 use parquet_derive::ParquetRecordReader;
 
+use super::super::viaf_ids::viaf_load_ids;
 use crate::arrow::*;
 use crate::language::*;
 use crate::prelude::*;
@@ -16,6 +17,12 @@ pub struct AuthorInfoLang {
 
 pub type AuthorLangTable = HashMap<String, AuthorInfoLang>;
 
+/// VIAF author language information, indexed by author name and by VIAF ID.
+pub struct AuthorLangTables {
+    pub names: AuthorLangTable,
+    pub ids: AuthorLangTable,
+}
+
 #[derive(Debug, ParquetRecordReader)]
 struct LanguageRow {
     rec_id: u32,
@@ -53,7 +60,6 @@ fn viaf_load_names() -> Result<HashMap<u32, Vec<String>>> {
     Ok(map)
 }
 
-
 /// Load VIAF author languages
 fn viaf_load_languages() -> Result<HashMap<u32, LanguageBag>> {
     let mut map: HashMap<u32, LanguageBag> = HashMap::new();
@@ -82,11 +88,12 @@ fn viaf_load_languages() -> Result<HashMap<u32, LanguageBag>> {
 
 /// Load the VIAF author language records.
 #[inline(never)]
-pub fn viaf_author_lang_table() -> Result<AuthorLangTable> {
+pub fn viaf_author_lang_tables() -> Result<AuthorLangTables> {
     let mut table = AuthorLangTable::new();
 
     let rec_names = viaf_load_names()?;
     let rec_languages = viaf_load_languages()?;
+    let rec_ids = viaf_load_ids()?;
 
     info!("merging gender records");
     let pb = item_progress(rec_names.len() as u64, "clusters");
@@ -104,5 +111,15 @@ pub fn viaf_author_lang_table() -> Result<AuthorLangTable> {
 
     info!("merged {} gender records in {}", table.len(), timer);
 
-    Ok(table)
+    let mut ids = AuthorLangTable::new();
+    for (rec_id, viaf_id) in rec_ids {
+        let rec = ids.entry(viaf_id).or_default();
+        rec.n_author_recs += 1;
+        if let Some(bag) = rec_languages.get(&rec_id) {
+            rec.languages.merge_from(bag);
+        }
+    }
+    info!("indexed {} VIAF IDs", ids.len());
+
+    Ok(AuthorLangTables { names: table, ids })
 }
@@ -3,13 +3,11 @@ use std::collections::HashMap;
 use std::convert::identity;
 use std::path::Path;
 
-use super::authors::AuthorLangTable;
-use crate::arrow::scan_parquet_file;
+use super::authors::AuthorLangTables;
 use crate::language::*;
 use crate::prelude::*;
 use crate::util::logging::item_progress;
 use anyhow::Result;
-use parquet_derive::ParquetRecordReader;
 use polars::prelude::*;
 
 /// Record for storing a cluster's language statistics while aggregating.
@@ -20,30 +18,39 @@ pub struct ClusterLangStats {
     pub languages: LanguageBag,
 }
 
-/// Row struct for reading cluster author names.
-#[derive(Debug, ParquetRecordReader)]
-struct ClusterAuthor {
-    cluster: i32,
-    author_name: String,
-}
-
 pub type ClusterLangTable = HashMap<i32, ClusterLangStats>;
 
 /// Read cluster author names and resolve them to language information.
-pub fn read_resolve(path: &Path, authors: &AuthorLangTable) -> Result<ClusterLangTable> {
+///
+/// Authors with a VIAF ID are resolved by that ID if VIAF has it, and by name
+/// otherwise.
+pub fn read_resolve(path: &Path, authors: &AuthorLangTables) -> Result<ClusterLangTable> {
     let timer = Timer::new();
     info!("reading cluster authors from {}", path.display());
-    let iter = scan_parquet_file(path)?;
+    let df = LazyFrame::scan_parquet(path, default())?;
+    let df = df
+        .select([col("cluster"), col("author_name"), col("author_viaf")])
+        .collect()?;
+    let clusters = df.column("cluster")?.i32()?;
+    let names = df.column("author_name")?.str()?;
+    let viaf_ids = df.column("author_viaf")?.str()?;
 
-    let pb = item_progress(iter.remaining() as u64, "authors");
+    let pb = item_progress(df.height() as u64, "authors");
 
     let mut table = ClusterLangTable::new();
+    let mut n_by_id = 0;
 
-    for row in pb.wrap_iter(iter) {
-        let row: ClusterAuthor = row?;
-        let rec = table.entry(row.cluster).or_default();
+    let rows = clusters.into_iter().zip(names).zip(viaf_ids);
+    for ((cluster, name), viaf_id) in pb.wrap_iter(rows) {
+        let cluster = cluster.ok_or_else(|| anyhow!("missing cluster"))?;
+        let rec = table.entry(cluster).or_default();
         rec.n_book_authors += 1;
-        if let Some(info) = authors.get(row.author_name.as_str()) {
+        let by_id = viaf_id.and_then(|id| authors.ids.get(id));
+        if by_id.is_some() {
+            n_by_id += 1;
+        }
+        let info = by_id.or_else(|| name.and_then(|n| authors.names.get(n)));
+        if let Some(info) = info {
             rec.n_author_recs += info.n_author_recs;
             rec.languages.merge_from(&info.languages);
         }
@@ -54,6 +61,7 @@ pub fn read_resolve(path: &Path, authors: &AuthorLangTable) -> Result<ClusterLan
         table.len(),
         timer.human_elapsed()
     );
+    info!("resolved {} of {} authors by VIAF ID", n_by_id, df.height());
 
     Ok(table)
 }
@@ -37,7 +37,11 @@ struct ClusterLanguageInfo {
     language: String,
 }
 
-fn save_languages(clusters: Vec<i32>, languages: clusters::ClusterLangTable, outf: &Path) -> Result<()> {
+fn save_languages(
+    clusters: Vec<i32>,
+    languages: clusters::ClusterLangTable,
+    outf: &Path,
+) -> Result<()> {
     info!("writing cluster languages to {}", outf.display());
     let mut out = TableWriter::open(outf)?;
 
@@ -70,8 +74,8 @@ fn save_languages(clusters: Vec<i32>, languages: clusters::ClusterLangTable, out
 impl Command for AuthorLanguage {
     fn exec(&self) -> Result<()> {
         let clusters = clusters::all_clusters("book-links/cluster-stats.parquet")?;
-        let name_languages = authors::viaf_author_lang_table()?;
-        let cluster_languages = clusters::read_resolve(&self.author_file, &name_languages)?;
+        let author_languages = authors::viaf_author_lang_tables()?;
+        let cluster_languages = clusters::read_resolve(&self.author_file, &author_languages)?;
         save_languages(clusters, cluster_languages, self.output.as_ref())?;
 
         Ok(())
@@ -1,4 +1,7 @@
 //! Extract author information for book clusters.
+//!
+//! Authors from OpenLibrary also carry their VIAF ID (`author_viaf`), when
+//! OpenLibrary records one, so they can be resolved to VIAF without matching names.
 use std::path::PathBuf;
 
 use parse_display::{Display, FromStr};
@@ -50,6 +53,12 @@ fn scan_openlib(first_only: bool) -> Result<LazyFrame> {
 
     info!("reading OL author names");
     let auth = scan_df_parquet("openlibrary/author-names.parquet")?;
+    info!("reading OL author VIAF IDs");
+    let viaf = scan_df_parquet("openlibrary/author-remote-ids.parquet")?;
+    let viaf = viaf
+        .filter(col("scheme").eq(lit("viaf")))
+        .select([col("id").alias("author"), col("value").alias("author_viaf")])
+        .unique(Some(vec!["author".into()]), UniqueKeepStrategy::First);
     let linked = icl.join(
         edl,
         [col("isbn_id")],
@@ -63,11 +72,18 @@ fn scan_openlib(first_only: bool) -> Result<LazyFrame> {
         JoinType::Inner.into(),
     );
     let linked = linked.join(auth, [col("author")], [col("id")], JoinType::Inner.into());
+    let linked = linked.join(
+        viaf,
+        [col("author")],
+        [col("author")],
+        JoinType::Left.into(),
+    );
     let authors = linked.select(vec![
         col("cluster"),
         col("name")
             .alias("author_name")
             .map(udf_clean_name, GetOutput::from_type(DataType::String)),
+        col("author_viaf"),
     ]);
 
     Ok(authors)
@@ -106,6 +122,7 @@ fn scan_loc(first_only: bool) -> Result<LazyFrame> {
     let authors = linked.select(vec![
         col("cluster"),
         col("author_name").map(udf_clean_name, GetOutput::from_type(DataType::String)),
+        lit(NULL).cast(DataType::String).alias("author_viaf"),
     ]);
 
     Ok(authors)
@@ -2,59 +2,72 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-use parquet_derive::ParquetRecordReader;
 use polars::prelude::*;
 
-use crate::arrow::*;
+use crate::graph::model::GRAPH_NODE_PATH;
+use crate::ids::codes::NS_EDITION;
 use crate::prelude::*;
-use crate::util::logging::item_progress;
 
-#[derive(Debug, ParquetRecordReader)]
-struct ClusterRow {
-    language_flag: u8,
-    cluster: i32,
-}
+/// Derive the English/other language flag from a set of language codes.
+///
+/// The flag is 0 for no languages, 1 for English only, 2 for English and
+/// others, and 3 for other languages only.
+pub fn language_flag<'a, I>(codes: I) -> u32
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut has_eng = false;
+    let mut has_other = false;
+    for code in codes {
+        if code == "eng" {
+            has_eng = true;
+        } else {
+            has_other = true;
+        }
+    }
 
-fn merge_languages(existing: u32, new: u32) -> u32 {
-    match (existing, new) {
-        (x, 0) | (0, x) => x,
-        (2, _) | (_, 2) => 2, 
-        (1, 3) | (3, 1) => 2,
-        (x,y) if x == y => x,
-        _ => existing,                // If no rule matches, keep the existing value
+    match (has_eng, has_other) {
+        (false, false) => 0,
+        (true, false) => 1,
+        (true, true) => 2,
+        (false, true) => 3,
     }
 }
 
-/// Load OpenLib Cluster-Language_flag
-/// First merge editions.parquet with edition-works.parquet
-/// Afterwards merge the result with work-cluster.parquet
+/// Load OpenLibrary cluster language flags.
+///
+/// The flags are derived from the cluster language distribution written by
+/// `cluster extract-ol-languages`.  Every cluster with an OpenLibrary edition gets
+/// a flag, with 0 for clusters whose editions list no languages.
 #[inline(never)]
 pub fn openlib_cluster_language(path: &Path) -> Result<HashMap<u32, u32>> {
+    let nodes = LazyFrame::scan_parquet(GRAPH_NODE_PATH, Default::default())?;
+    let ol_clusters = nodes
+        .filter(col("node_type").eq(lit(NS_EDITION.name())))
+        .select([col("cluster")])
+        .unique(None, UniqueKeepStrategy::Any)
+        .collect()?;
+    let mut codes: HashMap<u32, Vec<&str>> = HashMap::new();
+    for cluster in ol_clusters.column("cluster")?.i32()?.into_iter().flatten() {
+        codes.insert(cluster as u32, Vec::new());
+    }
+
     let lf = LazyFrame::scan_parquet(path, Default::default())?;
-    info!("schema {:?}", lf.schema()?);
-    let df = lf.collect()?;
-    let mut map: HashMap<u32, u32> = HashMap::new();
-    let rows: Vec<ClusterRow> = df.column("language_flag").unwrap().u8().unwrap().into_iter()
-    .zip(df.column("cluster").unwrap().i32().unwrap().into_iter())
-    .map(|(language_flag, cluster)| {
-        ClusterRow {
-            language_flag: language_flag.unwrap(),
-            cluster: cluster.unwrap(),
+    let df = lf.select([col("cluster"), col("language")]).collect()?;
+    let clusters = df.column("cluster")?.i32()?;
+    let langs = df.column("language")?.str()?;
+
+    for (cluster, lang) in clusters.into_iter().zip(langs) {
+        if let (Some(cluster), Some(lang)) = (cluster, lang) {
+            codes.entry(cluster as u32).or_default().push(lang);
         }
-    })
-    .collect();
-    
-    info!("{}", rows.len());
-    for row in rows {
-        map
-            .entry(row.cluster as u32)
-            .and_modify(|existing_language| {
-                // Merge with existing language
-                *existing_language = merge_languages(*existing_language, row.language_flag as u32);
-            })
-            .or_insert(row.language_flag as u32); // Insert if not already in the map
     }
-    info!("merged {} cluster-ol-lan records", map.len());
+
+    let map: HashMap<u32, u32> = codes
+        .into_iter()
+        .map(|(cluster, langs)| (cluster, language_flag(langs)))
+        .collect();
+    info!("derived language flags for {} clusters", map.len());
 
     Ok(map)
 }
@@ -69,11 +82,12 @@ pub struct TranslationClusterRow {
 pub type TranslationClusterTable = HashMap<u32, String>;
 
 // Load Loc Translation Status
-/// First merge 
+/// First merge
 /// Afterwards merge the result with work-cluster.parquet
 #[inline(never)]
 pub fn loc_cluster_translation() -> Result<TranslationClusterTable> {
-    let translation_rec_lf = LazyFrame::scan_parquet("loc-mds/book-languages.parquet", Default::default())?;
+    let translation_rec_lf =
+        LazyFrame::scan_parquet("loc-mds/book-languages.parquet", Default::default())?;
     let translation_rec_df = translation_rec_lf.collect()?;
 
     let rec_ids_lf = LazyFrame::scan_parquet("loc-mds/book-isbn-ids.parquet", Default::default())?;
@@ -83,22 +97,42 @@ pub fn loc_cluster_translation() -> Result<TranslationClusterTable> {
 
     // Perform an inner join on the "rec_id" column
     info!("Perform an inner join on the rec_id column");
-    let translation_ids_df = translation_rec_df.join(&rec_ids_df, ["rec_id"], ["rec_id"], JoinType::Inner.into())?;
+    let translation_ids_df =
+        translation_rec_df.join(&rec_ids_df, ["rec_id"], ["rec_id"], JoinType::Inner.into())?;
     let translation_ids_df = translation_ids_df.drop("rec_id")?;
 
-    let isbn_clusters_lf = LazyFrame::scan_parquet("book-links/isbn-clusters.parquet", Default::default())?;
+    let isbn_clusters_lf =
+        LazyFrame::scan_parquet("book-links/isbn-clusters.parquet", Default::default())?;
     let isbn_clusters_df = isbn_clusters_lf.collect()?;
 
     // Perform inner join on the "isbn_id" column
     info!("Perform an inner join on the isbn_id column");
-    let translation_clusters_df = translation_ids_df.join(&isbn_clusters_df, ["isbn_id"], ["isbn_id"], JoinType::Inner.into())?;
+    let translation_clusters_df = translation_ids_df.join(
+        &isbn_clusters_df,
+        ["isbn_id"],
+        ["isbn_id"],
+        JoinType::Inner.into(),
+    )?;
     let translation_clusters_df = translation_clusters_df.drop("isbn_id")?;
     let translation_clusters_df = translation_clusters_df.drop("isbn")?;
 
     // Extract columns and map to Vec<TranslationClusterRow>
-    let rows: Vec<TranslationClusterRow> = translation_clusters_df.column("cluster")?.i32()?.into_iter()
-        .zip(translation_clusters_df.column("original_language")?.str()?.into_iter())
-        .zip(translation_clusters_df.column("translated_language")?.str()?.into_iter())
+    let rows: Vec<TranslationClusterRow> = translation_clusters_df
+        .column("cluster")?
+        .i32()?
+        .into_iter()
+        .zip(
+            translation_clusters_df
+                .column("original_language")?
+                .str()?
+                .into_iter(),
+        )
+        .zip(
+            translation_clusters_df
+                .column("translated_language")?
+                .str()?
+                .into_iter(),
+        )
         .map(|((cluster, original_language), translated_language)| {
             TranslationClusterRow {
                 cluster: cluster.unwrap() as u32, // Convert i32 to u32
@@ -141,9 +175,8 @@ pub fn loc_cluster_translation() -> Result<TranslationClusterTable> {
         } else {
             value = "unknown".to_string();
         }
-        
-        map
-            .entry(row.cluster)
+
+        map.entry(row.cluster)
             .and_modify(|existing_value| {
                 if existing_value == "unknown" {
                     // Preserve the existing value if it's "unknown"
@@ -165,7 +198,6 @@ pub struct WorkLanguageRow {
     pub language_loc: Option<String>,
     pub language_author: Option<String>,
     pub language_ol: Option<i32>,
-
 }
 
 impl WorkLanguageRow {
@@ -174,16 +206,36 @@ impl WorkLanguageRow {
         let mut result_loc: String = "unknown".to_string();
         let mut result_author: String = "unknown".to_string();
         let mut result_ol: String = "unknown".to_string();
-    
-        if self.language_loc.as_ref().map(|s| s == "eng").unwrap_or(false) {
+
+        if self
+            .language_loc
+            .as_ref()
+            .map(|s| s == "eng")
+            .unwrap_or(false)
+        {
             result_loc = "eng".to_string();
-        } else if self.language_loc.as_ref().map(|s| s == "eng").unwrap_or(false) {
+        } else if self
+            .language_loc
+            .as_ref()
+            .map(|s| s == "eng")
+            .unwrap_or(false)
+        {
             result_loc = "other".to_string();
         }
 
-        if self.language_author.as_ref().map(|s| s == "eng").unwrap_or(false) {
+        if self
+            .language_author
+            .as_ref()
+            .map(|s| s == "eng")
+            .unwrap_or(false)
+        {
             result_author = "eng".to_string();
-        } else if self.language_author.as_ref().map(|s| s == "other").unwrap_or(false) {
+        } else if self
+            .language_author
+            .as_ref()
+            .map(|s| s == "other")
+            .unwrap_or(false)
+        {
             result_author = "other".to_string();
         }
 
@@ -196,12 +248,18 @@ impl WorkLanguageRow {
             };
         }
 
-        match (result_loc.as_str(), result_author.as_str(), result_ol.as_str()) {
+        match (
+            result_loc.as_str(),
+            result_author.as_str(),
+            result_ol.as_str(),
+        ) {
             ("eng", _, _) | (_, "eng", _) => "eng-original".to_string(),
             ("other", _, "eng") | ("other", _, "both") => "other-translated".to_string(),
             (_, "other", "eng") | (_, "other", "both") => "other-translated".to_string(),
-            ("other", "unknown", "other") | ("unknown", "other", "other") | ("other", "other", "other") => "other-translation-not-found".to_string(),
-            (_, _ , "eng") => "eng-original".to_string(),
+            ("other", "unknown", "other")
+            | ("unknown", "other", "other")
+            | ("other", "other", "other") => "other-translation-not-found".to_string(),
+            (_, _, "eng") => "eng-original".to_string(),
             (_, _, "other") => "other-translation-not-found".to_string(),
             (_, _, "both") => "ambiguous".to_string(),
             _ => "unknown".to_string(),
@@ -211,14 +269,14 @@ impl WorkLanguageRow {
     fn merge(&mut self, other: WorkLanguageRow) {
         // merge language loc
         match (&self.language_loc, other.language_loc) {
-            (Some(_cur_loc), None) => {},
+            (Some(_cur_loc), None) => {}
             (None, Some(new_loc)) => self.language_loc = Some(new_loc),
             (Some(cur_loc), Some(new_loc)) => {
                 if *cur_loc == "ambiguous".to_string() || *cur_loc == "unknown".to_string() {
-                    self.language_loc = Some(new_loc); 
-                } else if *cur_loc == "other".to_string() &&  new_loc == "eng".to_string() {
+                    self.language_loc = Some(new_loc);
+                } else if *cur_loc == "other".to_string() && new_loc == "eng".to_string() {
                     info!("Work id {} has eng and other!", self.work_id);
-                } else if *cur_loc == "eng".to_string() &&  new_loc == "other".to_string() {
+                } else if *cur_loc == "eng".to_string() && new_loc == "other".to_string() {
                     info!("Work id {} has eng and other!", self.work_id);
                 }
             }
@@ -227,58 +285,81 @@ impl WorkLanguageRow {
 
         // merge language author
         match (&self.language_author, other.language_author) {
-            (Some(_cur_author), None) => {},
+            (Some(_cur_author), None) => {}
             (None, Some(new_author)) => self.language_author = Some(new_author),
             (Some(cur_author), Some(new_author)) => {
-                if *cur_author == "other".to_string() &&  new_author == "eng".to_string() {
+                if *cur_author == "other".to_string() && new_author == "eng".to_string() {
                     info!("Work id {} has eng and other!", self.work_id);
-                } else if *cur_author == "eng".to_string() &&  new_author == "other".to_string() {
+                } else if *cur_author == "eng".to_string() && new_author == "other".to_string() {
                     info!("Work id {} has eng and other!", self.work_id);
                 } else if new_author == "eng".to_string() || new_author == "other".to_string() {
                     self.language_author = Some(new_author);
                 }
-            },
+            }
             (_, _) => {}
         }
 
         // merge language ol
-        match(&self.language_ol, other.language_ol) {
+        match (&self.language_ol, other.language_ol) {
             (None, Some(x)) => self.language_ol = Some(x),
-            (Some(_x), Some(0)) => {},
+            (Some(_x), Some(0)) => {}
             (Some(0), Some(x)) => self.language_ol = Some(x),
-            (Some(2), _) | (_, Some(2)) => self.language_ol = Some(2), 
+            (Some(2), _) | (_, Some(2)) => self.language_ol = Some(2),
             (Some(1), Some(3)) | (Some(3), Some(1)) => self.language_ol = Some(2),
-            (Some(x),Some(y)) if *x == y => {},
-            _ => {},                // If no rule matches, keep the existing value
+            (Some(x), Some(y)) if *x == y => {}
+            _ => {} // If no rule matches, keep the existing value
         }
     }
 }
 
 #[inline(never)]
 pub fn cluster_derive_language() -> Result<HashMap<u32, WorkLanguageRow>> {
-    let author_lf = LazyFrame::scan_parquet("book-links/cluster-languages.parquet", Default::default())?;
+    let author_lf =
+        LazyFrame::scan_parquet("book-links/cluster-languages.parquet", Default::default())?;
     let mut author_df = author_lf.collect()?;
     let author_df = author_df.rename("language", "language_author")?;
 
-    let ol_lf = LazyFrame::scan_parquet("book-links/cluster-ol-work-language.parquet", Default::default())?;
+    let ol_lf = LazyFrame::scan_parquet(
+        "book-links/cluster-ol-work-language.parquet",
+        Default::default(),
+    )?;
     let mut ol_df = ol_lf.collect()?;
     let ol_df = ol_df.rename("language", "language_ol")?;
 
     info!("Perform an outer join author-ol");
-    let author_ol_df = author_df.join(&ol_df, ["cluster"], ["cluster"], JoinType::Outer{ coalesce: true }.into())?;
-
-    let loc_lf = LazyFrame::scan_parquet("book-links/cluster-loc-translations.parquet", Default::default())?;
+    let author_ol_df = author_df.join(
+        &ol_df,
+        ["cluster"],
+        ["cluster"],
+        JoinType::Outer { coalesce: true }.into(),
+    )?;
+
+    let loc_lf = LazyFrame::scan_parquet(
+        "book-links/cluster-loc-translations.parquet",
+        Default::default(),
+    )?;
     let mut loc_df = loc_lf.collect()?;
     let loc_df = loc_df.rename("loc_original_language", "language_loc")?;
 
     info!("Perform an outer join author-ol-loc");
-    let author_ol_loc_df = author_ol_df.join(&loc_df, ["cluster"], ["cluster"], JoinType::Outer{ coalesce: true }.into())?;
-
-    let book_links_lf = LazyFrame::scan_parquet("goodreads/gr-book-link.parquet", Default::default())?;
+    let author_ol_loc_df = author_ol_df.join(
+        &loc_df,
+        ["cluster"],
+        ["cluster"],
+        JoinType::Outer { coalesce: true }.into(),
+    )?;
+
+    let book_links_lf =
+        LazyFrame::scan_parquet("goodreads/gr-book-link.parquet", Default::default())?;
     let book_links_df = book_links_lf.collect()?;
- 
+
     info!("Perform an outer join author-ol-loc-gr-booklinks");
-    let merged_df = author_ol_loc_df.join(&book_links_df, ["cluster"], ["cluster"], JoinType::Inner.into())?;
+    let merged_df = author_ol_loc_df.join(
+        &book_links_df,
+        ["cluster"],
+        ["cluster"],
+        JoinType::Inner.into(),
+    )?;
     let merged_df = merged_df.filter(&merged_df.column("work_id")?.is_not_null())?;
 
     let has_none = merged_df.column("work_id")?.is_null().any();
@@ -298,34 +379,35 @@ pub fn cluster_derive_language() -> Result<HashMap<u32, WorkLanguageRow>> {
         .zip(merged_df.column("language_loc")?.str()?.into_iter())
         .zip(merged_df.column("language_author")?.str()?.into_iter())
         .zip(merged_df.column("language_ol")?.i32()?.into_iter())
-        .map(|(((work_id, language_loc), language_author), language_ol)| {
-            let invalid_authors = ["no-book-author", "no-author-rec", "no-language"];
-
-            // Convert language_author to None if it matches any of the invalid values
-            let language_author = language_author.and_then(|s| {
-                if invalid_authors.contains(&s) {
-                    None
-                } else {
-                    Some(s.to_string())
+        .map(
+            |(((work_id, language_loc), language_author), language_ol)| {
+                let invalid_authors = ["no-book-author", "no-author-rec", "no-language"];
+
+                // Convert language_author to None if it matches any of the invalid values
+                let language_author = language_author.and_then(|s| {
+                    if invalid_authors.contains(&s) {
+                        None
+                    } else {
+                        Some(s.to_string())
+                    }
+                });
+
+                WorkLanguageRow {
+                    work_id: work_id.unwrap() as u32, // Convert i32 to u32
+                    language_loc: language_loc.map(|s| s.to_string()), // Convert Option<&str> to Option<String>
+                    language_author: language_author, // Convert Option<&str> to Option<String>
+                    language_ol: language_ol,         // Already Option<u8>
                 }
-            });
-
-            WorkLanguageRow {
-                work_id: work_id.unwrap() as u32, // Convert i32 to u32
-                language_loc: language_loc.map(|s| s.to_string()), // Convert Option<&str> to Option<String>
-                language_author: language_author, // Convert Option<&str> to Option<String>
-                language_ol: language_ol, // Already Option<u8>
-            }
-        })
+            },
+        )
         .collect();
 
-    
     // let mut frequency: HashMap<u32, u32> = HashMap::new();
 
     // for row in &rows {
     //     let count = frequency.entry(row.work_id).or_insert(0);
     //     *count += 1;
-        
+
     //     if *count > 2 {
     //         println!("work_id {} appears more than twice!", row.work_id);
     //     }
@@ -349,17 +431,20 @@ pub fn cluster_derive_language() -> Result<HashMap<u32, WorkLanguageRow>> {
 pub struct GRItemLanguageRow {
     pub gr_item: u32,
     pub deduced_language: String,
-
 }
 
 impl GRItemLanguageRow {
     fn merge(&mut self, other: GRItemLanguageRow) {
         //other-not-found into other-translation
-        if self.deduced_language == "other-translation-not-found".to_string() && other.deduced_language == "other-translated".to_string() {
-           self.deduced_language = other.deduced_language;
+        if self.deduced_language == "other-translation-not-found".to_string()
+            && other.deduced_language == "other-translated".to_string()
+        {
+            self.deduced_language = other.deduced_language;
         } else
         // language ambiguous
-        if self.deduced_language == "ambiguous".to_string() && other.deduced_language != "unknown".to_string() {
+        if self.deduced_language == "ambiguous".to_string()
+            && other.deduced_language != "unknown".to_string()
+        {
             self.deduced_language = other.deduced_language;
         } else
         // language unknown
@@ -375,29 +460,34 @@ pub struct GRRatingLanguageRow {
     pub user_item: u32,
     pub rating: u32,
     pub deduced_language: String,
-
 }
 
 #[inline(never)]
 pub fn gr_work_to_id() -> Result<Vec<GRRatingLanguageRow>> {
-    let gr_item_languages_lf = LazyFrame::scan_parquet("goodreads/gr-item-languages.parquet", Default::default())?;
+    let gr_item_languages_lf =
+        LazyFrame::scan_parquet("goodreads/gr-item-languages.parquet", Default::default())?;
     let gr_item_languages_df = gr_item_languages_lf.collect()?;
 
     info!("Prepare rows for iteration...");
     println!("{:?}", gr_item_languages_df.schema());
     println!("Number of rows merged: {}", gr_item_languages_df.height());
     let rows: Vec<GRItemLanguageRow> = gr_item_languages_df
-    .column("gr_item")?
-    .i32()?
-    .into_iter()
-    .zip(gr_item_languages_df.column("deduced_language")?.str()?.into_iter())
-    .map(|(gr_item, deduced_language)| {
-        GRItemLanguageRow {
-            gr_item: gr_item.unwrap() as u32, // Convert i32 to u32
-            deduced_language: deduced_language.unwrap().to_string(), // Convert Option<&str> to Option<String>
-        }
-    })
-    .collect();
+        .column("gr_item")?
+        .i32()?
+        .into_iter()
+        .zip(
+            gr_item_languages_df
+                .column("deduced_language")?
+                .str()?
+                .into_iter(),
+        )
+        .map(|(gr_item, deduced_language)| {
+            GRItemLanguageRow {
+                gr_item: gr_item.unwrap() as u32, // Convert i32 to u32
+                deduced_language: deduced_language.unwrap().to_string(), // Convert Option<&str> to Option<String>
+            }
+        })
+        .collect();
 
     let mut map: HashMap<u32, GRItemLanguageRow> = HashMap::new();
     for row in rows {
@@ -412,7 +502,8 @@ pub fn gr_work_to_id() -> Result<Vec<GRRatingLanguageRow>> {
 
     info!("length: {}", map.len());
 
-    let gr_ratings_lf = LazyFrame::scan_parquet("goodreads/gr-work-ratings.parquet", Default::default())?;
+    let gr_ratings_lf =
+        LazyFrame::scan_parquet("goodreads/gr-work-ratings.parquet", Default::default())?;
     let gr_ratings_df = gr_ratings_lf.collect()?;
 
     info!("Prepare rows for iteration...");
@@ -420,22 +511,33 @@ pub fn gr_work_to_id() -> Result<Vec<GRRatingLanguageRow>> {
     println!("Number of rows merged: {}", gr_ratings_df.height());
 
     let rows: Vec<GRRatingLanguageRow> = gr_ratings_df
-    .column("user")?
-    .i32()?
-    .into_iter()
-    .zip(gr_ratings_df.column("item")?.i32()?.into_iter())
-    .zip(gr_ratings_df.column("rating")?.f32()?.into_iter())
-    .map(|((user, item), rating)| {
-        GRRatingLanguageRow {
-            gr_item: item.unwrap() as u32, // Convert i32 to u32
-            user_item: user.unwrap() as u32,
-            rating: rating.unwrap() as u32,
-            deduced_language: map.get(&(item.unwrap() as u32)).map(|s| s.deduced_language.clone()).unwrap_or("unknown".to_string()),
-            //deduced_language: map.get(&(item.unwrap() as u32)).unrwap().deduced_language.cloned().unwrap_or_else(|| "unknown".to_string()),
-        }
-    })
-    .collect();
+        .column("user")?
+        .i32()?
+        .into_iter()
+        .zip(gr_ratings_df.column("item")?.i32()?.into_iter())
+        .zip(gr_ratings_df.column("rating")?.f32()?.into_iter())
+        .map(|((user, item), rating)| {
+            GRRatingLanguageRow {
+                gr_item: item.unwrap() as u32, // Convert i32 to u32
+                user_item: user.unwrap() as u32,
+                rating: rating.unwrap() as u32,
+                deduced_language: map
+                    .get(&(item.unwrap() as u32))
+                    .map(|s| s.deduced_language.clone())
+                    .unwrap_or("unknown".to_string()),
+                //deduced_language: map.get(&(item.unwrap() as u32)).unrwap().deduced_language.cloned().unwrap_or_else(|| "unknown".to_string()),
+            }
+        })
+        .collect();
 
     info!("length: {}", rows.len());
     Ok(rows)
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_language_flag() {
+    assert_eq!(language_flag([]), 0);
+    assert_eq!(language_flag(["eng"]), 1);
+    assert_eq!(language_flag(["eng", "fre"]), 2);
+    assert_eq!(language_flag(["fre", "ger"]), 3);
+}
@@ -5,8 +5,8 @@
 //!
 //! We use a lot of left joins so that we can compute statistics across
 //! the integration pipeline.
-use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use parquet_derive::ParquetRecordWriter;
 use serde::{Deserialize, Serialize};
@@ -24,7 +24,7 @@ pub struct ClusterOLLanguage {
     /// Specify output file
     #[arg(short = 'o', long = "output")]
     output: PathBuf,
-    /// Specify the cluster-author file.
+    /// Specify the cluster OpenLibrary language distribution file.
     #[arg(short = 'A', long = "cluster-ol-lang")]
     cluster_ol_lang_file: PathBuf,
 }
@@ -72,7 +72,10 @@ fn save_languages(cluster_languages: HashMap<u32, u32>, outf: &Path) -> Result<(
     let mut out = TableWriter::open(outf)?;
 
     for (cluster, language) in cluster_languages {
-        out.write_object(ClusterLanguageInfo { cluster: cluster.try_into().unwrap(), language: language.try_into().unwrap() })?;
+        out.write_object(ClusterLanguageInfo {
+            cluster: cluster.try_into().unwrap(),
+            language: language.try_into().unwrap(),
+        })?;
     }
 
     out.finish()?;
@@ -80,12 +83,18 @@ fn save_languages(cluster_languages: HashMap<u32, u32>, outf: &Path) -> Result<(
     Ok(())
 }
 
-fn save_translations(cluster_translations: clusters::TranslationClusterTable, outf: &Path) -> Result<()> {
+fn save_translations(
+    cluster_translations: clusters::TranslationClusterTable,
+    outf: &Path,
+) -> Result<()> {
     info!("writing cluster translations to {}", outf.display());
     let mut out = TableWriter::open(outf)?;
 
     for (cluster, original_language) in cluster_translations {
-        out.write_object(ClusterTranslationInfo { cluster: cluster.try_into().unwrap(), loc_original_language: original_language.try_into().unwrap() })?;
+        out.write_object(ClusterTranslationInfo {
+            cluster: cluster.try_into().unwrap(),
+            loc_original_language: original_language.try_into().unwrap(),
+        })?;
     }
 
     out.finish()?;
@@ -101,14 +110,20 @@ struct GRWorkLanguage {
     language_ol: Option<i32>,
 }
 
-fn save_merged_languages(work_merged_languages: HashMap<u32, clusters::WorkLanguageRow>, outf: &Path) -> Result<()> {
+fn save_merged_languages(
+    work_merged_languages: HashMap<u32, clusters::WorkLanguageRow>,
+    outf: &Path,
+) -> Result<()> {
     info!("writing merged_languages to {}", outf.display());
     let mut out = TableWriter::open(outf)?;
 
     for (work_id, languages) in work_merged_languages {
-        out.write_object(GRWorkLanguage { work_id: work_id.try_into().unwrap(), language_loc: languages.language_loc.try_into()?,
-                                          language_author: languages.language_author.try_into()?, language_ol: languages.language_ol.try_into()?,
-                                         })?;
+        out.write_object(GRWorkLanguage {
+            work_id: work_id.try_into().unwrap(),
+            language_loc: languages.language_loc.try_into()?,
+            language_author: languages.language_author.try_into()?,
+            language_ol: languages.language_ol.try_into()?,
+        })?;
     }
     out.finish()?;
 
@@ -121,12 +136,18 @@ struct GRWorkDeducedLanguage {
     deduced_language: String,
 }
 
-fn save_deduced_languages(work_merged_languages: HashMap<u32, clusters::WorkLanguageRow>, outf: &Path) -> Result<()> {
+fn save_deduced_languages(
+    work_merged_languages: HashMap<u32, clusters::WorkLanguageRow>,
+    outf: &Path,
+) -> Result<()> {
     info!("writing deduced_languages to {}", outf.display());
     let mut out = TableWriter::open(outf)?;
 
     for (work_id, languages) in work_merged_languages {
-        out.write_object(GRWorkDeducedLanguage { work_id: work_id.try_into().unwrap(), deduced_language: languages.deduce_language().try_into()?, })?;
+        out.write_object(GRWorkDeducedLanguage {
+            work_id: work_id.try_into().unwrap(),
+            deduced_language: languages.deduce_language().try_into()?,
+        })?;
     }
     out.finish()?;
 
@@ -141,15 +162,20 @@ struct GRItemRatingLanguage {
     deduced_language: String,
 }
 
-fn save_ratings_with_languages(gr_ratings_language: Vec<clusters::GRRatingLanguageRow>, outf: &Path) -> Result<()> {
+fn save_ratings_with_languages(
+    gr_ratings_language: Vec<clusters::GRRatingLanguageRow>,
+    outf: &Path,
+) -> Result<()> {
     info!("writing ratings with languages to {}", outf.display());
     let mut out = TableWriter::open(outf)?;
 
     for row in gr_ratings_language {
-        out.write_object(GRItemRatingLanguage { user: row.user_item.try_into().unwrap(),
-                                                item: row.gr_item.try_into().unwrap(),
-                                                rating: row.rating.try_into().unwrap(),
-                                                deduced_language: row.deduced_language.try_into()?, })?;
+        out.write_object(GRItemRatingLanguage {
+            user: row.user_item.try_into().unwrap(),
+            item: row.gr_item.try_into().unwrap(),
+            rating: row.rating.try_into().unwrap(),
+            deduced_language: row.deduced_language.try_into()?,
+        })?;
     }
     out.finish()?;
 
@@ -189,4 +215,4 @@ impl Command for GRRatingLanguage {
         save_ratings_with_languages(gr_ratings_language, self.output.as_ref())?;
         Ok(())
     }
-}
\ No newline at end of file
+}
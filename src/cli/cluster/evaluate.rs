@@ -0,0 +1,162 @@
+//! Evaluate clusters against source work groupings.
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::PathBuf;
+
+use parse_display::{Display, FromStr};
+
+use crate::graph::evaluate::{evaluate, recluster_without, ClusterEval};
+use crate::graph::model::GRAPH_NODE_PATH;
+use crate::ids::codes::*;
+use crate::prelude::*;
+use polars::prelude::*;
+
+#[derive(Display, FromStr, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Reference {
+    #[display("gr-works")]
+    GRWorks,
+    #[display("ol-works")]
+    OLWorks,
+}
+
+#[derive(Args, Debug)]
+#[command(name = "evaluate")]
+/// Evaluate the book clusters against GoodReads or OpenLibrary works.
+///
+/// When a reference's work edges are part of the book graph, the records are
+/// evaluated against clusters rebuilt without those edges, so that recall measures
+/// how well the other sources recover the works.
+pub struct EvaluateClusters {
+    /// Evaluate against REF (gr-works or ol-works; repeatable).
+    #[arg(
+        short = 'r',
+        long = "reference",
+        name = "REF",
+        default_value = "gr-works"
+    )]
+    references: Vec<Reference>,
+
+    /// Write the scores as JSON to FILE.
+    #[arg(
+        short = 'o',
+        long = "output",
+        name = "FILE",
+        default_value = "book-links/cluster-eval.json"
+    )]
+    output: PathBuf,
+}
+
+/// Scan the graph nodes with their clusters, optionally reclustered without a source.
+fn scan_nodes(held_out: Option<&str>) -> Result<LazyFrame> {
+    if let Some(source) = held_out {
+        info!("reclustering without {} edges", source);
+        Ok(recluster_without(source)?.lazy())
+    } else {
+        Ok(LazyFrame::scan_parquet(GRAPH_NODE_PATH, default())?)
+    }
+}
+
+/// Scan the GoodReads books with their works and clusters.
+fn scan_gr_works(nodes: LazyFrame) -> Result<LazyFrame> {
+    let nodes = nodes
+        .filter(col("node_type").eq(lit(NS_GR_BOOK.name())))
+        .select([
+            (col("book_code") - lit(NS_GR_BOOK.base())).alias("book_id"),
+            col("cluster"),
+        ]);
+    let links = LazyFrame::scan_parquet("goodreads/gr-book-link.parquet", default())?
+        .select([col("book_id"), col("work_id").alias("class")]);
+    Ok(nodes.join(
+        links,
+        [col("book_id")],
+        [col("book_id")],
+        JoinType::Inner.into(),
+    ))
+}
+
+/// Scan the OpenLibrary editions with their works and clusters.
+fn scan_ol_works(nodes: LazyFrame) -> Result<LazyFrame> {
+    let nodes = nodes
+        .filter(col("node_type").eq(lit(NS_EDITION.name())))
+        .select([
+            (col("book_code") - lit(NS_EDITION.base())).alias("edition"),
+            col("cluster"),
+        ]);
+    let works = LazyFrame::scan_parquet("openlibrary/edition-works.parquet", default())?.select([
+        col("edition").cast(DataType::Int32),
+        col("work").cast(DataType::Int32).alias("class"),
+    ]);
+    Ok(nodes.join(
+        works,
+        [col("edition")],
+        [col("edition")],
+        JoinType::Inner.into(),
+    ))
+}
+
+/// Load the (cluster, class) pairs for a reference.
+fn load_pairs(reference: Reference, held_out: Option<&str>) -> Result<Vec<(i32, i32)>> {
+    let nodes = scan_nodes(held_out)?;
+    let df = match reference {
+        Reference::GRWorks => scan_gr_works(nodes)?,
+        Reference::OLWorks => scan_ol_works(nodes)?,
+    };
+    let df = df
+        .select([
+            col("cluster").cast(DataType::Int32),
+            col("class").cast(DataType::Int32),
+        ])
+        .drop_nulls(None)
+        .collect()?;
+    let clusters = df.column("cluster")?.i32()?;
+    let classes = df.column("class")?.i32()?;
+    Ok(clusters
+        .into_iter()
+        .zip(classes)
+        .filter_map(|(c, r)| Some((c?, r?)))
+        .collect())
+}
+
+impl Command for EvaluateClusters {
+    fn exec(&self) -> Result<()> {
+        require_working_root()?;
+        let cfg = load_config()?;
+
+        let mut results: BTreeMap<String, ClusterEval> = BTreeMap::new();
+        for reference in &self.references {
+            let (source, default) = match reference {
+                Reference::GRWorks => ("GRWorks", cfg.goodreads.enabled),
+                Reference::OLWorks => ("OLWorks", true),
+            };
+            let held_out = if cfg.graph.builtin_enabled(source, default) {
+                Some(source)
+            } else {
+                None
+            };
+
+            info!("loading records with {}", reference);
+            let pairs = load_pairs(*reference, held_out)?;
+            info!("evaluating {} records against {}", pairs.len(), reference);
+            let eval = evaluate(pairs);
+            info!(
+                "{}: pairwise P={:.4} R={:.4} F1={:.4}",
+                reference, eval.pairwise.precision, eval.pairwise.recall, eval.pairwise.f1
+            );
+            info!(
+                "{}: B-cubed P={:.4} R={:.4} F1={:.4}",
+                reference, eval.bcubed.precision, eval.bcubed.recall, eval.bcubed.f1
+            );
+            info!(
+                "{}: purity {:.4}, inverse purity {:.4}",
+                reference, eval.purity, eval.inverse_purity
+            );
+            results.insert(reference.to_string(), eval);
+        }
+
+        info!("saving scores to {}", self.output.display());
+        let out = File::create(&self.output)?;
+        serde_json::to_writer_pretty(out, &results)?;
+
+        Ok(())
+    }
+}
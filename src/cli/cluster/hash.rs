@@ -1,17 +1,49 @@
-//! Extract author information for book clusters.
-use std::collections::HashMap;
+//! Compute hashes of the members of book clusters.
+//!
+//! A cluster's hash digests its sorted members (ISBNs or book codes), so it
+//! identifies the cluster's contents independently of its cluster ID.  Bits of
+//! the hash give stable, content-based assignments for splitting items.
 use std::path::PathBuf;
 
-use hex;
 use md5::{Digest, Md5};
-use parquet_derive::ParquetRecordWriter;
+use parse_display::{Display, FromStr};
+use sha2::Sha256;
+use xxhash_rust::xxh3::Xxh3;
 
-use crate::arrow::*;
+use crate::cleaning::isbns::isbn13;
 use crate::prelude::*;
 use polars::prelude::*;
 
 use crate::prelude::Result;
 
+#[derive(Display, FromStr, Debug, Clone, Copy, PartialEq, Eq)]
+#[display(style = "lowercase")]
+enum HashAlgo {
+    Md5,
+    Sha256,
+    Blake3,
+    Xxh3,
+}
+
+#[derive(Display, FromStr, Debug, Clone, Copy, PartialEq, Eq)]
+#[display(style = "lowercase")]
+enum Members {
+    /// Hash the cluster's ISBNs, from the ISBN cluster file.
+    Isbns,
+    /// Hash the cluster's book codes, from the graph node file.
+    Codes,
+}
+
+impl Members {
+    /// The name of the member column, also used to prefix the output columns.
+    fn column(self) -> &'static str {
+        match self {
+            Members::Isbns => "isbn",
+            Members::Codes => "code",
+        }
+    }
+}
+
 #[derive(Args, Debug)]
 #[command(name = "hash")]
 /// Compute a hash for each cluster.
@@ -20,66 +52,240 @@ pub struct HashCmd {
     #[arg(short = 'o', long = "output", name = "FILE")]
     output: PathBuf,
 
-    /// Specify input file
-    #[arg(name = "ISBN_CLUSTERS")]
+    /// Hash with ALGO (md5, sha256, blake3, or xxh3).
+    #[arg(short = 'a', long = "algorithm", name = "ALGO", default_value = "md5")]
+    algorithm: HashAlgo,
+
+    /// Hash MEMBERS of each cluster (isbns or codes).
+    #[arg(
+        short = 'm',
+        long = "members",
+        name = "MEMBERS",
+        default_value = "isbns"
+    )]
+    members: Members,
+
+    /// Convert ISBN-10s to ISBN-13 before hashing.
+    #[arg(long = "isbn13")]
+    isbn13: bool,
+
+    /// Assign each cluster to one of K buckets by its hash.
+    #[arg(short = 'k', long = "buckets", name = "K")]
+    buckets: Option<u32>,
+
+    /// Specify input file (ISBN clusters, or graph nodes for codes)
+    #[arg(name = "CLUSTER_FILE")]
     cluster_file: PathBuf,
 }
 
-#[derive(ParquetRecordWriter)]
-struct ClusterHash {
-    cluster: i32,
-    isbn_hash: String,
-    isbn_dcode: i8,
+/// Incremental state for the supported hash algorithms.
+enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<Xxh3>),
 }
 
-/// Load ISBN data
-fn scan_isbns(path: &Path) -> Result<LazyFrame> {
+impl Hasher {
+    fn new(algo: HashAlgo) -> Hasher {
+        match algo {
+            HashAlgo::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgo::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgo::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgo::Xxh3 => Hasher::Xxh3(Box::new(Xxh3::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Xxh3(h) => h.update(data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            Hasher::Xxh3(h) => h.digest128().to_be_bytes().to_vec(),
+        }
+    }
+}
+
+/// Hash the members of each cluster, from `(cluster, member)` rows sorted by cluster.
+fn hash_clusters<T, I>(rows: I, algo: HashAlgo) -> Vec<(i32, Vec<u8>)>
+where
+    T: AsRef<[u8]>,
+    I: IntoIterator<Item = (i32, T)>,
+{
+    let mut hashes = Vec::new();
+    let mut cur: Option<(i32, Hasher)> = None;
+    for (cluster, member) in rows {
+        match &mut cur {
+            Some((c, h)) if *c == cluster => h.update(member.as_ref()),
+            _ => {
+                if let Some((c, h)) = cur.take() {
+                    hashes.push((c, h.finish()));
+                }
+                let mut h = Hasher::new(algo);
+                h.update(member.as_ref());
+                cur = Some((cluster, h));
+            }
+        }
+    }
+    if let Some((c, h)) = cur {
+        hashes.push((c, h.finish()));
+    }
+    hashes
+}
+
+/// Pick a hash's bucket from its leading 8 bytes.
+fn bucket(hash: &[u8], k: u32) -> u32 {
+    let mut lead = [0u8; 8];
+    lead.copy_from_slice(&hash[..8]);
+    (u64::from_be_bytes(lead) % k as u64) as u32
+}
+
+fn udf_isbn13(s: Series) -> PolarsResult<Option<Series>> {
+    let res: StringChunked = s
+        .str()?
+        .into_iter()
+        .map(|v| v.map(|i| isbn13(i).into_owned()))
+        .collect();
+    Ok(Some(res.into_series()))
+}
+
+/// Load the cluster members, sorted by cluster and member.
+fn scan_members(path: &Path, members: Members, canonical: bool) -> Result<DataFrame> {
     let path = path
         .to_str()
         .map(|s| s.to_string())
         .ok_or(anyhow!("invalid UTF8 pathname"))?;
-    info!("scanning ISBN cluster file {}", path);
-    let icl = LazyFrame::scan_parquet(path, default())?;
-    let icl = icl.select(&[col("isbn"), col("cluster")]);
-    Ok(icl)
+    info!("scanning cluster file {}", path);
+    let df = LazyFrame::scan_parquet(path, default())?;
+    let df = match members {
+        Members::Isbns if canonical => df
+            .select([
+                col("cluster"),
+                col("isbn").map(udf_isbn13, GetOutput::from_type(DataType::String)),
+            ])
+            .unique(None, UniqueKeepStrategy::Any),
+        Members::Isbns => df.select([col("cluster"), col("isbn")]),
+        Members::Codes => df.select([col("cluster"), col("book_code").alias("code")]),
+    };
+
+    // It would be nice to do this with group-by, but group-by is quite slow and introduces
+    // unhelpful overhead. Sorting (for consistency) and a custom loop to aggregate members
+    // into hashes is much more efficient.
+    info!("reading sorted {} into memory", members);
+    let df = df
+        .sort_by_exprs(
+            [col("cluster"), col(members.column())],
+            [false, false],
+            false,
+            false,
+        )
+        .collect()?;
+    Ok(df)
 }
 
 impl Command for HashCmd {
     fn exec(&self) -> Result<()> {
-        let isbns = scan_isbns(self.cluster_file.as_path())?;
-
-        // It would be nice to do this with group-by, but group-by is quite slow and introduces
-        // unhelpful overhead. Sorting (for consistency) and a custom loop to aggregate ISBNs
-        // into hashes is much more efficient.
-        info!("reading sorted ISBNs into memory");
-        let isbns = isbns.sort("isbn", SortOptions::default()).collect()?;
-
-        info!("computing ISBN hashes");
-        let mut hashes: HashMap<i32, Md5> = HashMap::new();
-        let isbn_col = isbns.column("isbn")?.str()?;
-        let clus_col = isbns.column("cluster")?.i32()?;
-        for pair in isbn_col.into_iter().zip(clus_col.into_iter()) {
-            if let (Some(i), Some(c)) = pair {
-                hashes.entry(c).or_default().update(i.as_bytes());
-            }
+        if self.isbn13 && self.members != Members::Isbns {
+            return Err(anyhow!("--isbn13 only applies to ISBN members"));
+        }
+        if self.buckets == Some(0) {
+            return Err(anyhow!("bucket count must be positive"));
         }
 
+        let df = scan_members(self.cluster_file.as_path(), self.members, self.isbn13)?;
+
+        info!("computing {} hashes", self.algorithm);
+        let clusters = df.column("cluster")?.i32()?;
+        let hashes = match self.members {
+            Members::Isbns => {
+                let isbns = df.column("isbn")?.str()?;
+                let rows = clusters
+                    .into_iter()
+                    .zip(isbns)
+                    .filter_map(|(c, i)| Some((c?, i?.as_bytes())));
+                hash_clusters(rows, self.algorithm)
+            }
+            Members::Codes => {
+                let codes = df.column("code")?.i32()?;
+                let rows = clusters
+                    .into_iter()
+                    .zip(codes)
+                    .filter_map(|(c, b)| Some((c?, b?.to_le_bytes())));
+                hash_clusters(rows, self.algorithm)
+            }
+        };
+        drop(df);
+
         info!("computed hashes for {} clusters", hashes.len());
 
-        let path = self.output.as_path();
-        info!("writing ISBN hashes to {:?}", path);
-        let mut writer = TableWriter::open(path)?;
-        for (cluster, h) in hashes.into_iter() {
-            let h = h.finalize();
-            writer.write_object(ClusterHash {
-                cluster,
-                isbn_hash: hex::encode(h),
-                isbn_dcode: (h[h.len() - 1] % 2) as i8,
-            })?;
+        let prefix = self.members.column();
+        let cluster: Int32Chunked = hashes.iter().map(|(c, _)| Some(*c)).collect();
+        let hash: StringChunked = hashes.iter().map(|(_, h)| Some(hex::encode(h))).collect();
+        let dcode: Int8Chunked = hashes
+            .iter()
+            .map(|(_, h)| Some((h[h.len() - 1] % 2) as i8))
+            .collect();
+        let mut columns = vec![
+            cluster.into_series().with_name("cluster"),
+            hash.into_series().with_name(&format!("{}_hash", prefix)),
+            dcode.into_series().with_name(&format!("{}_dcode", prefix)),
+        ];
+        if let Some(k) = self.buckets {
+            let buckets: UInt32Chunked = hashes.iter().map(|(_, h)| Some(bucket(h, k))).collect();
+            columns.push(
+                buckets
+                    .into_series()
+                    .with_name(&format!("{}_bucket", prefix)),
+            );
         }
+        let df = DataFrame::new(columns)?;
 
-        writer.finish()?;
+        let path = self.output.as_path();
+        info!("writing {} hashes to {:?}", prefix, path);
+        save_df_parquet(df, path)?;
 
         Ok(())
     }
 }
+
+#[test]
+fn test_hash_runs() {
+    let rows = vec![(1, "a"), (1, "b"), (2, "c")];
+    let hashes = hash_clusters(rows, HashAlgo::Md5);
+    assert_eq!(hashes.len(), 2);
+    assert_eq!(hashes[0].0, 1);
+    assert_eq!(
+        hex::encode(&hashes[0].1),
+        "187ef4436122d1cc2f40dc2b92f0eba0"
+    );
+    assert_eq!(hashes[1].0, 2);
+    assert_eq!(
+        hex::encode(&hashes[1].1),
+        "4a8a08f09d37b73795649038408b5f33"
+    );
+}
+
+#[test]
+fn test_hash_lengths() {
+    for (algo, len) in [
+        (HashAlgo::Md5, 16),
+        (HashAlgo::Sha256, 32),
+        (HashAlgo::Blake3, 32),
+        (HashAlgo::Xxh3, 16),
+    ] {
+        let hashes = hash_clusters([(1, [0u8; 4])], algo);
+        assert_eq!(hashes[0].1.len(), len);
+        assert!(bucket(&hashes[0].1, 5) < 5);
+    }
+}
@@ -0,0 +1,53 @@
+//! Link records without ISBNs by title and author.
+use std::fs::File;
+
+use crate::graph::titles::*;
+use crate::prelude::*;
+
+/// Link book records without ISBNs to other records with the same title and author.
+///
+/// The links are used by `cluster-books` when the `TitleLinks` graph source is
+/// enabled in `config.yaml`.
+#[derive(Args, Debug)]
+#[command(name = "link-titles")]
+pub struct LinkTitles {
+    /// Only link matches scoring at least S.
+    #[arg(long = "threshold", name = "S", default_value_t = 0.8)]
+    threshold: f32,
+
+    /// Skip blocks with more than N records.
+    #[arg(long = "max-block", name = "N", default_value_t = 50)]
+    max_block: usize,
+
+    /// Hold out fraction F of records with ISBNs to estimate precision.
+    #[arg(long = "sample-rate", name = "F", default_value_t = 0.01)]
+    sample_rate: f64,
+
+    /// Write the precision report to REPORT.
+    #[arg(long = "report", name = "REPORT")]
+    report: Option<PathBuf>,
+
+    /// Write links to FILE.
+    #[arg(short = 'o', long = "output", name = "FILE", default_value = TITLE_LINK_PATH)]
+    output: String,
+}
+
+impl Command for LinkTitles {
+    fn exec(&self) -> Result<()> {
+        let cfg = load_config()?;
+        let opts = TitleLinkOptions {
+            threshold: self.threshold,
+            max_block: self.max_block,
+            sample_rate: self.sample_rate,
+        };
+        let report = link_titles(&cfg, &opts, &self.output)?;
+
+        if let Some(path) = &self.report {
+            info!("writing report to {}", path.display());
+            let out = File::create(path)?;
+            serde_json::to_writer_pretty(out, &report)?;
+        }
+
+        Ok(())
+    }
+}
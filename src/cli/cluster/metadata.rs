@@ -0,0 +1,376 @@
+//! Extract canonical metadata for book clusters.
+//!
+//! Each cluster gets a title, first author, earliest publication year, and
+//! language, chosen from the records in the cluster.  Titles, authors, and
+//! languages are chosen by majority vote over the cluster's records (with titles
+//! compared in normalized form, authors by surname and initials, and languages
+//! as MARC codes); ties go to the higher-priority source, which also provides
+//! the displayed value.  The year is the earliest one any record reports.
+use std::path::PathBuf;
+
+use crate::arrow::dfext::*;
+use crate::cleaning::names::clean_name;
+use crate::cleaning::strings::trim_title;
+use crate::graph::model::{CLUSTER_STATS_PATH, GRAPH_NODE_PATH};
+use crate::ids::codes::*;
+use crate::marc::fixed::{self, FIXED_TAG};
+use crate::prelude::*;
+use polars::prelude::*;
+
+/// The default source priority.
+const DEFAULT_PRIORITY: &str = "LOC,OLEditions,OLWorks,GRBooks,GRWorks";
+
+#[derive(Args, Debug)]
+#[command(name = "extract-metadata")]
+/// Extract a canonical title, author, year, and language for each cluster.
+pub struct ExtractMetadata {
+    /// Specify output file
+    #[arg(short = 'o', long = "output")]
+    output: PathBuf,
+
+    /// Comma-separated metadata sources, highest priority first.
+    ///
+    /// Sources not listed are not used.
+    #[arg(
+        short = 'p',
+        long = "priority",
+        name = "SOURCES",
+        value_delimiter = ',',
+        default_value = DEFAULT_PRIORITY
+    )]
+    priority: Vec<String>,
+}
+
+fn udf_title(s: Series) -> PolarsResult<Option<Series>> {
+    map_strings(s, |t| {
+        Some(trim_title(t).to_string()).filter(|t| !t.is_empty())
+    })
+}
+
+fn udf_author(s: Series) -> PolarsResult<Option<Series>> {
+    map_strings(s, |n| Some(clean_name(n)).filter(|n| !n.is_empty()))
+}
+
+fn udf_fixed_year(s: Series) -> PolarsResult<Option<Series>> {
+    let res: Int16Chunked = s
+        .str()?
+        .into_iter()
+        .map(|v| v.and_then(fixed::date1))
+        .collect();
+    Ok(Some(res.into_series()))
+}
+
+/// Null columns for the fields a source does not provide.
+fn null_str(name: &str) -> Expr {
+    lit(NULL).cast(DataType::String).alias(name)
+}
+
+fn null_year() -> Expr {
+    lit(NULL).cast(DataType::Int16).alias("year")
+}
+
+/// Scan the LOC book records.
+fn scan_loc() -> Result<LazyFrame> {
+    let fields = LazyFrame::scan_parquet("loc-mds/book-fields.parquet", default())?;
+    let titles = fields
+        .clone()
+        .filter(
+            col("tag")
+                .eq(lit(245))
+                .and(col("sf_code").eq(lit(b'a' as i32))),
+        )
+        .group_by([col("rec_id")])
+        .agg([col("contents").first().alias("title")]);
+    let fixed = fields
+        .filter(col("tag").eq(lit(FIXED_TAG as i32)))
+        .group_by([col("rec_id")])
+        .agg([col("contents").first().alias("fixed")]);
+    let authors = LazyFrame::scan_parquet("loc-mds/book-authors.parquet", default())?
+        .filter(col("author_name").is_not_null())
+        .group_by_stable([col("rec_id")])
+        .agg([col("author_name").first().alias("author")]);
+
+    let df = titles
+        .join(
+            fixed,
+            [col("rec_id")],
+            [col("rec_id")],
+            JoinType::Outer { coalesce: true }.into(),
+        )
+        .join(
+            authors,
+            [col("rec_id")],
+            [col("rec_id")],
+            JoinType::Left.into(),
+        );
+    Ok(df.select([
+        (col("rec_id").cast(DataType::Int32) + lit(NS_LOC_REC.base())).alias("code"),
+        col("title"),
+        col("author"),
+        col("fixed")
+            .map(udf_fixed_year, GetOutput::from_type(DataType::Int16))
+            .alias("year"),
+        col("fixed")
+            .map(udf_fixed_language, GetOutput::from_type(DataType::String))
+            .alias("language"),
+    ]))
+}
+
+/// Scan the OpenLibrary editions.
+fn scan_ol_editions() -> Result<LazyFrame> {
+    let editions = LazyFrame::scan_parquet("openlibrary/editions.parquet", default())?;
+    let authors = LazyFrame::scan_parquet("openlibrary/edition-authors.parquet", default())?
+        .filter(col("pos").eq(lit(0)))
+        .select([col("edition"), col("author")]);
+    let names = LazyFrame::scan_parquet("openlibrary/author-names.parquet", default())?
+        .group_by_stable([col("id")])
+        .agg([col("name").first()]);
+    let langs = LazyFrame::scan_parquet("openlibrary/edition-languages.parquet", default())?
+        .filter(col("pos").eq(lit(0)))
+        .select([col("edition").alias("lang_edition"), col("language")]);
+
+    let df = editions
+        .join(
+            authors,
+            [col("id")],
+            [col("edition")],
+            JoinType::Left.into(),
+        )
+        .join(names, [col("author")], [col("id")], JoinType::Left.into())
+        .join(
+            langs,
+            [col("id")],
+            [col("lang_edition")],
+            JoinType::Left.into(),
+        );
+    Ok(df.select([
+        (col("id").cast(DataType::Int32) + lit(NS_EDITION.base())).alias("code"),
+        col("title"),
+        col("name").alias("author"),
+        null_year(),
+        col("language"),
+    ]))
+}
+
+/// Scan the OpenLibrary works.
+fn scan_ol_works() -> Result<LazyFrame> {
+    let works = LazyFrame::scan_parquet("openlibrary/works.parquet", default())?;
+    let authors = LazyFrame::scan_parquet("openlibrary/work-authors.parquet", default())?
+        .filter(col("pos").eq(lit(0)))
+        .select([col("id").alias("work"), col("author")]);
+    let names = LazyFrame::scan_parquet("openlibrary/author-names.parquet", default())?
+        .group_by_stable([col("id")])
+        .agg([col("name").first()]);
+
+    let df = works
+        .join(authors, [col("id")], [col("work")], JoinType::Left.into())
+        .join(names, [col("author")], [col("id")], JoinType::Left.into());
+    Ok(df.select([
+        (col("id").cast(DataType::Int32) + lit(NS_WORK.base())).alias("code"),
+        col("title"),
+        col("name").alias("author"),
+        null_year(),
+        null_str("language"),
+    ]))
+}
+
+/// Scan the GoodReads books.
+fn scan_gr_books() -> Result<LazyFrame> {
+    let books = LazyFrame::scan_parquet("goodreads/gr-book-info.parquet", default())?;
+    let authors = LazyFrame::scan_parquet("goodreads/gr-book-authors.parquet", default())?
+        .group_by_stable([col("book_id")])
+        .agg([col("author_id").first()]);
+    let names = LazyFrame::scan_parquet("goodreads/gr-author-info.parquet", default())?;
+    let details = LazyFrame::scan_parquet("goodreads/gr-book-details.parquet", default())?
+        .select([col("book_id"), col("lang_code").alias("language")]);
+
+    let df = books
+        .join(
+            authors,
+            [col("book_id")],
+            [col("book_id")],
+            JoinType::Left.into(),
+        )
+        .join(
+            names,
+            [col("author_id")],
+            [col("author_id")],
+            JoinType::Left.into(),
+        )
+        .join(
+            details,
+            [col("book_id")],
+            [col("book_id")],
+            JoinType::Left.into(),
+        );
+    Ok(df.select([
+        (col("book_id").cast(DataType::Int32) + lit(NS_GR_BOOK.base())).alias("code"),
+        col("title"),
+        col("name").alias("author"),
+        col("pub_year").cast(DataType::Int16).alias("year"),
+        col("language"),
+    ]))
+}
+
+/// Scan the GoodReads works.
+fn scan_gr_works() -> Result<LazyFrame> {
+    let works = LazyFrame::scan_parquet("goodreads/gr-work-info.parquet", default())?;
+    Ok(works.select([
+        (col("work_id").cast(DataType::Int32) + lit(NS_GR_WORK.base())).alias("code"),
+        col("title"),
+        null_str("author"),
+        col("pub_year").cast(DataType::Int16).alias("year"),
+        null_str("language"),
+    ]))
+}
+
+/// Scan a metadata source by name, if the configuration enables it.
+fn scan_source(cfg: &Config, name: &str) -> Result<Option<LazyFrame>> {
+    let gc = &cfg.graph;
+    let gr = cfg.goodreads.enabled;
+    let (enabled, scan): (bool, fn() -> Result<LazyFrame>) = match name {
+        "LOC" => (gc.builtin_enabled(name, true), scan_loc),
+        "OLEditions" => (gc.builtin_enabled(name, true), scan_ol_editions),
+        "OLWorks" => (gc.builtin_enabled(name, true), scan_ol_works),
+        "GRBooks" => (gc.builtin_enabled(name, gr), scan_gr_books),
+        "GRWorks" => (gc.builtin_enabled(name, gr), scan_gr_works),
+        _ => return Err(anyhow!("unknown metadata source {}", name)),
+    };
+    if enabled {
+        Ok(Some(scan()?))
+    } else {
+        warn!("metadata source {} is disabled", name);
+        Ok(None)
+    }
+}
+
+/// Select the candidate values of a field, with the keys to vote on.
+fn field_cands(cands: &LazyFrame, key: Expr, value: Expr) -> LazyFrame {
+    cands.clone().select([
+        col("cluster"),
+        col("source"),
+        col("rank"),
+        key.alias("key"),
+        value.alias("value"),
+    ])
+}
+
+/// Pick each cluster's value of a field by majority vote on its key.
+///
+/// `cands` has columns `cluster`, `source`, `rank`, `key`, and `value`.
+fn vote(cands: LazyFrame, field: &str) -> LazyFrame {
+    let prov = format!("{}_source", field);
+    let groups = cands
+        .filter(col("key").is_not_null().and(col("value").is_not_null()))
+        .group_by([col("cluster"), col("key")])
+        .agg([
+            col("rank").count().alias("votes"),
+            col("rank").min().alias("best"),
+            col("value")
+                .sort_by([col("rank")], [false])
+                .first()
+                .alias(field),
+            col("source")
+                .sort_by([col("rank")], [false])
+                .first()
+                .alias(&prov),
+        ]);
+    groups
+        .sort_by_exprs(
+            [col("cluster"), col("votes"), col("best")],
+            [false, true, false],
+            false,
+            false,
+        )
+        .group_by_stable([col("cluster")])
+        .agg([col(field).first(), col(&prov).first()])
+}
+
+/// Pick each cluster's earliest year.
+fn earliest(cands: LazyFrame) -> LazyFrame {
+    cands
+        .filter(col("year").is_not_null())
+        .sort_by_exprs(
+            [col("cluster"), col("year"), col("rank")],
+            [false, false, false],
+            false,
+            false,
+        )
+        .group_by_stable([col("cluster")])
+        .agg([
+            col("year").first(),
+            col("source").first().alias("year_source"),
+        ])
+}
+
+impl Command for ExtractMetadata {
+    fn exec(&self) -> Result<()> {
+        require_working_root()?;
+        let cfg = load_config()?;
+
+        let nodes = LazyFrame::scan_parquet(GRAPH_NODE_PATH, default())?
+            .select([col("book_code"), col("cluster")]);
+
+        let mut frames = Vec::new();
+        for (rank, name) in self.priority.iter().enumerate() {
+            if let Some(src) = scan_source(&cfg, name)? {
+                info!("scanning metadata from {} (priority {})", name, rank + 1);
+                let src = src.with_columns([
+                    lit(name.as_str()).alias("source"),
+                    lit(rank as u32).alias("rank"),
+                ]);
+                frames.push(src.join(
+                    nodes.clone(),
+                    [col("code")],
+                    [col("book_code")],
+                    JoinType::Inner.into(),
+                ));
+            }
+        }
+        if frames.is_empty() {
+            return Err(anyhow!("no metadata sources enabled"));
+        }
+        let cands = concat(frames, UnionArgs::default())?.cache();
+
+        let title = col("title").map(udf_title, GetOutput::from_type(DataType::String));
+        let title_key = col("title").map(udf_title_key, GetOutput::from_type(DataType::String));
+        let titles = vote(field_cands(&cands, title_key, title), "title");
+
+        let author = col("author").map(udf_author, GetOutput::from_type(DataType::String));
+        let author_key = author
+            .clone()
+            .map(udf_author_key, GetOutput::from_type(DataType::String));
+        let authors = vote(field_cands(&cands, author_key, author), "author");
+
+        let years = earliest(cands.clone());
+        let languages = vote(
+            field_cands(&cands, col("language"), col("language")),
+            "language",
+        );
+
+        let clusters =
+            LazyFrame::scan_parquet(CLUSTER_STATS_PATH, default())?.select([col("cluster")]);
+        let mut meta = clusters;
+        for fld in [titles, authors, years, languages] {
+            meta = meta.join(
+                fld,
+                [col("cluster")],
+                [col("cluster")],
+                JoinType::Left.into(),
+            );
+        }
+        let meta = meta.sort("cluster", default());
+
+        info!("collecting cluster metadata");
+        let meta = meta.collect()?;
+        for fld in ["title", "author", "year", "language"] {
+            let n = meta.column(fld)?.is_not_null().sum().unwrap_or(0);
+            info!("{} of {} clusters have a {}", n, meta.height(), fld);
+        }
+
+        info!("saving to {:?}", &self.output);
+        save_df_parquet(meta, &self.output)?;
+
+        Ok(())
+    }
+}
@@ -1,7 +1,16 @@
 //! Commands for processing clusters.
+pub mod audit;
 pub mod author_gender;
 pub mod author_language;
-pub mod cluster_ol_language;
 pub mod authors;
 pub mod books;
-pub mod hash;
\ No newline at end of file
+pub mod cluster_ol_language;
+pub mod evaluate;
+pub mod hash;
+pub mod link_titles;
+pub mod metadata;
+pub mod ol_languages;
+pub mod save_graph;
+pub mod suspect_isbns;
+pub mod translations;
+mod viaf_ids;
@@ -0,0 +1,68 @@
+//! Extract the distribution of OpenLibrary edition languages in each cluster.
+use std::path::PathBuf;
+
+use crate::graph::model::GRAPH_NODE_PATH;
+use crate::ids::codes::NS_EDITION;
+use crate::prelude::*;
+use polars::prelude::*;
+
+#[derive(Args, Debug)]
+#[command(name = "extract-ol-languages")]
+/// Count the languages of the OpenLibrary editions in each cluster.
+pub struct ExtractOLLanguages {
+    /// Specify output file
+    #[arg(
+        short = 'o',
+        long = "output",
+        default_value = "book-links/cluster-ol-languages.parquet"
+    )]
+    output: PathBuf,
+}
+
+impl Command for ExtractOLLanguages {
+    fn exec(&self) -> Result<()> {
+        require_working_root()?;
+
+        let nodes = LazyFrame::scan_parquet(GRAPH_NODE_PATH, default())?;
+        let editions = nodes
+            .filter(col("node_type").eq(lit(NS_EDITION.name())))
+            .select([
+                (col("book_code") - lit(NS_EDITION.base())).alias("edition"),
+                col("cluster"),
+            ]);
+
+        // an edition listing a language twice only counts once
+        let langs = LazyFrame::scan_parquet("openlibrary/edition-languages.parquet", default())?;
+        let langs = langs
+            .select([col("edition").cast(DataType::Int32), col("language")])
+            .unique(None, UniqueKeepStrategy::Any);
+
+        let joined = editions.join(
+            langs,
+            [col("edition")],
+            [col("edition")],
+            JoinType::Inner.into(),
+        );
+        let counts = joined
+            .group_by([col("cluster"), col("language")])
+            .agg([col("edition").count().alias("editions")])
+            .sort_by_exprs(
+                [col("cluster"), col("editions"), col("language")],
+                [false, true, false],
+                false,
+                false,
+            );
+
+        info!("counting edition languages");
+        let counts = counts.collect()?;
+        info!(
+            "found {} languages in {} clusters",
+            counts.column("language")?.n_unique()?,
+            counts.column("cluster")?.n_unique()?
+        );
+
+        save_df_parquet(counts, &self.output)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+//! Save the clustered book graph.
+use crate::graph::hierarchy::save_cluster_hierarchy;
+use crate::graph::*;
+use crate::prelude::*;
+
+/// Save the clustered book graph and the work hierarchy within its clusters.
+///
+/// This rebuilds the graph from the node and edge tables written by `cluster-books`,
+/// so clustering itself can use the streaming union-find and the full graph is only
+/// built when something needs it.
+#[derive(Args, Debug)]
+#[command(name = "save-graph")]
+pub struct SaveGraph {
+    /// Also write the cluster work hierarchy.
+    #[arg(long = "hierarchy")]
+    hierarchy: bool,
+
+    /// Save the graph to FILE.
+    #[arg(name = "FILE")]
+    output: PathBuf,
+}
+
+impl Command for SaveGraph {
+    fn exec(&self) -> Result<()> {
+        info!("loading clustered graph");
+        let graph = load_clustered_graph()?;
+        if self.hierarchy {
+            save_cluster_hierarchy(&graph)?;
+        }
+        info!("saving graph to {}", self.output.display());
+        save_graph(&graph, &self.output)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+//! Flag promiscuous ISBNs.
+use crate::arrow::*;
+use crate::graph::suspects::*;
+use crate::prelude::*;
+
+/// Flag ISBNs that link too many unrelated records.
+#[derive(Args, Debug)]
+#[command(name = "suspect-isbns")]
+pub struct SuspectISBNs {
+    /// Only examine ISBNs with at least N links.
+    #[arg(long = "min-degree", name = "N", default_value_t = 3)]
+    min_degree: u32,
+
+    /// Flag ISBNs with more than N links.
+    #[arg(long = "max-degree", name = "MAXDEG", default_value_t = 20)]
+    max_degree: u32,
+
+    /// Flag ISBNs whose neighbors have more than N distinct titles.
+    #[arg(long = "max-titles", name = "MAXTITLES", default_value_t = 4)]
+    max_titles: u32,
+
+    /// Flag ISBNs whose neighbors have more than N distinct authors.
+    #[arg(long = "max-authors", name = "MAXAUTHORS", default_value_t = 4)]
+    max_authors: u32,
+
+    /// Write output to FILE.
+    #[arg(short = 'o', long = "output", name = "FILE", default_value = SUSPECT_ISBN_PATH)]
+    output: PathBuf,
+}
+
+impl Command for SuspectISBNs {
+    fn exec(&self) -> Result<()> {
+        let cfg = load_config()?;
+        let thresholds = SuspectThresholds {
+            min_degree: self.min_degree,
+            max_degree: self.max_degree,
+            max_titles: self.max_titles,
+            max_authors: self.max_authors,
+        };
+        let suspects = find_suspect_isbns(&cfg, &thresholds)?;
+
+        info!("writing suspects to {}", self.output.display());
+        let mut writer = TableWriter::open(&self.output)?;
+        for s in suspects {
+            writer.write_object(s)?;
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! Link clusters that are translations of other clusters.
+//!
+//! LOC records for translations set the first indicator of the 041 field and
+//! give the language of the original in 041 $h; most also carry the original
+//! title as a uniform title in 240 $a.  We match the uniform title and first
+//! author of each translated record against the canonical title and author of
+//! the other clusters (from `cluster extract-metadata`) to find the cluster
+//! holding the original.
+//!
+//! A link's confidence is the fraction of the translation cluster's translated
+//! records that point to the original cluster, halved when the original
+//! cluster's language is unknown rather than matching 041 $h.
+use std::path::PathBuf;
+
+use crate::arrow::dfext::*;
+use crate::graph::model::GRAPH_NODE_PATH;
+use crate::ids::codes::*;
+use crate::marc::fixed::FIXED_TAG;
+use crate::prelude::*;
+use polars::prelude::*;
+
+#[derive(Args, Debug)]
+#[command(name = "link-translations")]
+/// Link translated clusters to the clusters of their originals.
+pub struct LinkTranslations {
+    /// Specify output file
+    #[arg(short = 'o', long = "output")]
+    output: PathBuf,
+
+    /// Read canonical cluster metadata from FILE.
+    #[arg(
+        long = "metadata",
+        name = "FILE",
+        default_value = "book-links/cluster-metadata.parquet"
+    )]
+    metadata: PathBuf,
+
+    /// Only keep links with confidence at least C.
+    #[arg(long = "min-confidence", name = "C", default_value_t = 0.5)]
+    min_confidence: f64,
+}
+
+/// Get the first language code from a language subfield.
+///
+/// Older records run several codes together in one subfield (`engfre`).
+fn first_language(codes: &str) -> Option<&str> {
+    let lang = codes.trim().get(0..3)?;
+    if lang.bytes().all(|b| b.is_ascii_lowercase()) {
+        Some(lang)
+    } else {
+        None
+    }
+}
+
+fn udf_language(s: Series) -> PolarsResult<Option<Series>> {
+    map_strings(s, |c| first_language(c).map(|l| l.to_string()))
+}
+
+fn title_key(e: Expr) -> Expr {
+    e.map(udf_title_key, GetOutput::from_type(DataType::String))
+}
+
+fn author_key(e: Expr) -> Expr {
+    e.map(udf_author_key, GetOutput::from_type(DataType::String))
+}
+
+/// Check a candidate original cluster's language against the original language.
+///
+/// Returns `Some(true)` when the languages match, `Some(false)` when the cluster's
+/// language is unknown, and `None` when they conflict (ruling out the link).
+fn language_match(target: Option<&str>, original: &str) -> Option<bool> {
+    match target {
+        Some(t) if t == original => Some(true),
+        Some(_) => None,
+        None => Some(false),
+    }
+}
+
+/// Compute a link's confidence from its supporting and total translated records.
+///
+/// The support is halved when the original cluster's language is unknown.
+fn link_confidence(records: u32, translated: u32, language_match: bool) -> f64 {
+    let support = records as f64 / translated as f64;
+    if language_match {
+        support
+    } else {
+        support * 0.5
+    }
+}
+
+fn udf_language_match(s: &mut [Series]) -> PolarsResult<Option<Series>> {
+    let targets = s[0].str()?;
+    let originals = s[1].str()?;
+    let res: BooleanChunked = targets
+        .into_iter()
+        .zip(originals)
+        .map(|(t, o)| language_match(t, o?))
+        .collect();
+    Ok(Some(res.into_series()))
+}
+
+fn udf_link_confidence(s: &mut [Series]) -> PolarsResult<Option<Series>> {
+    let records = s[0].u32()?;
+    let translated = s[1].u32()?;
+    let matched = s[2].bool()?;
+    let res: Float64Chunked = records
+        .into_iter()
+        .zip(translated)
+        .zip(matched)
+        .map(|((r, t), m)| Some(link_confidence(r?, t?, m?)))
+        .collect();
+    Ok(Some(res.into_series()))
+}
+
+/// Select the contents of a subfield, for aggregating a record's fields.
+fn subfield(code: char) -> Expr {
+    col("contents")
+        .filter(col("sf_code").eq(lit(code as i32)))
+        .first()
+}
+
+/// Scan the translated LOC records with their clusters, languages, and keys.
+fn scan_translations() -> Result<LazyFrame> {
+    let fields = LazyFrame::scan_parquet("loc-mds/book-fields.parquet", default())?;
+    let langs = fields
+        .clone()
+        .filter(col("tag").eq(lit(41)).and(col("ind1").eq(lit(b'1' as i32))))
+        .group_by([col("rec_id")])
+        .agg([
+            subfield('a').alias("text_language"),
+            subfield('h').alias("original_language"),
+        ]);
+    let uniform = fields
+        .clone()
+        .filter(
+            col("tag")
+                .eq(lit(240))
+                .and(col("sf_code").eq(lit(b'a' as i32))),
+        )
+        .group_by([col("rec_id")])
+        .agg([col("contents").first().alias("uniform_title")]);
+    let fixed = fields
+        .filter(col("tag").eq(lit(FIXED_TAG as i32)))
+        .group_by([col("rec_id")])
+        .agg([col("contents").first().alias("fixed")]);
+    let authors = LazyFrame::scan_parquet("loc-mds/book-authors.parquet", default())?
+        .filter(col("author_name").is_not_null())
+        .group_by_stable([col("rec_id")])
+        .agg([col("author_name").first().alias("author")]);
+    let nodes = LazyFrame::scan_parquet(GRAPH_NODE_PATH, default())?
+        .select([col("book_code"), col("cluster")]);
+
+    let df = langs
+        .join(
+            uniform,
+            [col("rec_id")],
+            [col("rec_id")],
+            JoinType::Inner.into(),
+        )
+        .join(
+            authors,
+            [col("rec_id")],
+            [col("rec_id")],
+            JoinType::Inner.into(),
+        )
+        .join(
+            fixed,
+            [col("rec_id")],
+            [col("rec_id")],
+            JoinType::Left.into(),
+        )
+        .with_column((col("rec_id").cast(DataType::Int32) + lit(NS_LOC_REC.base())).alias("code"))
+        .join(
+            nodes,
+            [col("code")],
+            [col("book_code")],
+            JoinType::Inner.into(),
+        );
+
+    let text_lang = col("text_language").map(udf_language, GetOutput::from_type(DataType::String));
+    let fixed_lang = col("fixed").map(udf_fixed_language, GetOutput::from_type(DataType::String));
+    let df = df.select([
+        col("cluster"),
+        when(text_lang.clone().is_not_null())
+            .then(text_lang)
+            .otherwise(fixed_lang)
+            .alias("language"),
+        col("original_language")
+            .map(udf_language, GetOutput::from_type(DataType::String))
+            .alias("original_language"),
+        title_key(col("uniform_title")).alias("title_key"),
+        author_key(col("author")).alias("author_key"),
+    ]);
+
+    Ok(df
+        .drop_nulls(Some(vec![
+            col("original_language"),
+            col("title_key"),
+            col("author_key"),
+        ]))
+        .filter(
+            col("language")
+                .is_null()
+                .or(col("language").neq(col("original_language"))),
+        ))
+}
+
+/// Scan the canonical titles and authors of the candidate original clusters.
+fn scan_originals(path: &Path) -> Result<LazyFrame> {
+    let meta = LazyFrame::scan_parquet(path, default())?;
+    Ok(meta
+        .select([
+            col("cluster").alias("translation_of"),
+            col("language").alias("target_language"),
+            title_key(col("title")).alias("title_key"),
+            author_key(col("author")).alias("author_key"),
+        ])
+        .drop_nulls(Some(vec![col("title_key"), col("author_key")])))
+}
+
+impl Command for LinkTranslations {
+    fn exec(&self) -> Result<()> {
+        require_working_root()?;
+
+        let trans = scan_translations()?.cache();
+        let originals = scan_originals(&self.metadata)?;
+
+        let totals = trans
+            .clone()
+            .group_by([col("cluster")])
+            .agg([col("title_key").count().alias("translated_records")]);
+
+        let links = trans
+            .join(
+                originals,
+                [col("title_key"), col("author_key")],
+                [col("title_key"), col("author_key")],
+                JoinType::Inner.into(),
+            )
+            .filter(col("cluster").neq(col("translation_of")))
+            .with_column(
+                map_multiple(
+                    udf_language_match,
+                    [col("target_language"), col("original_language")],
+                    GetOutput::from_type(DataType::Boolean),
+                )
+                .alias("language_match"),
+            )
+            .filter(col("language_match").is_not_null())
+            .group_by([col("cluster"), col("translation_of")])
+            .agg([
+                col("language").drop_nulls().first(),
+                col("original_language").first(),
+                col("title_key").count().alias("records"),
+                col("language_match").max(),
+            ])
+            .join(
+                totals,
+                [col("cluster")],
+                [col("cluster")],
+                JoinType::Inner.into(),
+            );
+
+        let links = links
+            .select([
+                col("cluster"),
+                col("translation_of"),
+                col("language"),
+                col("original_language"),
+                col("records"),
+                map_multiple(
+                    udf_link_confidence,
+                    [
+                        col("records").cast(DataType::UInt32),
+                        col("translated_records").cast(DataType::UInt32),
+                        col("language_match"),
+                    ],
+                    GetOutput::from_type(DataType::Float64),
+                )
+                .alias("confidence"),
+            ])
+            .filter(col("confidence").gt_eq(lit(self.min_confidence)))
+            .sort_by_exprs(
+                [col("cluster"), col("confidence")],
+                [false, true],
+                false,
+                false,
+            );
+
+        info!("linking translations");
+        let links = links.collect()?;
+        let n_clusters = links.column("cluster")?.n_unique()?;
+        info!(
+            "found {} translation links for {} clusters",
+            links.height(),
+            n_clusters
+        );
+
+        info!("saving to {:?}", &self.output);
+        save_df_parquet(links, &self.output)?;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_first_language() {
+    assert_eq!(first_language("ger"), Some("ger"));
+    assert_eq!(first_language(" engfre"), Some("eng"));
+    assert_eq!(first_language("en"), None);
+    assert_eq!(first_language("ENG"), None);
+}
+
+#[test]
+fn test_language_match() {
+    assert_eq!(language_match(Some("eng"), "eng"), Some(true));
+    assert_eq!(language_match(None, "eng"), Some(false));
+    assert_eq!(language_match(Some("fre"), "eng"), None);
+}
+
+#[test]
+fn test_link_confidence() {
+    assert_eq!(link_confidence(3, 4, true), 0.75);
+    assert_eq!(link_confidence(3, 4, false), 0.375);
+    assert_eq!(link_confidence(2, 2, false), 0.5);
+}
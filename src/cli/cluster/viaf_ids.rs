@@ -0,0 +1,52 @@
+//! Map VIAF records to their VIAF IDs.
+use std::collections::HashMap;
+
+use polars::prelude::*;
+
+use crate::prelude::*;
+
+/// Get the VIAF ID from the contents of a VIAF record's 001 field.
+fn parse_viaf_id(cf: &str) -> Option<&str> {
+    let id = cf.trim();
+    let id = id.strip_prefix("viaf").unwrap_or(id);
+    if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+/// Load the VIAF ID of each VIAF record, keyed by record ID.
+pub(super) fn viaf_load_ids() -> Result<HashMap<u32, String>> {
+    let timer = Timer::new();
+    info!("loading VIAF record IDs");
+    let df = LazyFrame::scan_parquet("viaf/viaf.parquet", default())?;
+    let df = df
+        .filter(col("tag").eq(lit(1i16)))
+        .select([col("rec_id"), col("contents")])
+        .collect()?;
+
+    let recs = df.column("rec_id")?.u32()?;
+    let ids = df.column("contents")?.str()?;
+    let mut map = HashMap::with_capacity(df.height());
+    for (rec, id) in recs.into_iter().zip(ids) {
+        if let (Some(rec), Some(id)) = (rec, id.and_then(parse_viaf_id)) {
+            map.insert(rec, id.to_string());
+        }
+    }
+
+    info!(
+        "loaded VIAF IDs for {} records in {}",
+        map.len(),
+        timer.human_elapsed()
+    );
+
+    Ok(map)
+}
+
+#[test]
+fn test_parse_viaf_id() {
+    assert_eq!(parse_viaf_id("viaf102333412"), Some("102333412"));
+    assert_eq!(parse_viaf_id("102333412"), Some("102333412"));
+    assert_eq!(parse_viaf_id("LC|n 79021164"), None);
+}
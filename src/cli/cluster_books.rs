@@ -1,78 +1,96 @@
 //! Book clustering command.
-use std::thread::{scope, Scope, ScopedJoinHandle};
+use std::thread::scope;
 
-use petgraph::algo::kosaraju_scc;
-
-use crate::graph::model::*;
+use crate::graph::curation::Override;
+use crate::graph::suspects::load_suspect_isbn_ids;
 use crate::graph::*;
+use crate::ids::codes::{BookRef, NS_ISBN};
+use crate::layout::Config;
 use crate::prelude::*;
 
 /// Run the book clustering algorithm.
+///
+/// Clusters are computed with a streaming union-find.  With `--save-graph`, the
+/// full graph is then rebuilt from the cluster outputs and saved, along with the
+/// work hierarchy within each cluster; the pipeline does this in a separate step
+/// with `cluster save-graph`.  With `--incremental`,
+/// the previous clusters are updated with the edges of the changed sources.
 #[derive(Args, Debug)]
 #[command(name = "cluster-books")]
 pub struct ClusterBooks {
-    #[arg(long = "save-graph")]
+    #[arg(long = "save-graph", conflicts_with = "incremental")]
     save_graph: Option<PathBuf>,
+
+    /// Update the previous clusters instead of rebuilding them.
+    #[arg(long = "incremental", requires = "SOURCE")]
+    incremental: bool,
+
+    /// Recluster with new edges from SOURCE (with --incremental; repeatable).
+    #[arg(long = "changed", name = "SOURCE", requires = "incremental")]
+    changed: Vec<String>,
+
+    /// Apply curation overrides from FILE (YAML or CSV).
+    #[arg(long = "curation", name = "FILE")]
+    curation: Option<PathBuf>,
+
+    /// Drop the suspect ISBNs listed in FILE before clustering.
+    #[arg(long = "drop-suspects", name = "SUSPECTS")]
+    drop_suspects: Option<PathBuf>,
 }
 
 impl Command for ClusterBooks {
     fn exec(&self) -> Result<()> {
         let cfg = load_config()?;
-        let mut graph = construct_graph(&cfg)?;
-
-        info!("computing connected components");
-        let clusters = kosaraju_scc(&graph);
-
-        info!("computed {} clusters", clusters.len());
-
-        info!("adding cluster annotations");
-        for ci in 0..clusters.len() {
-            let verts = &clusters[ci];
-            let vids: Vec<_> = verts
-                .iter()
-                .map(|v| graph.node_weight(*v).unwrap())
-                .collect();
-            let cluster = vids.iter().map(|b| b.code).min().unwrap();
-            for v in verts {
-                let node = graph.node_weight_mut(*v).unwrap();
-                node.cluster = cluster;
+        let mut curation = match &self.curation {
+            Some(path) => Curation::load(path)?,
+            None => Curation::default(),
+        };
+        if let Some(path) = &self.drop_suspects {
+            let path = path
+                .to_str()
+                .ok_or_else(|| anyhow!("invalid UTF8 pathname"))?;
+            let ids = load_suspect_isbn_ids(path)?;
+            info!("dropping {} suspect ISBNs", ids.len());
+            for id in ids {
+                curation
+                    .overrides
+                    .push(Override::Block(BookRef::Native(&NS_ISBN, id)));
             }
         }
 
-        scope(|scope| -> Result<()> {
-            let sthread = self.maybe_save_graph(scope, &graph);
-
-            save_graph_cluster_data(&graph, clusters)?;
-
-            if let Some(h) = sthread {
-                info!("waiting for background save to finish");
-                let br = h.join().expect("thread join failed");
-                br?;
-            }
-            Ok(())
-        })?;
-
-        Ok(())
+        if let Some(path) = &self.save_graph {
+            info!("clustering with union-find");
+            self.cluster_graph(&cfg, &curation, path)
+        } else if self.incremental {
+            incremental_clusters(&cfg, &curation, &self.changed)
+        } else {
+            info!("clustering with union-find");
+            stream_clusters(&cfg, &curation)
+        }
     }
 }
 
 impl ClusterBooks {
-    fn maybe_save_graph<'scope, 'env>(
-        &'env self,
-        scope: &'scope Scope<'scope, 'env>,
-        graph: &'env IdGraph,
-    ) -> Option<ScopedJoinHandle<'scope, Result<()>>> {
-        if let Some(gf) = &self.save_graph {
-            Some(scope.spawn(move || {
-                info!("saving graph to {} (in background)", gf.display());
-                let res = save_graph(&graph, gf);
+    /// Cluster with the union-find, then rebuild the full graph from the cluster
+    /// outputs to save it and the work hierarchy.
+    fn cluster_graph(&self, cfg: &Config, curation: &Curation, path: &Path) -> Result<()> {
+        stream_clusters(cfg, curation)?;
+        let graph = load_clustered_graph()?;
+
+        scope(|scope| -> Result<()> {
+            let sthread = scope.spawn(|| {
+                info!("saving graph to {} (in background)", path.display());
+                let res = save_graph(&graph, path);
                 if let Err(e) = &res {
                     error!("error saving graph: {}", e);
                 }
                 res
-            }))
-        } else {
-            None
-        }
+            });
+
+            hierarchy::save_cluster_hierarchy(&graph)?;
+
+            info!("waiting for background save to finish");
+            sthread.join().expect("thread join failed")
+        })
     }
 }
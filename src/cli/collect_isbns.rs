@@ -4,6 +4,7 @@ use std::fmt::Debug;
 use fallible_iterator::IteratorExt;
 use polars::prelude::*;
 
+use crate::cleaning::isbns::asin_is_isbn;
 use crate::prelude::Result;
 use crate::prelude::*;
 
@@ -40,11 +41,13 @@ fn all_sources(cfg: &Config) -> Vec<ISBNSource> {
             .enabled(cfg.az2014.enabled)
             .path("../az2014/ratings.parquet")
             .column("asin")
+            .asins()
             .finish(),
         ISBNSource::new("AZ18")
             .enabled(cfg.az2018.enabled)
             .path("../az2018/ratings.parquet")
             .column("asin")
+            .asins()
             .finish(),
     ]
 }
@@ -55,6 +58,7 @@ struct ISBNSource {
     enabled: bool,
     path: &'static str,
     columns: Vec<&'static str>,
+    asins: bool,
 }
 
 impl ISBNSource {
@@ -64,6 +68,15 @@ impl ISBNSource {
             enabled: true,
             path: "",
             columns: vec![],
+            asins: false,
+        }
+    }
+
+    /// Mark this source as providing ASINs, only some of which are ISBNs.
+    fn asins(self) -> ISBNSource {
+        ISBNSource {
+            asins: true,
+            ..self
         }
     }
 
@@ -101,6 +114,17 @@ impl ISBNSource {
     }
 }
 
+/// Expression selecting the ASINs that are ISBNs.
+fn isbn_asins(expr: Expr) -> Expr {
+    expr.map(
+        |s| {
+            let mask: BooleanChunked = s.str()?.into_iter().map(|a| a.map(asin_is_isbn)).collect();
+            Ok(Some(mask.into_series()))
+        },
+        GetOutput::from_type(DataType::Boolean),
+    )
+}
+
 /// Read a single ISBN source into the accumulator.
 fn scan_source(src: &ISBNSource) -> Result<LazyFrame> {
     info!("scanning ISBNs from {}", src.path);
@@ -118,6 +142,11 @@ fn scan_source(src: &ISBNSource) -> Result<LazyFrame> {
         info!("counting column {}", id_col);
         let df = read.clone().select(&[col(id_col).alias("isbn")]);
         let df = df.drop_nulls(None);
+        let df = if src.asins {
+            df.filter(isbn_asins(col("isbn")))
+        } else {
+            df
+        };
         let df = df.group_by(["isbn"]).agg([len().alias("nrecs")]);
         if let Some(prev) = counted {
             let joined = prev.join(
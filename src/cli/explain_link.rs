@@ -0,0 +1,232 @@
+//! Explain why two book identifiers are in the same cluster.
+use std::collections::{HashMap, HashSet};
+use std::io::{stdout, Write};
+
+use petgraph::graph::EdgeIndex;
+use polars::prelude::*;
+
+use crate::graph::cuts::CutAnalysis;
+use crate::graph::model::{GRAPH_EDGE_PATH, GRAPH_NODE_PATH, ISBN_CLUSTER_PATH};
+use crate::graph::paths::shortest_paths;
+use crate::graph::*;
+use crate::ids::codes::*;
+use crate::prelude::*;
+
+/// Explain the link between two book identifiers.
+///
+/// Identifiers may be book codes, namespaced native IDs (e.g. `GR-B:1234`), or ISBNs.
+#[derive(Args, Debug)]
+#[command(name = "explain-link")]
+pub struct ExplainLink {
+    /// Load the saved graph from FILE instead of the graph tables.
+    #[arg(long = "graph-file", name = "FILE")]
+    graph_file: Option<PathBuf>,
+
+    /// Show at most N shortest paths.
+    #[arg(short = 'n', long = "max-paths", name = "N", default_value_t = 5)]
+    max_paths: usize,
+
+    /// The first book.
+    #[arg(name = "A")]
+    book_a: BookRef,
+
+    /// The second book.
+    #[arg(name = "B")]
+    book_b: BookRef,
+}
+
+/// Resolve a book reference to its book code.
+fn resolve_code(book: &BookRef) -> Result<i32> {
+    if let Some(code) = book.code() {
+        return Ok(code);
+    }
+    let isbn = match book {
+        BookRef::ISBN(isbn) => isbn.as_str(),
+        _ => unreachable!("non-ISBN reference without code"),
+    };
+
+    let df = LazyFrame::scan_parquet(ISBN_CLUSTER_PATH, default())?;
+    let df = df
+        .filter(col("isbn").eq(lit(isbn)))
+        .select([col("isbn_id")]);
+    let df = df.collect()?;
+    let id = df
+        .column("isbn_id")?
+        .i32()?
+        .get(0)
+        .ok_or_else(|| anyhow!("ISBN {} not found", isbn))?;
+    Ok(NS_ISBN.to_code(id))
+}
+
+/// Look up the cluster for a book code.
+fn lookup_cluster(code: i32) -> Result<i32> {
+    let df = LazyFrame::scan_parquet(GRAPH_NODE_PATH, default())?;
+    let df = df
+        .filter(col("book_code").eq(lit(code)))
+        .select([col("cluster")]);
+    let df = df.collect()?;
+    let cluster = df
+        .column("cluster")?
+        .i32()?
+        .get(0)
+        .ok_or_else(|| anyhow!("book code {} not in graph", code))?;
+    Ok(cluster)
+}
+
+/// Look up the sources that contributed the edges between pairs of book codes.
+///
+/// The result is keyed by the ordered pair `(min, max)`, since edges are undirected.
+/// A pair linked by several sources has each of their names.
+fn lookup_edge_sources(pairs: &[(i32, i32)]) -> Result<HashMap<(i32, i32), Vec<String>>> {
+    let mut filter = lit(false);
+    for (a, b) in pairs {
+        let fwd = col("src").eq(lit(*a)).and(col("dst").eq(lit(*b)));
+        let rev = col("src").eq(lit(*b)).and(col("dst").eq(lit(*a)));
+        filter = filter.or(fwd).or(rev);
+    }
+    let df = LazyFrame::scan_parquet(GRAPH_EDGE_PATH, default())?;
+    let df = df
+        .filter(filter)
+        .select([col("src"), col("dst"), col("source")])
+        .collect()?;
+
+    let srcs = df.column("src")?.i32()?;
+    let dsts = df.column("dst")?.i32()?;
+    let names = df.column("source")?.str()?;
+    let mut sources: HashMap<(i32, i32), Vec<String>> = HashMap::new();
+    for ((s, d), n) in srcs.into_iter().zip(dsts).zip(names) {
+        if let (Some(s), Some(d), Some(n)) = (s, d, n) {
+            let names = sources.entry((s.min(d), s.max(d))).or_default();
+            if !names.iter().any(|x| x == n) {
+                names.push(n.to_string());
+            }
+        }
+    }
+    Ok(sources)
+}
+
+/// Describe a node for display.
+fn describe(node: &BookID) -> String {
+    let ns = ns_of_book_code(node.code).unwrap();
+    let id = ns.from_code(node.code).unwrap();
+    match &node.label {
+        Some(l) => format!("{}:{} ({})", ns.name(), id, l),
+        None => format!("{}:{}", ns.name(), id),
+    }
+}
+
+impl Command for ExplainLink {
+    fn exec(&self) -> Result<()> {
+        require_working_root()?;
+        let code_a = resolve_code(&self.book_a)?;
+        let code_b = resolve_code(&self.book_b)?;
+        let cluster_a = lookup_cluster(code_a)?;
+        let cluster_b = lookup_cluster(code_b)?;
+
+        let out = stdout();
+        let mut out = out.lock();
+        writeln!(
+            out,
+            "{} is book {} in cluster {}",
+            self.book_a, code_a, cluster_a
+        )?;
+        writeln!(
+            out,
+            "{} is book {} in cluster {}",
+            self.book_b, code_b, cluster_b
+        )?;
+        if cluster_a != cluster_b {
+            writeln!(out, "the books are not in the same cluster")?;
+            return Ok(());
+        }
+
+        let graph = if let Some(path) = &self.graph_file {
+            info!("loading graph from {}", path.display());
+            let mut graph = load_graph(path)?;
+            graph.retain_nodes(|g, n| g.node_weight(n).unwrap().cluster == cluster_a);
+            graph
+        } else {
+            info!("loading cluster {} from graph tables", cluster_a);
+            load_cluster_graph(&[cluster_a])?
+        };
+        info!(
+            "cluster has {} nodes and {} edges",
+            graph.node_count(),
+            graph.edge_count()
+        );
+
+        let find = |code| {
+            graph
+                .node_indices()
+                .find(|n| graph.node_weight(*n).unwrap().code == code)
+                .ok_or_else(|| anyhow!("book code {} missing from cluster graph", code))
+        };
+        let node_a = find(code_a)?;
+        let node_b = find(code_b)?;
+
+        let paths = shortest_paths(&graph, node_a, node_b, self.max_paths);
+        if paths.is_empty() {
+            writeln!(out, "no path found, is the graph out of date?")?;
+            return Ok(());
+        }
+
+        let mut pairs = Vec::new();
+        for path in &paths {
+            for pair in path.windows(2) {
+                pairs.push((graph[pair[0]].code, graph[pair[1]].code));
+            }
+        }
+        let sources = lookup_edge_sources(&pairs)?;
+
+        let cuts = CutAnalysis::compute(&graph);
+        let bridges: HashSet<EdgeIndex> = cuts.bridges(&graph).into_iter().collect();
+        let mut path_bridges = Vec::new();
+
+        writeln!(
+            out,
+            "found {} shortest path(s) of length {}",
+            paths.len(),
+            paths[0].len() - 1
+        )?;
+        for (i, path) in paths.iter().enumerate() {
+            writeln!(out, "path {}:", i + 1)?;
+            writeln!(out, "    {}", describe(&graph[path[0]]))?;
+            for pair in path.windows(2) {
+                let src = &graph[pair[0]];
+                let dst = &graph[pair[1]];
+                let key = (src.code.min(dst.code), src.code.max(dst.code));
+                let via = match sources.get(&key) {
+                    Some(names) => names.join(", "),
+                    None => "unknown source".to_string(),
+                };
+                let edge = graph.find_edge(pair[0], pair[1]).unwrap();
+                if bridges.contains(&edge) {
+                    writeln!(out, "      | {} (bridge)", via)?;
+                    if !path_bridges.contains(&edge) {
+                        path_bridges.push(edge);
+                    }
+                } else {
+                    writeln!(out, "      | {}", via)?;
+                }
+                writeln!(out, "    {}", describe(dst))?;
+            }
+        }
+
+        if path_bridges.is_empty() {
+            writeln!(out, "no single edge separates the books")?;
+        } else {
+            writeln!(out, "removing any of these edges would separate the books:")?;
+            for e in path_bridges {
+                let (s, d) = graph.edge_endpoints(e).unwrap();
+                writeln!(
+                    out,
+                    "    {} -- {}",
+                    describe(&graph[s]),
+                    describe(&graph[d])
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
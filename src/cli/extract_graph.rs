@@ -1,45 +1,150 @@
 //! Graph extraction program.
+use std::collections::HashSet;
 use std::convert::From;
 use std::path::PathBuf;
 
-use crate::graph::{load_graph, save_gml};
+use crate::graph::columnar::{is_columnar_path, ColumnarGraph};
+use crate::graph::paths::ego_nodes;
+use crate::graph::*;
+use crate::ids::codes::{BookRef, NS_ISBN};
 use crate::prelude::*;
 
 /// Extract a subgraph.
+///
+/// The output format is determined by the output file's extension, and can be
+/// GML (`.gml`), GraphML (`.graphml`), DOT (`.dot` or `.gv`), node-link JSON
+/// (`.json`), or a CSV edge list (`.csv`).
 #[derive(Args, Debug)]
 #[command(name = "extract-graph")]
 pub struct ExtractGraph {
     #[arg(long = "graph-file")]
     graph_file: Option<PathBuf>,
 
-    #[arg(short = 'c', long = "cluster")]
-    cluster: Option<i32>,
+    /// Extract the cluster CLUSTER (can be repeated).
+    #[arg(short = 'c', long = "cluster", name = "CLUSTER")]
+    clusters: Vec<i32>,
+
+    /// Extract the neighborhood of BOOK (a book code, NS:ID, or ISBN).
+    #[arg(long = "ego", name = "BOOK")]
+    ego: Option<BookRef>,
+
+    /// Include nodes within N hops of the ego book.
+    #[arg(long = "radius", name = "N", default_value_t = 1, requires = "BOOK")]
+    radius: usize,
 
     #[arg(long = "output", short = 'o')]
     out_file: Option<PathBuf>,
 }
 
-impl Command for ExtractGraph {
-    fn exec(&self) -> Result<()> {
-        let path = match &self.graph_file {
-            Some(p) => p.clone(),
-            None => PathBuf::from("book-links/book-graph.mp.zst"),
-        };
+/// Find the node for a book reference.
+fn find_node(graph: &IdGraph, book: &BookRef) -> Option<IdNode> {
+    graph.node_indices().find(|n| {
+        let node = graph.node_weight(*n).unwrap();
+        match book {
+            BookRef::ISBN(isbn) => {
+                NS_ISBN.from_code(node.code).is_some() && node.label.as_ref() == Some(isbn)
+            }
+            b => b.code() == Some(node.code),
+        }
+    })
+}
+
+impl ExtractGraph {
+    /// Extract the subgraph from a columnar graph, only reading the nodes we need.
+    fn extract_columnar(&self, path: &Path) -> Result<IdGraph> {
+        info!("opening columnar graph {}", path.display());
+        let cg = ColumnarGraph::open(path)?;
+        info!(
+            "graph has {} nodes and {} edges in {} clusters",
+            cg.node_count(),
+            cg.edge_count(),
+            cg.cluster_count()
+        );
+
+        let mut nodes: Option<Vec<usize>> = None;
+        if !self.clusters.is_empty() {
+            let mut cn = Vec::new();
+            for c in &self.clusters {
+                let range = cg
+                    .cluster_nodes(*c)
+                    .ok_or_else(|| anyhow!("cluster {} not found", c))?;
+                cn.extend(range);
+            }
+            nodes = Some(cn);
+        }
+
+        if let Some(book) = &self.ego {
+            let center = match book {
+                BookRef::ISBN(isbn) => {
+                    // ISBNs are only labels, so we need to scan for them
+                    let mut found = None;
+                    for n in 0..cg.node_count() {
+                        if NS_ISBN.from_code(cg.code(n)).is_some()
+                            && cg.label(n)? == Some(isbn.as_str())
+                        {
+                            found = Some(n);
+                            break;
+                        }
+                    }
+                    found
+                }
+                b => b.code().and_then(|c| cg.find_code(c)),
+            };
+            let center = center.ok_or_else(|| anyhow!("book {} not found in graph", book))?;
+            let mut ego = cg.ego_nodes(center, self.radius);
+            if let Some(cn) = &nodes {
+                let cn: HashSet<usize> = cn.iter().copied().collect();
+                ego.retain(|n| cn.contains(n));
+            }
+            nodes = Some(ego);
+        }
+
+        match nodes {
+            Some(nodes) => cg.subgraph(nodes),
+            None => cg.to_graph(),
+        }
+    }
 
-        info!("loading graph from {}", path.to_string_lossy());
+    /// Extract the subgraph by loading the full graph.
+    fn extract_full(&self, path: &Path) -> Result<IdGraph> {
+        info!("loading graph from {}", path.display());
         let mut graph = load_graph(path)?;
 
-        if let Some(c) = &self.cluster {
+        if !self.clusters.is_empty() {
             graph.retain_nodes(|g, n| {
                 let book = g.node_weight(n).unwrap();
-                book.cluster == *c
+                self.clusters.contains(&book.cluster)
             });
         }
 
+        if let Some(book) = &self.ego {
+            let center = find_node(&graph, book)
+                .ok_or_else(|| anyhow!("book {} not found in graph", book))?;
+            let ego = ego_nodes(&graph, center, self.radius);
+            graph.retain_nodes(|_g, n| ego.contains(&n));
+        }
+
+        Ok(graph)
+    }
+}
+
+impl Command for ExtractGraph {
+    fn exec(&self) -> Result<()> {
+        let path = match &self.graph_file {
+            Some(p) => p.clone(),
+            None => PathBuf::from("book-links/book-graph.bdgraph"),
+        };
+
+        let graph = if is_columnar_path(&path) {
+            self.extract_columnar(&path)?
+        } else {
+            self.extract_full(&path)?
+        };
+
         info!("filtered graph to {} nodes", graph.node_count());
 
         if let Some(outf) = &self.out_file {
-            save_gml(&graph, outf)?;
+            export_graph(&graph, outf)?;
         }
 
         Ok(())
@@ -3,6 +3,7 @@ use std::path::{Path, PathBuf};
 use clap::Args;
 
 use crate::arrow::*;
+use crate::graph::hierarchy::scan_record_works;
 use crate::ids::codes::{NS_GR_BOOK, NS_GR_WORK};
 use crate::prelude::*;
 
@@ -22,6 +23,10 @@ pub struct CICommand {
     #[arg(long = "native-works")]
     native_works: bool,
 
+    /// Cluster using the works within book clusters (from the cluster hierarchy).
+    #[arg(long = "cluster-works", conflicts_with = "native_works")]
+    cluster_works: bool,
+
     /// Write output to FILE.
     #[arg(short = 'o', long = "output", name = "FILE")]
     output: PathBuf,
@@ -37,6 +42,7 @@ enum ActionType {
 enum AggType {
     Clusters,
     NativeWorks,
+    ClusterWorks,
 }
 
 #[derive(Debug)]
@@ -58,6 +64,8 @@ impl CICommand {
         };
         if self.native_works {
             op = op.native_works();
+        } else if self.cluster_works {
+            op = op.cluster_works();
         }
 
         op.cluster()
@@ -91,6 +99,14 @@ impl ClusterOp {
         }
     }
 
+    /// Set operation to cluster with the works in the cluster hierarchy.
+    pub fn cluster_works(self) -> ClusterOp {
+        ClusterOp {
+            clusters: AggType::ClusterWorks,
+            ..self
+        }
+    }
+
     /// Run the clustering operation.
     pub fn cluster(self) -> Result<()> {
         let interactions = self.load_interactions()?;
@@ -127,6 +143,18 @@ impl ClusterOp {
             &[col("book_id")],
             JoinType::Inner.into(),
         );
+
+        let data = if self.clusters == AggType::ClusterWorks {
+            let works = scan_record_works("")?;
+            data.join(
+                works,
+                &[col("book_id") + lit(NS_GR_BOOK.base())],
+                &[col("book_code")],
+                JoinType::Left.into(),
+            )
+        } else {
+            data
+        };
         Ok(data)
     }
 
@@ -151,6 +179,12 @@ impl ClusterOp {
                     .then(col("work_id") + lit(NS_GR_WORK.base()))
                     .otherwise(col("book_id") + lit(NS_GR_BOOK.base()))
             }
+            AggType::ClusterWorks => {
+                info!("grouping by works within clusters");
+                when(col("work").is_not_null())
+                    .then(col("work"))
+                    .otherwise(col("book_id") + lit(NS_GR_BOOK.base()))
+            }
         }
     }
 
@@ -1,5 +1,6 @@
 use crate::goodreads::*;
 use crate::io::object::{ChunkWriter, ThreadObjectWriter, UnchunkWriter};
+use crate::io::{ErrorOpts, Rejects};
 use crate::prelude::*;
 use crate::util::logging::data_progress;
 use serde::de::DeserializeOwned;
@@ -25,25 +26,32 @@ pub struct ScanInput {
     /// Input file
     #[arg(name = "INPUT")]
     infile: PathBuf,
+
+    #[command(flatten)]
+    errors: ErrorOpts,
 }
 
-fn scan_gr<R, W>(path: &Path, proc: W) -> Result<()>
+fn scan_gr<R, W>(opts: &ScanInput, proc: W) -> Result<()>
 where
     W: ObjectWriter<R> + DataSink + Send + Sync + 'static,
     R: DeserializeOwned + Send + Sync + 'static,
 {
+    let path = opts.infile.as_path();
     let outs: Vec<_> = proc.output_files();
 
     info!("reading data from {}", path.display());
     let pb = data_progress(0);
     let read = LineProcessor::open_gzip(path, pb.clone())?;
+    let read = read.with_rejects(Rejects::open(&opts.errors, path)?);
     let proc = ChunkWriter::new(proc);
     let writer = ThreadObjectWriter::wrap(proc).with_name("output").spawn();
     let mut writer = UnchunkWriter::new(writer);
-    read.process_json(&mut writer)?;
+    let res = read.process_json(&mut writer);
     pb.finish_and_clear();
 
+    // finish the writer even if there were too many rejects, so output threads shut down
     writer.finish()?;
+    res?;
 
     for out in outs {
         let outf = out.as_path();
@@ -62,27 +70,27 @@ impl GRScan {
         match self {
             GRScan::Works(opts) => {
                 info!("scanning GoodReads works");
-                scan_gr(&opts.infile, work::WorkWriter::open()?)?;
+                scan_gr(opts, work::WorkWriter::open()?)?;
             }
             GRScan::Books(opts) => {
                 info!("scanning GoodReads books");
-                scan_gr(&opts.infile, book::BookWriter::open()?)?;
+                scan_gr(opts, book::BookWriter::open()?)?;
             }
             GRScan::Genres(opts) => {
                 info!("scanning GoodReads book genres");
-                scan_gr(&opts.infile, genres::BookGenreWriter::open()?)?;
+                scan_gr(opts, genres::BookGenreWriter::open()?)?;
             }
             GRScan::Authors(opts) => {
                 info!("scanning GoodReads book genres");
-                scan_gr(&opts.infile, author::AuthorWriter::open()?)?;
+                scan_gr(opts, author::AuthorWriter::open()?)?;
             }
             GRScan::Interactions(opts) => {
                 info!("scanning GoodReads interactions");
-                scan_gr(&opts.infile, interaction::IntWriter::open()?)?;
+                scan_gr(opts, interaction::IntWriter::open()?)?;
             }
             GRScan::Reviews(opts) => {
                 info!("scanning GoodReads reviews");
-                scan_gr(&opts.infile, review::ReviewWriter::open()?)?;
+                scan_gr(opts, review::ReviewWriter::open()?)?;
             }
         };
 
@@ -9,6 +9,7 @@ pub mod bx;
 pub mod cluster;
 pub mod cluster_books;
 pub mod collect_isbns;
+pub mod explain_link;
 pub mod extract_graph;
 pub mod filter_marc;
 pub mod goodreads;
@@ -69,6 +70,7 @@ pub enum RootCommand {
     ClusterBooks(cluster_books::ClusterBooks),
     IndexNames(index_names::IndexNames),
     ExtractGraph(extract_graph::ExtractGraph),
+    ExplainLink(explain_link::ExplainLink),
     CollectISBNS(collect_isbns::CollectISBNs),
     LinkISBNIds(link_isbns::LinkISBNIds),
     /// Commands for processing Amazon data.
@@ -95,6 +97,7 @@ wrap_subcommands!(ClusterCommand);
 enum AmazonCommand {
     ScanRatings(amazon::ScanRatings),
     ScanReviews(amazon::ScanReviews),
+    CollectAsins(amazon::CollectASINs),
     ClusterRatings(amazon::ClusterRatings),
 }
 
@@ -103,6 +106,8 @@ enum AmazonCommand {
 enum BXCommand {
     /// Extract BX from source data and clean.
     Extract(bx::Extract),
+    /// Assign IDs to BX books.
+    CollectIds(bx::CollectIds),
     /// Match BX interactions with clusters.
     ClusterActions(bx::Cluster),
 }
@@ -111,10 +116,18 @@ enum BXCommand {
 #[derive(Subcommand, Debug)]
 pub enum ClusterCommand {
     Hash(cluster::hash::HashCmd),
+    Audit(cluster::audit::AuditClusters),
+    SaveGraph(cluster::save_graph::SaveGraph),
+    Evaluate(cluster::evaluate::EvaluateClusters),
+    SuspectIsbns(cluster::suspect_isbns::SuspectISBNs),
+    LinkTitles(cluster::link_titles::LinkTitles),
+    LinkTranslations(cluster::translations::LinkTranslations),
     ExtractBooks(cluster::books::ExtractBooks),
+    ExtractMetadata(cluster::metadata::ExtractMetadata),
     ExtractAuthors(cluster::authors::ClusterAuthors),
     ExtractAuthorGender(cluster::author_gender::AuthorGender),
     ExtractAuthorLanguage(cluster::author_language::AuthorLanguage),
+    ExtractOLLanguages(cluster::ol_languages::ExtractOLLanguages),
     ExtractClusterOLLanguage(cluster::cluster_ol_language::ClusterOLLanguage),
     ExtractClusterLocTranslation(cluster::cluster_ol_language::ClusterLocTranslation),
     ExxtractClusterDeduceLanguage(cluster::cluster_ol_language::ClusterDeduceLanguage),
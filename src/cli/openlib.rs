@@ -1,8 +1,10 @@
 use std::path::{Path, PathBuf};
 
-use serde::de::DeserializeOwned;
+use polars::prelude::*;
+use serde::de::{DeserializeOwned, IgnoredAny};
 
-use crate::io::LineProcessor;
+use crate::io::{ErrorOpts, LineProcessor, Rejects};
+use crate::openlib::interaction::RawInteraction;
 use crate::openlib::*;
 use crate::prelude::*;
 use crate::util::logging::data_progress;
@@ -14,6 +16,34 @@ struct Input {
     /// Input file
     #[arg(name = "INPUT")]
     infile: PathBuf,
+
+    #[command(flatten)]
+    errors: ErrorOpts,
+}
+
+#[derive(Args, Debug)]
+struct Inputs {
+    /// Input files
+    #[arg(name = "INPUT", required = true)]
+    infiles: Vec<PathBuf>,
+
+    #[command(flatten)]
+    errors: ErrorOpts,
+}
+
+#[derive(Args, Debug)]
+struct ClusterOpts {
+    /// Cluster ratings.
+    #[arg(long = "ratings")]
+    ratings: bool,
+
+    /// Cluster reading-log (add-to-shelf) actions.
+    #[arg(long = "add-actions", conflicts_with = "ratings")]
+    add_actions: bool,
+
+    /// Write output to FILE.
+    #[arg(short = 'o', long = "output", name = "FILE")]
+    output: PathBuf,
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -25,11 +55,25 @@ enum DataType {
 
     /// Parse OpenLibrary editions.
     ///
-    /// Authors and works must be processed first.
+    /// Authors, works, and redirects must be processed first.
     ScanEditions(Input),
 
+    /// Collect redirects and deletions from OpenLibrary dumps.
+    ScanRedirects(Inputs),
+
     /// Parse OpenLibrary authors.
     ScanAuthors(Input),
+
+    /// Parse the OpenLibrary ratings dump.
+    ScanRatings(Input),
+
+    /// Parse the OpenLibrary reading-log dump.
+    ScanReadingLog(Input),
+
+    /// Group ratings or reading-log actions by cluster.
+    ///
+    /// The work clusters must be extracted first.
+    ClusterInteractions(ClusterOpts),
 }
 
 /// Scan OpenLibrary data.
@@ -40,43 +84,155 @@ pub struct OpenLib {
     mode: DataType,
 }
 
+/// Open an OpenLibrary dump file, with its rejected-line policy.
+fn open_dump(path: &Path, errors: &ErrorOpts) -> Result<LineProcessor> {
+    info!("opening file {}", path.to_string_lossy());
+    let pb = data_progress(0);
+    let input = LineProcessor::open_gzip(path, pb.clone())?;
+    Ok(input.with_rejects(Rejects::open(errors, path)?))
+}
+
+/// Read the entries of an OpenLibrary dump file.
+fn scan_entries<R, F>(path: &Path, errors: &ErrorOpts, f: F) -> Result<()>
+where
+    R: DeserializeOwned + Send,
+    F: FnMut(Entry<R>) -> Result<()>,
+{
+    open_dump(path, errors)?.for_each_record(f)?;
+
+    Ok(())
+}
+
 /// Helper function to route OpenLibrary data.
-fn scan_openlib<R, Proc>(path: &Path, proc: Proc) -> Result<()>
+///
+/// Redirect and delete rows are skipped, since they are not records.
+fn scan_openlib<R, Proc>(opts: &Input, proc: Proc) -> Result<()>
 where
     Proc: ObjectWriter<Row<R>>,
-    R: DeserializeOwned,
+    R: DeserializeOwned + Send,
 {
     let mut proc = proc;
-    let mut nlines = 0;
-    info!("opening file {}", path.to_string_lossy());
-    let pb = data_progress(0);
-    let input = LineProcessor::open_gzip(path, pb.clone())?;
-
-    for line in input.records() {
-        nlines += 1;
-        if !line.is_ok() {
-            error!("parse error on line {}", nlines);
+    let mut nskipped = 0;
+    scan_entries(&opts.infile, &opts.errors, |entry| match entry {
+        Entry::Record(row) => proc.write_object(row),
+        _ => {
+            nskipped += 1;
+            Ok(())
         }
-        let row: Row<R> = line?;
-        proc.write_object(row)?;
-    }
+    })?;
+    info!("skipped {} redirect and delete rows", nskipped);
+
+    proc.finish()?;
+
+    Ok(())
+}
+
+/// Helper function to route OpenLibrary ratings and reading-log data.
+fn scan_interactions<Proc>(opts: &Input, proc: Proc) -> Result<()>
+where
+    Proc: ObjectWriter<RawInteraction>,
+{
+    let mut proc = proc;
+    open_dump(&opts.infile, &opts.errors)?.for_each_record(|row| proc.write_object(row))?;
 
     proc.finish()?;
 
     Ok(())
 }
 
+/// Group interactions by the clusters of their works.
+fn cluster_interactions(opts: &ClusterOpts) -> Result<()> {
+    let (path, aggs) = if opts.ratings {
+        (
+            "ratings.parquet",
+            vec![
+                col("rating").median().alias("rating"),
+                col("rating").last().alias("last_rating"),
+                col("timestamp").min().alias("first_time"),
+                col("timestamp").max().alias("last_time"),
+                col("item").count().alias("nratings"),
+            ],
+        )
+    } else if opts.add_actions {
+        (
+            "reading-log.parquet",
+            vec![
+                col("timestamp").min().alias("first_time"),
+                col("timestamp").max().alias("last_time"),
+                col("item").count().alias("nactions"),
+            ],
+        )
+    } else {
+        error!("must specify one of --add-actions or --ratings");
+        return Err(anyhow!("no operating mode specified"));
+    };
+
+    let data = LazyFrame::scan_parquet(path, default())?;
+    // anonymous interactions cannot form user-item pairs
+    let data = data.filter(col("user").is_not_null());
+    let users = data
+        .clone()
+        .select([col("user").count().cast(polars::prelude::DataType::Int64)])
+        .collect()?;
+    if users.column("user")?.i64()?.get(0).unwrap_or(0) == 0 {
+        error!("{} has no interactions with a user", path);
+        return Err(anyhow!("cannot cluster anonymous interactions"));
+    }
+    let clusters = LazyFrame::scan_parquet("work-clusters.parquet", default())?;
+    let data = data.join(
+        clusters,
+        [col("work").cast(polars::prelude::DataType::Int32)],
+        [col("work_id")],
+        JoinType::Inner.into(),
+    );
+    let mut cols = vec![col("user"), col("cluster").alias("item"), col("timestamp")];
+    if opts.ratings {
+        cols.push(col("rating"));
+    }
+    let actions = data
+        .select(cols)
+        .sort("timestamp", default())
+        .group_by([col("user"), col("item")])
+        .agg(aggs);
+
+    info!("collecting results");
+    let actions = actions.collect()?;
+
+    info!("writing {} actions to {:?}", actions.height(), &opts.output);
+    save_df_parquet(actions, &opts.output)?;
+
+    Ok(())
+}
+
 impl Command for OpenLib {
     fn exec(&self) -> Result<()> {
         match &self.mode {
             DataType::ScanAuthors(opts) => {
-                scan_openlib(&opts.infile, AuthorProcessor::new()?)?;
+                scan_openlib(opts, AuthorProcessor::new()?)?;
             }
             DataType::ScanWorks(opts) => {
-                scan_openlib(&opts.infile, WorkProcessor::new()?)?;
+                scan_openlib(opts, WorkProcessor::new()?)?;
             }
             DataType::ScanEditions(opts) => {
-                scan_openlib(&opts.infile, EditionProcessor::new()?)?;
+                scan_openlib(opts, EditionProcessor::new()?)?;
+            }
+            DataType::ScanRatings(opts) => {
+                scan_interactions(opts, RatingProcessor::new()?)?;
+            }
+            DataType::ScanReadingLog(opts) => {
+                scan_interactions(opts, ReadingLogProcessor::new()?)?;
+            }
+            DataType::ClusterInteractions(opts) => {
+                cluster_interactions(opts)?;
+            }
+            DataType::ScanRedirects(opts) => {
+                let mut proc = RedirectProcessor::new()?;
+                for path in &opts.infiles {
+                    scan_entries::<IgnoredAny, _>(path, &opts.errors, |entry| {
+                        proc.write_object(entry)
+                    })?;
+                }
+                proc.finish()?;
             }
         };
 
@@ -1,4 +1,7 @@
 //! GoodReads book schemas and record processing.
+use std::error::Error as StdError;
+use std::str::FromStr;
+
 use parquet_derive::ParquetRecordWriter;
 use serde::Deserialize;
 
@@ -6,11 +9,13 @@ use crate::arrow::*;
 use crate::cleaning::isbns::*;
 use crate::ids::codes::NS_GR_BOOK;
 use crate::ids::codes::NS_GR_WORK;
+use crate::language::marc_language;
 use crate::parsing::*;
 use crate::prelude::*;
 
 const ID_FILE: &'static str = "gr-book-ids.parquet";
 const INFO_FILE: &'static str = "gr-book-info.parquet";
+const DETAIL_FILE: &str = "gr-book-details.parquet";
 const SERIES_FILE: &'static str = "gr-book-series.parquet";
 const AUTHOR_FILE: &'static str = "gr-book-authors.parquet";
 
@@ -34,6 +39,30 @@ pub struct RawBook {
     pub publication_day: String,
     #[serde(default)]
     pub series: Vec<String>,
+    #[serde(default)]
+    pub language_code: String,
+    #[serde(default)]
+    pub country_code: String,
+    #[serde(default)]
+    pub num_pages: String,
+    #[serde(default)]
+    pub publisher: String,
+    #[serde(default)]
+    pub format: String,
+    #[serde(default)]
+    pub edition_information: String,
+    #[serde(default)]
+    pub is_ebook: String,
+    #[serde(default)]
+    pub average_rating: String,
+    #[serde(default)]
+    pub ratings_count: String,
+    #[serde(default)]
+    pub text_reviews_count: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub image_url: String,
 }
 
 /// The raw author records from JSON.
@@ -64,6 +93,69 @@ pub struct BookRecord {
     pub pub_month: Option<u8>,
 }
 
+/// book detail records, with the descriptive fields not in [BookRecord]
+#[derive(ParquetRecordWriter)]
+pub struct BookDetailRecord {
+    pub book_id: i32,
+    /// The language code as GoodReads records it.
+    pub language: Option<String>,
+    /// The language as an ISO 639-2 (MARC) code.
+    pub lang_code: Option<String>,
+    pub country: Option<String>,
+    pub num_pages: Option<u32>,
+    pub publisher: Option<String>,
+    pub format: Option<String>,
+    pub edition_info: Option<String>,
+    pub is_ebook: Option<bool>,
+    pub average_rating: Option<f32>,
+    pub ratings_count: Option<u32>,
+    pub text_reviews_count: Option<u32>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+}
+
+/// Clean a GoodReads cover image URL, dropping the placeholder for books without covers.
+fn clean_image_url(url: &str) -> Option<String> {
+    trim_owned(url).filter(|u| !u.contains("/nophoto/"))
+}
+
+/// Parse an optional detail field, counting values that fail to parse.
+///
+/// The descriptive fields are not needed for linking, so bad values become nulls
+/// instead of failing the record.
+fn parse_lenient<T: FromStr>(s: &str, failures: &mut usize) -> Option<T>
+where
+    T::Err: StdError + Sync + Send + 'static,
+{
+    let res = parse_opt(s);
+    if res.is_err() {
+        *failures += 1;
+    }
+    res.ok().flatten()
+}
+
+/// Make the detail record for a book.
+fn detail_record(book_id: i32, row: &RawBook, failures: &mut usize) -> BookDetailRecord {
+    let language = trim_owned(&row.language_code);
+    let lang_code = language.as_deref().and_then(marc_language);
+    BookDetailRecord {
+        book_id,
+        language,
+        lang_code,
+        country: trim_owned(&row.country_code),
+        num_pages: parse_lenient(&row.num_pages, failures),
+        publisher: trim_owned(&row.publisher),
+        format: trim_owned(&row.format),
+        edition_info: trim_owned(&row.edition_information),
+        is_ebook: parse_lenient(&row.is_ebook, failures),
+        average_rating: parse_lenient(&row.average_rating, failures),
+        ratings_count: parse_lenient(&row.ratings_count, failures),
+        text_reviews_count: parse_lenient(&row.text_reviews_count, failures),
+        description: trim_owned(&row.description),
+        image_url: clean_image_url(&row.image_url),
+    }
+}
+
 /// book series linking records
 #[derive(ParquetRecordWriter)]
 pub struct BookSeriesRecord {
@@ -83,28 +175,33 @@ pub struct BookAuthorRecord {
 pub struct BookWriter {
     id_out: TableWriter<BookIdRecord>,
     info_out: TableWriter<BookRecord>,
+    detail_out: TableWriter<BookDetailRecord>,
     author_out: TableWriter<BookAuthorRecord>,
     series_out: TableWriter<BookSeriesRecord>,
+    detail_failures: usize,
 }
 
 impl BookWriter {
     pub fn open() -> Result<BookWriter> {
         let id_out = TableWriter::open(ID_FILE)?;
         let info_out = TableWriter::open(INFO_FILE)?;
+        let detail_out = TableWriter::open(DETAIL_FILE)?;
         let author_out = TableWriter::open(AUTHOR_FILE)?;
         let series_out = TableWriter::open(SERIES_FILE)?;
         Ok(BookWriter {
             id_out,
             info_out,
+            detail_out,
             author_out,
             series_out,
+            detail_failures: 0,
         })
     }
 }
 
 impl DataSink for BookWriter {
     fn output_files<'a>(&'a self) -> Vec<PathBuf> {
-        path_list(&[ID_FILE, INFO_FILE, AUTHOR_FILE, SERIES_FILE])
+        path_list(&[ID_FILE, INFO_FILE, DETAIL_FILE, AUTHOR_FILE, SERIES_FILE])
     }
 }
 
@@ -143,6 +240,9 @@ impl ObjectWriter<RawBook> for BookWriter {
             pub_month,
         })?;
 
+        self.detail_out
+            .write_object(detail_record(book_id, &row, &mut self.detail_failures))?;
+
         for author in row.authors {
             self.author_out.write_object(BookAuthorRecord {
                 book_id,
@@ -160,10 +260,52 @@ impl ObjectWriter<RawBook> for BookWriter {
     }
 
     fn finish(self) -> Result<usize> {
+        if self.detail_failures > 0 {
+            warn!(
+                "{} book detail values failed to parse and were dropped",
+                self.detail_failures
+            );
+        }
         self.id_out.finish()?;
         self.info_out.finish()?;
+        self.detail_out.finish()?;
         self.author_out.finish()?;
         self.series_out.finish()?;
         Ok(0)
     }
 }
+
+#[test]
+fn test_book_details() {
+    let json = r#"{"book_id": "5", "work_id": "", "isbn": "", "isbn13": "", "asin": "",
+        "language_code": " en-US ", "country_code": "US", "num_pages": "256",
+        "publisher": "Tor", "is_ebook": "false", "average_rating": "3.92",
+        "ratings_count": "", "text_reviews_count": "many", "image_url":
+        "https://s.gr-assets.com/assets/nophoto/book/111x148-bcc042a9c91a29c1d680899eff700a03.png"}"#;
+    let row: RawBook = serde_json::from_str(json).expect("parse failed");
+    let mut failures = 0;
+    let rec = detail_record(5, &row, &mut failures);
+    assert_eq!(rec.book_id, 5);
+    assert_eq!(rec.language.as_deref(), Some("en-US"));
+    assert_eq!(rec.lang_code.as_deref(), Some("eng"));
+    assert_eq!(rec.country.as_deref(), Some("US"));
+    assert_eq!(rec.num_pages, Some(256));
+    assert_eq!(rec.publisher.as_deref(), Some("Tor"));
+    assert_eq!(rec.format, None);
+    assert_eq!(rec.is_ebook, Some(false));
+    assert_eq!(rec.average_rating, Some(3.92));
+    assert_eq!(rec.ratings_count, None);
+    assert_eq!(rec.text_reviews_count, None);
+    assert_eq!(rec.description, None);
+    assert_eq!(rec.image_url, None);
+    assert_eq!(failures, 1);
+}
+
+#[test]
+fn test_clean_image_url() {
+    assert_eq!(clean_image_url(""), None);
+    assert_eq!(
+        clean_image_url(" https://images.gr-assets.com/books/1/5.jpg "),
+        Some("https://images.gr-assets.com/books/1/5.jpg".to_string())
+    );
+}
@@ -0,0 +1,484 @@
+//! Columnar, memory-mappable graph storage.
+//!
+//! The serialized petgraph has to be decoded in full before it can be used, which
+//! takes minutes and most of the RAM on the full book graph.  This format instead
+//! lays the graph out in flat little-endian arrays so it can be memory-mapped, and
+//! extracting one cluster or a neighborhood only touches the pages it needs.
+//!
+//! The file is a fixed header followed by 8-byte-aligned sections:
+//!
+//! | section           | type        | length        |
+//! |-------------------|-------------|---------------|
+//! | node codes        | `i32`       | nodes         |
+//! | node clusters     | `i32`       | nodes         |
+//! | code index        | `u32`       | nodes         |
+//! | adjacency offsets | `u64`       | nodes + 1     |
+//! | adjacency         | `u32`       | 2 × edges     |
+//! | cluster IDs       | `i32`       | clusters      |
+//! | cluster offsets   | `u64`       | clusters + 1  |
+//! | label offsets     | `u64`       | nodes + 1     |
+//! | labels            | UTF-8 bytes | label bytes   |
+//!
+//! Nodes are sorted by cluster and then code, so each cluster is a contiguous range
+//! of nodes; the code index lists node positions in code order for lookup.  The
+//! adjacency is in compressed sparse row form, with each edge stored in both
+//! directions.  A node with an empty label has no label.
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use log::*;
+use memmap2::Mmap;
+
+use super::{BookID, IdGraph, IdNode};
+
+const MAGIC: &[u8; 8] = b"BDGRAPH1";
+const HEADER_LEN: usize = 8 + 4 * 8;
+
+/// Check whether a path names a columnar graph file.
+pub fn is_columnar_path<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().extension().and_then(|e| e.to_str()) == Some("bdgraph")
+}
+
+fn pad8(n: usize) -> usize {
+    (n + 7) & !7
+}
+
+/// Section layout for a graph of a given size.
+#[derive(Debug, Clone)]
+struct Layout {
+    n_nodes: usize,
+    n_adj: usize,
+    n_clusters: usize,
+    label_bytes: usize,
+    codes: usize,
+    clusters: usize,
+    code_index: usize,
+    adj_offsets: usize,
+    adj: usize,
+    cluster_ids: usize,
+    cluster_offsets: usize,
+    label_offsets: usize,
+    labels: usize,
+    end: usize,
+}
+
+impl Layout {
+    fn new(n_nodes: usize, n_adj: usize, n_clusters: usize, label_bytes: usize) -> Layout {
+        let codes = HEADER_LEN;
+        let clusters = codes + pad8(n_nodes * 4);
+        let code_index = clusters + pad8(n_nodes * 4);
+        let adj_offsets = code_index + pad8(n_nodes * 4);
+        let adj = adj_offsets + (n_nodes + 1) * 8;
+        let cluster_ids = adj + pad8(n_adj * 4);
+        let cluster_offsets = cluster_ids + pad8(n_clusters * 4);
+        let label_offsets = cluster_offsets + (n_clusters + 1) * 8;
+        let labels = label_offsets + (n_nodes + 1) * 8;
+        let end = labels + label_bytes;
+        Layout {
+            n_nodes,
+            n_adj,
+            n_clusters,
+            label_bytes,
+            codes,
+            clusters,
+            code_index,
+            adj_offsets,
+            adj,
+            cluster_ids,
+            cluster_offsets,
+            label_offsets,
+            labels,
+            end,
+        }
+    }
+}
+
+/// Writer that tracks its position to pad sections.
+struct SectionWriter<W: Write> {
+    out: W,
+    pos: usize,
+}
+
+impl<W: Write> SectionWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.out.write_all(bytes)?;
+        self.pos += bytes.len();
+        Ok(())
+    }
+
+    /// Pad to the start of a section, checking we are where the layout expects.
+    fn start(&mut self, offset: usize) -> Result<()> {
+        let pad = pad8(self.pos) - self.pos;
+        self.write(&[0u8; 8][..pad])?;
+        if self.pos != offset {
+            return Err(anyhow!(
+                "section misaligned: at {}, expected {}",
+                self.pos,
+                offset
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Save a graph in columnar format.
+pub fn save_columnar<P: AsRef<Path>>(graph: &IdGraph, path: P) -> Result<()> {
+    let path = path.as_ref();
+    info!("saving columnar graph to {}", path.display());
+
+    // order nodes by cluster, then code
+    let mut order: Vec<IdNode> = graph.node_indices().collect();
+    order.sort_unstable_by_key(|n| {
+        let b = graph.node_weight(*n).unwrap();
+        (b.cluster, b.code)
+    });
+    let mut pos = vec![0u32; graph.node_count()];
+    for (i, n) in order.iter().enumerate() {
+        pos[n.index()] = i as u32;
+    }
+    let books: Vec<&BookID> = order
+        .iter()
+        .map(|n| graph.node_weight(*n).unwrap())
+        .collect();
+
+    let mut code_index: Vec<u32> = (0..books.len() as u32).collect();
+    code_index.sort_unstable_by_key(|i| books[*i as usize].code);
+
+    let mut cluster_ids = Vec::new();
+    let mut cluster_offsets = Vec::new();
+    for (i, b) in books.iter().enumerate() {
+        if cluster_ids.last() != Some(&b.cluster) {
+            cluster_ids.push(b.cluster);
+            cluster_offsets.push(i as u64);
+        }
+    }
+    cluster_offsets.push(books.len() as u64);
+
+    let label_bytes = books
+        .iter()
+        .map(|b| b.label.as_ref().map(|l| l.len()).unwrap_or(0))
+        .sum();
+    let layout = Layout::new(
+        books.len(),
+        graph.edge_count() * 2,
+        cluster_ids.len(),
+        label_bytes,
+    );
+
+    let out = BufWriter::new(File::create(path)?);
+    let mut w = SectionWriter { out, pos: 0 };
+    w.write(MAGIC)?;
+    for n in [
+        layout.n_nodes,
+        layout.n_adj,
+        layout.n_clusters,
+        layout.label_bytes,
+    ] {
+        w.write(&(n as u64).to_le_bytes())?;
+    }
+
+    w.start(layout.codes)?;
+    for b in &books {
+        w.write(&b.code.to_le_bytes())?;
+    }
+    w.start(layout.clusters)?;
+    for b in &books {
+        w.write(&b.cluster.to_le_bytes())?;
+    }
+    w.start(layout.code_index)?;
+    for i in &code_index {
+        w.write(&i.to_le_bytes())?;
+    }
+
+    w.start(layout.adj_offsets)?;
+    let mut off = 0u64;
+    w.write(&off.to_le_bytes())?;
+    for n in &order {
+        off += graph.neighbors(*n).count() as u64;
+        w.write(&off.to_le_bytes())?;
+    }
+    w.start(layout.adj)?;
+    for n in &order {
+        let mut adj: Vec<u32> = graph.neighbors(*n).map(|m| pos[m.index()]).collect();
+        adj.sort_unstable();
+        for a in adj {
+            w.write(&a.to_le_bytes())?;
+        }
+    }
+
+    w.start(layout.cluster_ids)?;
+    for c in &cluster_ids {
+        w.write(&c.to_le_bytes())?;
+    }
+    w.start(layout.cluster_offsets)?;
+    for o in &cluster_offsets {
+        w.write(&o.to_le_bytes())?;
+    }
+
+    w.start(layout.label_offsets)?;
+    let mut off = 0u64;
+    w.write(&off.to_le_bytes())?;
+    for b in &books {
+        off += b.label.as_ref().map(|l| l.len()).unwrap_or(0) as u64;
+        w.write(&off.to_le_bytes())?;
+    }
+    w.start(layout.labels)?;
+    for b in &books {
+        if let Some(l) = &b.label {
+            w.write(l.as_bytes())?;
+        }
+    }
+
+    if w.pos != layout.end {
+        return Err(anyhow!("wrote {} bytes, expected {}", w.pos, layout.end));
+    }
+    w.out.flush()?;
+    Ok(())
+}
+
+/// A memory-mapped columnar graph.
+///
+/// Nodes are identified by their position in the file.
+pub struct ColumnarGraph {
+    map: Mmap,
+    layout: Layout,
+}
+
+impl ColumnarGraph {
+    /// Open a columnar graph file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ColumnarGraph> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        // SAFETY: graph files are written once and not modified while in use
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < HEADER_LEN || &map[..8] != MAGIC {
+            return Err(anyhow!("{} is not a columnar graph file", path.display()));
+        }
+        let field = |i: usize| {
+            let start = 8 + i * 8;
+            u64::from_le_bytes(map[start..start + 8].try_into().unwrap()) as usize
+        };
+        let layout = Layout::new(field(0), field(1), field(2), field(3));
+        if map.len() != layout.end {
+            return Err(anyhow!(
+                "{}: expected {} bytes, found {}",
+                path.display(),
+                layout.end,
+                map.len()
+            ));
+        }
+        Ok(ColumnarGraph { map, layout })
+    }
+
+    fn u32_at(&self, section: usize, i: usize) -> u32 {
+        let start = section + i * 4;
+        u32::from_le_bytes(self.map[start..start + 4].try_into().unwrap())
+    }
+
+    fn i32_at(&self, section: usize, i: usize) -> i32 {
+        let start = section + i * 4;
+        i32::from_le_bytes(self.map[start..start + 4].try_into().unwrap())
+    }
+
+    fn u64_at(&self, section: usize, i: usize) -> usize {
+        let start = section + i * 8;
+        u64::from_le_bytes(self.map[start..start + 8].try_into().unwrap()) as usize
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.layout.n_nodes
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.layout.n_adj / 2
+    }
+
+    pub fn cluster_count(&self) -> usize {
+        self.layout.n_clusters
+    }
+
+    /// Get a node's book code.
+    pub fn code(&self, node: usize) -> i32 {
+        self.i32_at(self.layout.codes, node)
+    }
+
+    /// Get a node's cluster.
+    pub fn cluster(&self, node: usize) -> i32 {
+        self.i32_at(self.layout.clusters, node)
+    }
+
+    /// Get a node's label.
+    pub fn label(&self, node: usize) -> Result<Option<&str>> {
+        let start = self.u64_at(self.layout.label_offsets, node);
+        let end = self.u64_at(self.layout.label_offsets, node + 1);
+        if start == end {
+            Ok(None)
+        } else {
+            let base = self.layout.labels;
+            let bytes = &self.map[base + start..base + end];
+            Ok(Some(std::str::from_utf8(bytes)?))
+        }
+    }
+
+    /// Get a node's neighbors.
+    pub fn neighbors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = self.u64_at(self.layout.adj_offsets, node);
+        let end = self.u64_at(self.layout.adj_offsets, node + 1);
+        (start..end).map(move |i| self.u32_at(self.layout.adj, i) as usize)
+    }
+
+    /// Find the node with a book code.
+    pub fn find_code(&self, code: i32) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.layout.n_nodes);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let node = self.u32_at(self.layout.code_index, mid) as usize;
+            let c = self.code(node);
+            if c == code {
+                return Some(node);
+            } else if c < code {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        None
+    }
+
+    /// Get the range of nodes in a cluster.
+    pub fn cluster_nodes(&self, cluster: i32) -> Option<Range<usize>> {
+        let (mut lo, mut hi) = (0, self.layout.n_clusters);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let c = self.i32_at(self.layout.cluster_ids, mid);
+            if c == cluster {
+                let start = self.u64_at(self.layout.cluster_offsets, mid);
+                let end = self.u64_at(self.layout.cluster_offsets, mid + 1);
+                return Some(start..end);
+            } else if c < cluster {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        None
+    }
+
+    /// Find the nodes within `radius` hops of a node, including the node itself.
+    pub fn ego_nodes(&self, center: usize, radius: usize) -> Vec<usize> {
+        let mut seen = HashSet::new();
+        seen.insert(center);
+        let mut frontier = vec![center];
+        for _ in 0..radius {
+            let mut next = Vec::new();
+            for v in frontier {
+                for w in self.neighbors(v) {
+                    if seen.insert(w) {
+                        next.push(w);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        let mut nodes: Vec<usize> = seen.into_iter().collect();
+        nodes.sort_unstable();
+        nodes
+    }
+
+    /// Extract the subgraph induced by a set of nodes.
+    pub fn subgraph<I: IntoIterator<Item = usize>>(&self, nodes: I) -> Result<IdGraph> {
+        let mut graph = IdGraph::new_undirected();
+        let mut map = HashMap::new();
+        for n in nodes {
+            let id = graph.add_node(BookID {
+                code: self.code(n),
+                label: self.label(n)?.map(|s| s.to_string()),
+                cluster: self.cluster(n),
+            });
+            map.insert(n, id);
+        }
+        for (n, id) in &map {
+            // each edge is stored in both directions, so only add it from one end
+            for m in self.neighbors(*n).filter(|m| m > n) {
+                if let Some(mid) = map.get(&m) {
+                    graph.add_edge(*id, *mid, ());
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Load the whole graph into memory.
+    pub fn to_graph(&self) -> Result<IdGraph> {
+        self.subgraph(0..self.node_count())
+    }
+}
+
+#[cfg(test)]
+use super::test_graph;
+
+#[cfg(test)]
+fn save_test_graph() -> (tempfile::TempDir, std::path::PathBuf) {
+    // two clusters: a triangle (1, 2, 3) and an edge (0, 4)
+    let (mut g, n) = test_graph(5, &[(1, 2), (2, 3), (3, 1), (0, 4)]);
+    for (i, c) in [(0, 0), (1, 1), (2, 1), (3, 1), (4, 0)] {
+        g[n[i]].cluster = c;
+    }
+    g[n[2]].label = Some("two".into());
+    let dir = tempfile::tempdir().expect("tempdir failed");
+    let path = dir.path().join("test.bdgraph");
+    save_columnar(&g, &path).expect("save failed");
+    (dir, path)
+}
+
+#[test]
+fn test_columnar_roundtrip() {
+    let (_dir, path) = save_test_graph();
+    let cg = ColumnarGraph::open(&path).expect("open failed");
+    assert_eq!(cg.node_count(), 5);
+    assert_eq!(cg.edge_count(), 4);
+    assert_eq!(cg.cluster_count(), 2);
+
+    let g = cg.to_graph().expect("load failed");
+    assert_eq!(g.node_count(), 5);
+    assert_eq!(g.edge_count(), 4);
+}
+
+#[test]
+fn test_columnar_lookup() {
+    let (_dir, path) = save_test_graph();
+    let cg = ColumnarGraph::open(&path).expect("open failed");
+    let n2 = cg.find_code(2).expect("code not found");
+    assert_eq!(cg.code(n2), 2);
+    assert_eq!(cg.cluster(n2), 1);
+    assert_eq!(cg.label(n2).unwrap(), Some("two"));
+    assert_eq!(cg.label(cg.find_code(3).unwrap()).unwrap(), None);
+    assert_eq!(cg.neighbors(n2).count(), 2);
+    assert!(cg.find_code(7).is_none());
+}
+
+#[test]
+fn test_columnar_cluster() {
+    let (_dir, path) = save_test_graph();
+    let cg = ColumnarGraph::open(&path).expect("open failed");
+    let r = cg.cluster_nodes(1).expect("cluster not found");
+    assert_eq!(r.len(), 3);
+    let g = cg.subgraph(r).expect("subgraph failed");
+    assert_eq!(g.edge_count(), 3);
+    assert!(cg.cluster_nodes(5).is_none());
+}
+
+#[test]
+fn test_columnar_ego() {
+    let (_dir, path) = save_test_graph();
+    let cg = ColumnarGraph::open(&path).expect("open failed");
+    let n0 = cg.find_code(0).unwrap();
+    let ego = cg.ego_nodes(n0, 1);
+    assert_eq!(ego.len(), 2);
+    let g = cg.subgraph(ego).expect("subgraph failed");
+    assert_eq!(g.edge_count(), 1);
+}
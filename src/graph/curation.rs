@@ -0,0 +1,393 @@
+//! Manual curation overrides for the book graph.
+//!
+//! Some bad links cannot be fixed in the source data (publishers reuse ISBNs, and
+//! some records carry garbage ISBNs like `0000000000`), and some merges we know are
+//! right are missing from it.  A curation file lists overrides for these so they
+//! are applied reproducibly every time the graph is built.
+//!
+//! The YAML form has three lists:
+//!
+//! ```yaml
+//! block:
+//!   - "0000000000"
+//! cut:
+//!   - ["LOC:1234", "9780140449136"]
+//! force:
+//!   - ["OL-W:27448", "GR-W:1234"]
+//! ```
+//!
+//! The CSV form has `action`, `book`, and `other` columns with one override per row.
+//! Books are given as anything [BookRef] can parse; quote ISBNs in YAML so they are
+//! not read as numbers.
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::{read_to_string, File};
+use std::hash::Hash;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use log::*;
+use petgraph::visit::EdgeRef;
+use serde::Deserialize;
+
+use super::{IdGraph, IdNode};
+use crate::ids::codes::{BookRef, NS_ISBN};
+
+/// A single curation override.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Override {
+    /// Remove all edges touching a node, leaving it in a cluster by itself.
+    Block(BookRef),
+    /// Remove the edges between two nodes.
+    Cut(BookRef, BookRef),
+    /// Add an edge between two nodes.
+    Force(BookRef, BookRef),
+}
+
+impl Override {
+    fn refs(&self) -> Vec<&BookRef> {
+        match self {
+            Override::Block(a) => vec![a],
+            Override::Cut(a, b) => vec![a, b],
+            Override::Force(a, b) => vec![a, b],
+        }
+    }
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Override::Block(a) => write!(f, "block {}", a),
+            Override::Cut(a, b) => write!(f, "cut {} -- {}", a, b),
+            Override::Force(a, b) => write!(f, "force {} -- {}", a, b),
+        }
+    }
+}
+
+/// A list of curation overrides.
+#[derive(Debug, Clone, Default)]
+pub struct Curation {
+    pub overrides: Vec<Override>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CurationFile {
+    #[serde(default)]
+    block: Vec<String>,
+    #[serde(default)]
+    cut: Vec<(String, String)>,
+    #[serde(default)]
+    force: Vec<(String, String)>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CurationRow {
+    action: String,
+    book: String,
+    other: Option<String>,
+}
+
+fn parse_ref(book: &str) -> Result<BookRef> {
+    book.trim()
+        .parse()
+        .map_err(|e| anyhow!("invalid book {:?}: {}", book, e))
+}
+
+impl Curation {
+    /// Load a curation file, in YAML or CSV format depending on its extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Curation> {
+        let path = path.as_ref();
+        info!("loading curation overrides from {}", path.display());
+        let ext = path.extension().and_then(|e| e.to_str());
+        match ext {
+            Some("yaml") | Some("yml") => Curation::from_yaml(&read_to_string(path)?),
+            Some("csv") => Curation::from_csv(File::open(path)?),
+            _ => Err(anyhow!("unknown curation file type: {}", path.display())),
+        }
+    }
+
+    /// Parse curation overrides from YAML.
+    pub fn from_yaml(text: &str) -> Result<Curation> {
+        let file: CurationFile = serde_yaml::from_str(text)?;
+        let mut overrides = Vec::new();
+        for b in file.block {
+            overrides.push(Override::Block(parse_ref(&b)?));
+        }
+        for (a, b) in file.cut {
+            overrides.push(Override::Cut(parse_ref(&a)?, parse_ref(&b)?));
+        }
+        for (a, b) in file.force {
+            overrides.push(Override::Force(parse_ref(&a)?, parse_ref(&b)?));
+        }
+        Ok(Curation { overrides })
+    }
+
+    /// Parse curation overrides from CSV.
+    pub fn from_csv<R: std::io::Read>(input: R) -> Result<Curation> {
+        let mut rdr = csv::Reader::from_reader(input);
+        let mut overrides = Vec::new();
+        for row in rdr.deserialize() {
+            let row: CurationRow = row?;
+            let book = parse_ref(&row.book)?;
+            let other = row.other.as_deref().map(parse_ref).transpose()?;
+            let ovr = match (row.action.as_str(), other) {
+                ("block", None) => Override::Block(book),
+                ("cut", Some(other)) => Override::Cut(book, other),
+                ("force", Some(other)) => Override::Force(book, other),
+                (act, _) => return Err(anyhow!("invalid curation row for {}: {}", row.book, act)),
+            };
+            overrides.push(ovr);
+        }
+        Ok(Curation { overrides })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    /// Apply the curation overrides to a graph.
+    ///
+    /// Blocks and cuts are applied before forced edges, so a forced edge to a blocked
+    /// node is kept.  Overrides naming books that are not in the graph are skipped
+    /// with a warning.
+    pub(super) fn apply(&self, graph: &mut IdGraph, nodes: &HashMap<i32, IdNode>) -> Result<()> {
+        // ISBNs are resolved by label, so find the ones we need in one pass
+        let mut isbns: HashMap<&str, IdNode> = HashMap::new();
+        let wanted = self.isbns();
+        if !wanted.is_empty() {
+            for n in graph.node_indices() {
+                let book = graph.node_weight(n).unwrap();
+                if NS_ISBN.from_code(book.code).is_some() {
+                    if let Some(l) = book.label.as_deref().and_then(|l| wanted.get(l)) {
+                        isbns.insert(l, n);
+                    }
+                }
+            }
+        }
+        let resolved = self.resolve_with(|r| match r {
+            BookRef::ISBN(isbn) => isbns.get(isbn.as_str()).copied(),
+            r => r.code().and_then(|c| nodes.get(&c).copied()),
+        });
+
+        let seeds = resolved.iter().flat_map(|(_, ns)| ns.iter().copied());
+        let before = label_components(graph, seeds);
+
+        let filter = EdgeFilter::new(&resolved);
+        let ninit = graph.edge_count();
+        graph.retain_edges(|g, e| {
+            let (a, b) = g.edge_endpoints(e).unwrap();
+            filter.keep(a, b)
+        });
+        info!("curation removed {} edges", ninit - graph.edge_count());
+
+        let mut n_forced = 0;
+        for (a, b) in forced_edges(&resolved) {
+            if graph.find_edge(a, b).is_none() {
+                graph.add_edge(a, b, ());
+                n_forced += 1;
+            }
+        }
+        info!("curation added {} edges", n_forced);
+
+        let after = label_components(graph, before.keys().copied());
+        log_effects(&resolved, &before, &after);
+
+        Ok(())
+    }
+
+    /// Get the ISBNs named by the overrides.
+    pub(super) fn isbns(&self) -> HashSet<&str> {
+        let mut isbns = HashSet::new();
+        for ovr in &self.overrides {
+            for r in ovr.refs() {
+                if let BookRef::ISBN(isbn) = r {
+                    isbns.insert(isbn.as_str());
+                }
+            }
+        }
+        isbns
+    }
+
+    /// Resolve the overrides' books to nodes with a lookup function.  Overrides
+    /// naming books that cannot be found are skipped with a warning.
+    pub(super) fn resolve_with<N, F>(&self, mut lookup: F) -> Resolved<'_, N>
+    where
+        F: FnMut(&BookRef) -> Option<N>,
+    {
+        let mut resolved = Vec::with_capacity(self.overrides.len());
+        for ovr in &self.overrides {
+            let ns: Option<Vec<N>> = ovr.refs().into_iter().map(&mut lookup).collect();
+            match ns {
+                Some(ns) => resolved.push((ovr, ns)),
+                None => warn!("{}: book not in graph, skipping", ovr),
+            }
+        }
+        resolved
+    }
+}
+
+/// Curation overrides with their books resolved to nodes.
+pub(super) type Resolved<'a, N> = Vec<(&'a Override, Vec<N>)>;
+
+/// Filter for the edges removed by blocks and cuts.
+pub(super) struct EdgeFilter<N> {
+    blocked: HashSet<N>,
+    cuts: HashSet<(N, N)>,
+}
+
+impl<N: Hash + Eq + Ord + Copy> EdgeFilter<N> {
+    pub(super) fn new(resolved: &Resolved<'_, N>) -> EdgeFilter<N> {
+        let mut blocked = HashSet::new();
+        let mut cuts = HashSet::new();
+        for (ovr, ns) in resolved {
+            match ovr {
+                Override::Block(_) => {
+                    blocked.insert(ns[0]);
+                }
+                Override::Cut(..) => {
+                    cuts.insert((ns[0].min(ns[1]), ns[0].max(ns[1])));
+                }
+                Override::Force(..) => (),
+            }
+        }
+        EdgeFilter { blocked, cuts }
+    }
+
+    /// Check whether an edge survives curation.
+    pub(super) fn keep(&self, a: N, b: N) -> bool {
+        !(self.blocked.contains(&a)
+            || self.blocked.contains(&b)
+            || self.cuts.contains(&(a.min(b), a.max(b))))
+    }
+}
+
+/// Get the edges added by forced links.
+pub(super) fn forced_edges<'a, N: Copy>(
+    resolved: &'a Resolved<'_, N>,
+) -> impl Iterator<Item = (N, N)> + 'a {
+    resolved.iter().filter_map(|(ovr, ns)| match ovr {
+        Override::Force(..) => Some((ns[0], ns[1])),
+        _ => None,
+    })
+}
+
+/// Label the components containing a set of seed nodes.
+fn label_components<I: IntoIterator<Item = IdNode>>(
+    graph: &IdGraph,
+    seeds: I,
+) -> HashMap<IdNode, usize> {
+    let mut labels = HashMap::new();
+    let mut next = 0;
+    for seed in seeds {
+        if labels.contains_key(&seed) {
+            continue;
+        }
+        labels.insert(seed, next);
+        let mut queue = VecDeque::from([seed]);
+        while let Some(v) = queue.pop_front() {
+            for e in graph.edges(v) {
+                let w = e.target();
+                if let Entry::Vacant(slot) = labels.entry(w) {
+                    slot.insert(next);
+                    queue.push_back(w);
+                }
+            }
+        }
+        next += 1;
+    }
+    labels
+}
+
+/// Log how many clusters each override touched before and after curation.
+///
+/// Overrides touching the same cluster interact, so these counts are for the
+/// combined effect of all overrides on each override's clusters.
+pub(super) fn log_effects<N: Hash + Eq>(
+    resolved: &Resolved<'_, N>,
+    before: &HashMap<N, usize>,
+    after: &HashMap<N, usize>,
+) {
+    // what each original cluster turned into
+    let mut became: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for (n, b) in before {
+        became.entry(*b).or_default().insert(after[n]);
+    }
+
+    let mut n_changed = 0;
+    for (ovr, ns) in resolved {
+        let old: HashSet<usize> = ns.iter().map(|n| before[n]).collect();
+        let new: HashSet<usize> = old.iter().flat_map(|c| became[c].iter().copied()).collect();
+        if old.len() != 1 || new.len() != 1 {
+            n_changed += 1;
+        }
+        info!("{}: {} clusters became {}", ovr, old.len(), new.len());
+    }
+    info!(
+        "{} of {} overrides changed clustering",
+        n_changed,
+        resolved.len()
+    );
+}
+
+#[test]
+fn test_parse_yaml() {
+    let cur = Curation::from_yaml(
+        r#"
+block:
+  - "0000000000"
+cut:
+  - ["LOC:1234", "9780140449136"]
+force:
+  - ["OL-W:5", "GR-W:7"]
+"#,
+    )
+    .expect("parse failed");
+    assert_eq!(cur.overrides.len(), 3);
+    assert_eq!(
+        cur.overrides[0],
+        Override::Block(BookRef::ISBN("0000000000".into()))
+    );
+    assert!(matches!(cur.overrides[1], Override::Cut(..)));
+    assert!(matches!(cur.overrides[2], Override::Force(..)));
+}
+
+#[test]
+fn test_parse_yaml_partial() {
+    let cur = Curation::from_yaml("block: []\n").expect("parse failed");
+    assert!(cur.is_empty());
+}
+
+#[test]
+fn test_parse_csv() {
+    let text = "action,book,other\nblock,0000000000,\nforce,OL-W:5,GR-W:7\n";
+    let cur = Curation::from_csv(text.as_bytes()).expect("parse failed");
+    assert_eq!(cur.overrides.len(), 2);
+    assert!(matches!(cur.overrides[1], Override::Force(..)));
+}
+
+#[test]
+fn test_parse_csv_bad_action() {
+    let text = "action,book,other\nfrobnicate,0000000000,\n";
+    assert!(Curation::from_csv(text.as_bytes()).is_err());
+}
+
+#[cfg(test)]
+use super::test_graph;
+
+#[test]
+fn test_apply() {
+    // two paths 0-1-2 and 3-4, block 1 and force 2-3
+    let (mut g, n) = test_graph(5, &[(0, 1), (1, 2), (3, 4)]);
+    let nodes: HashMap<i32, IdNode> = n.iter().map(|v| (g[*v].code, *v)).collect();
+    let cur = Curation {
+        overrides: vec![
+            Override::Block(BookRef::Code(1)),
+            Override::Force(BookRef::Code(2), BookRef::Code(3)),
+        ],
+    };
+    cur.apply(&mut g, &nodes).expect("apply failed");
+    assert_eq!(g.edge_count(), 2);
+    assert!(g.find_edge(n[2], n[3]).is_some());
+    assert!(g.find_edge(n[0], n[1]).is_none());
+}
@@ -0,0 +1,282 @@
+//! Bridges and cut structure of the identifier graph.
+//!
+//! This uses the classic Hopcroft-Tarjan low-point depth-first search, implemented
+//! iteratively since book clusters can be far too deep for the call stack.
+use std::collections::HashMap;
+
+use petgraph::graph::{EdgeIndex, Edges};
+use petgraph::visit::EdgeRef;
+use petgraph::Undirected;
+
+use super::{IdGraph, IdNode};
+
+const UNVISITED: u32 = u32::MAX;
+
+/// Depth-first search results for finding graph cuts.
+pub struct CutAnalysis {
+    /// Discovery order of each node.
+    disc: Vec<u32>,
+    /// Lowest discovery order reachable from each node's DFS subtree with one back edge.
+    low: Vec<u32>,
+    /// The tree edge through which each node was discovered.
+    parent: Vec<Option<EdgeIndex>>,
+    /// Number of nodes in each node's DFS subtree.
+    size: Vec<u32>,
+    /// The DFS root of each node's component.
+    root: Vec<IdNode>,
+}
+
+impl CutAnalysis {
+    /// Run the low-point search over every component of a graph.
+    pub fn compute(graph: &IdGraph) -> CutAnalysis {
+        let n = graph.node_count();
+        let mut ca = CutAnalysis {
+            disc: vec![UNVISITED; n],
+            low: vec![0; n],
+            parent: vec![None; n],
+            size: vec![0; n],
+            root: vec![IdNode::end(); n],
+        };
+        let mut time = 0;
+
+        for root in graph.node_indices() {
+            if ca.disc[root.index()] == UNVISITED {
+                ca.search(graph, root, &mut time);
+            }
+        }
+
+        ca
+    }
+
+    fn search(&mut self, graph: &IdGraph, root: IdNode, time: &mut u32) {
+        let mut stack: Vec<(IdNode, Edges<'_, (), Undirected>)> = Vec::new();
+        self.visit(root, root, None, time);
+        stack.push((root, graph.edges(root)));
+
+        while let Some((v, iter)) = stack.last_mut() {
+            let v = *v;
+            let vi = v.index();
+            if let Some(e) = iter.next() {
+                let w = e.target();
+                let wi = w.index();
+                if Some(e.id()) == self.parent[vi] {
+                    // don't go back up the tree edge (parallel edges are fine)
+                    continue;
+                }
+                if self.disc[wi] == UNVISITED {
+                    self.visit(w, root, Some(e.id()), time);
+                    stack.push((w, graph.edges(w)));
+                } else {
+                    self.low[vi] = self.low[vi].min(self.disc[wi]);
+                }
+            } else {
+                stack.pop();
+                if let Some((p, _)) = stack.last() {
+                    let pi = p.index();
+                    self.low[pi] = self.low[pi].min(self.low[vi]);
+                    self.size[pi] += self.size[vi];
+                }
+            }
+        }
+    }
+
+    fn visit(&mut self, v: IdNode, root: IdNode, parent: Option<EdgeIndex>, time: &mut u32) {
+        let vi = v.index();
+        self.disc[vi] = *time;
+        self.low[vi] = *time;
+        self.parent[vi] = parent;
+        self.size[vi] = 1;
+        self.root[vi] = root;
+        *time += 1;
+    }
+
+    /// Get the DFS parent of a node, if it is not a root.
+    fn parent_node(&self, graph: &IdGraph, v: IdNode) -> Option<IdNode> {
+        self.parent[v.index()].map(|e| {
+            let (a, b) = graph.edge_endpoints(e).unwrap();
+            if a == v {
+                b
+            } else {
+                a
+            }
+        })
+    }
+
+    /// Get the number of nodes in a node's connected component.
+    pub fn component_size(&self, v: IdNode) -> u32 {
+        self.size[self.root[v.index()].index()]
+    }
+
+    /// Get the bridges of the graph: edges whose removal disconnects their component.
+    pub fn bridges(&self, graph: &IdGraph) -> Vec<EdgeIndex> {
+        self.bridge_splits(graph)
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect()
+    }
+
+    /// Get the bridges of the graph, with the sizes of the two pieces each leaves
+    /// behind when removed (largest first).
+    pub fn bridge_splits(&self, graph: &IdGraph) -> Vec<(EdgeIndex, Vec<u32>)> {
+        let mut bridges = Vec::new();
+        for v in graph.node_indices() {
+            let vi = v.index();
+            if let Some(p) = self.parent_node(graph, v) {
+                if self.low[vi] > self.disc[p.index()] {
+                    let sub = self.size[vi];
+                    let rest = self.component_size(v) - sub;
+                    bridges.push((self.parent[vi].unwrap(), vec![sub.max(rest), sub.min(rest)]));
+                }
+            }
+        }
+        bridges
+    }
+
+    /// Get the articulation points of the graph: nodes whose removal disconnects their
+    /// component.  Each point is returned with the sizes of the pieces its removal
+    /// leaves behind (largest first).
+    pub fn articulation_points(&self, graph: &IdGraph) -> Vec<(IdNode, Vec<u32>)> {
+        // every DFS child that cannot reach above its parent is cut off with it
+        let mut pieces: HashMap<IdNode, Vec<u32>> = HashMap::new();
+        for v in graph.node_indices() {
+            let vi = v.index();
+            if let Some(p) = self.parent_node(graph, v) {
+                if self.low[vi] >= self.disc[p.index()] {
+                    pieces.entry(p).or_default().push(self.size[vi]);
+                }
+            }
+        }
+
+        let mut points = Vec::new();
+        for (v, mut ps) in pieces {
+            if self.parent[v.index()].is_none() {
+                // a root is only a cut if it has more than one child
+                if ps.len() < 2 {
+                    continue;
+                }
+            } else {
+                // the rest of the component, above the node in the tree
+                let below: u32 = ps.iter().sum();
+                ps.push(self.component_size(v) - below - 1);
+            }
+            ps.sort_unstable_by(|a, b| b.cmp(a));
+            points.push((v, ps));
+        }
+        points.sort_unstable_by_key(|(v, _)| *v);
+        points
+    }
+}
+
+/// Measure how evenly a cut splits its component.
+///
+/// This is the fraction of the remaining nodes that are *not* in the largest piece:
+/// 0 when a cut only detaches a sliver, approaching 1 for a cut that shatters a
+/// component into many equal pieces, and 0.5 for a bridge between equal halves.
+pub fn split_balance(pieces: &[u32]) -> f64 {
+    let total: u32 = pieces.iter().sum();
+    let largest = pieces.iter().max().copied().unwrap_or(0);
+    if total == 0 {
+        0.0
+    } else {
+        1.0 - largest as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+use super::test_graph;
+
+#[test]
+fn test_bridges_line() {
+    let (g, _n) = test_graph(3, &[(0, 1), (1, 2)]);
+    let ca = CutAnalysis::compute(&g);
+    assert_eq!(ca.bridges(&g).len(), 2);
+}
+
+#[test]
+fn test_bridges_cycle() {
+    let (g, _n) = test_graph(3, &[(0, 1), (1, 2), (2, 0)]);
+    let ca = CutAnalysis::compute(&g);
+    assert!(ca.bridges(&g).is_empty());
+}
+
+#[test]
+fn test_bridges_parallel() {
+    let (g, _n) = test_graph(2, &[(0, 1), (0, 1)]);
+    let ca = CutAnalysis::compute(&g);
+    assert!(ca.bridges(&g).is_empty());
+}
+
+#[test]
+fn test_bridges_barbell() {
+    // two triangles joined by a single edge
+    let (g, n) = test_graph(6, &[(0, 1), (1, 2), (2, 0), (2, 3), (3, 4), (4, 5), (5, 3)]);
+    let ca = CutAnalysis::compute(&g);
+    let bridges = ca.bridges(&g);
+    assert_eq!(bridges.len(), 1);
+    let (a, b) = g.edge_endpoints(bridges[0]).unwrap();
+    assert!((a, b) == (n[2], n[3]) || (a, b) == (n[3], n[2]));
+}
+
+#[test]
+fn test_bridge_splits_barbell() {
+    let (g, _n) = test_graph(6, &[(0, 1), (1, 2), (2, 0), (2, 3), (3, 4), (4, 5), (5, 3)]);
+    let ca = CutAnalysis::compute(&g);
+    let splits = ca.bridge_splits(&g);
+    assert_eq!(splits.len(), 1);
+    assert_eq!(splits[0].1, vec![3, 3]);
+    assert_eq!(split_balance(&splits[0].1), 0.5);
+}
+
+#[test]
+fn test_articulation_line() {
+    let (g, n) = test_graph(3, &[(0, 1), (1, 2)]);
+    let ca = CutAnalysis::compute(&g);
+    assert_eq!(ca.articulation_points(&g), vec![(n[1], vec![1, 1])]);
+}
+
+#[test]
+fn test_articulation_star_root() {
+    // the DFS starts at the hub, so this checks the root case
+    let (g, n) = test_graph(4, &[(0, 1), (0, 2), (0, 3)]);
+    let ca = CutAnalysis::compute(&g);
+    assert_eq!(ca.articulation_points(&g), vec![(n[0], vec![1, 1, 1])]);
+}
+
+#[test]
+fn test_articulation_barbell() {
+    // two triangles joined through a middle node
+    let (g, n) = test_graph(
+        7,
+        &[
+            (0, 1),
+            (1, 2),
+            (2, 0),
+            (2, 3),
+            (3, 4),
+            (4, 5),
+            (5, 6),
+            (6, 4),
+        ],
+    );
+    let ca = CutAnalysis::compute(&g);
+    let points = ca.articulation_points(&g);
+    assert_eq!(
+        points,
+        vec![(n[2], vec![4, 2]), (n[3], vec![3, 3]), (n[4], vec![4, 2])]
+    );
+    assert_eq!(ca.component_size(n[0]), 7);
+}
+
+#[test]
+fn test_articulation_cycle() {
+    let (g, _n) = test_graph(4, &[(0, 1), (1, 2), (2, 3), (3, 0)]);
+    let ca = CutAnalysis::compute(&g);
+    assert!(ca.articulation_points(&g).is_empty());
+}
+
+#[test]
+fn test_split_balance() {
+    assert_eq!(split_balance(&[3, 1]), 0.25);
+    assert_eq!(split_balance(&[2, 2, 2, 2]), 0.75);
+    assert_eq!(split_balance(&[]), 0.0);
+}
@@ -0,0 +1,347 @@
+//! Evaluate book clusters against a reference grouping.
+//!
+//! Some sources group their own records into works (GoodReads works, OpenLibrary
+//! works).  These groupings are a partial ground truth for our clusters: records
+//! in the same work should be in the same cluster, and a cluster should not mix
+//! many works.  This module computes the standard external clustering measures
+//! from the (cluster, reference class) pairs of the records that have both.
+//!
+//! Both passes work on sorted pair lists rather than hash tables, so evaluating
+//! against the tens of millions of OpenLibrary editions stays within memory.
+//!
+//! Since the work links are themselves graph edges, [recluster_without] rebuilds
+//! the clusters without one source's edges so they can be scored against that
+//! source's works.
+use anyhow::{anyhow, Result};
+use log::*;
+use polars::prelude::*;
+use serde::Serialize;
+
+use super::model::{GRAPH_EDGE_PATH, GRAPH_NODE_PATH};
+use super::stream::{label_clusters, node_frame, CodeIndex};
+use super::unionfind::UnionFind;
+use crate::util::default;
+
+/// Precision, recall, and their harmonic mean.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PRF {
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+/// Scores for the clusters within a range of sizes.
+///
+/// The size of a cluster here is the number of evaluated records it contains.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct SizeBin {
+    pub min_size: u32,
+    pub max_size: Option<u32>,
+    pub clusters: u64,
+    pub items: u64,
+    pub pairwise_precision: f64,
+    pub bcubed_precision: f64,
+    pub bcubed_recall: f64,
+    pub purity: f64,
+}
+
+/// The evaluation of a clustering against a reference.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ClusterEval {
+    pub items: u64,
+    pub clusters: u64,
+    pub classes: u64,
+    pub pairwise: PRF,
+    pub bcubed: PRF,
+    pub purity: f64,
+    pub inverse_purity: f64,
+    pub by_size: Vec<SizeBin>,
+}
+
+/// Lower bounds of the cluster size bins.
+const SIZE_BINS: &[u32] = &[1, 2, 5, 10, 100, 1000];
+
+fn size_bin(size: u32) -> usize {
+    SIZE_BINS.iter().rposition(|b| size >= *b).unwrap_or(0)
+}
+
+fn pairs(n: u64) -> u64 {
+    n * n.saturating_sub(1) / 2
+}
+
+fn ratio(n: f64, d: f64) -> f64 {
+    if d > 0.0 {
+        n / d
+    } else {
+        0.0
+    }
+}
+
+impl PRF {
+    fn new(precision: f64, recall: f64) -> PRF {
+        PRF {
+            precision,
+            recall,
+            f1: ratio(2.0 * precision * recall, precision + recall),
+        }
+    }
+}
+
+/// Accumulators for a size bin.
+#[derive(Default)]
+struct BinAcc {
+    clusters: u64,
+    items: u64,
+    true_pairs: u64,
+    pred_pairs: u64,
+    bc_prec: f64,
+    bc_rec: f64,
+    pure: u64,
+}
+
+/// Call a function with each run of equal keys in a sorted slice.
+fn runs<T, K: PartialEq, F: FnMut(&[T])>(items: &[T], key: impl Fn(&T) -> K, mut f: F) {
+    let mut start = 0;
+    for i in 1..=items.len() {
+        if i == items.len() || key(&items[i]) != key(&items[start]) {
+            f(&items[start..i]);
+            start = i;
+        }
+    }
+}
+
+/// Evaluate a clustering from the `(cluster, class)` pairs of the evaluated records.
+pub fn evaluate(mut items: Vec<(i32, i32)>) -> ClusterEval {
+    let n = items.len() as u64;
+    let mut bins: Vec<BinAcc> = SIZE_BINS.iter().map(|_| BinAcc::default()).collect();
+    let mut eval = ClusterEval {
+        items: n,
+        ..ClusterEval::default()
+    };
+    let mut true_pairs = 0;
+    let mut pred_pairs = 0;
+    let mut bc_prec = 0.0;
+    let mut pure = 0;
+
+    // first pass: by cluster, then class
+    items.sort_unstable();
+    let mut sized = Vec::with_capacity(items.len());
+    runs(
+        &items,
+        |(c, _)| *c,
+        |cluster| {
+            let size = cluster.len() as u64;
+            let bin = &mut bins[size_bin(size as u32)];
+            let mut largest = 0;
+            runs(
+                cluster,
+                |p| *p,
+                |cell| {
+                    let n_cr = cell.len() as u64;
+                    true_pairs += pairs(n_cr);
+                    bin.true_pairs += pairs(n_cr);
+                    let p = (n_cr * n_cr) as f64 / size as f64;
+                    bc_prec += p;
+                    bin.bc_prec += p;
+                    largest = largest.max(n_cr);
+                    for (_, r) in cell {
+                        sized.push((*r, cluster[0].0, size as u32));
+                    }
+                },
+            );
+            eval.clusters += 1;
+            pred_pairs += pairs(size);
+            pure += largest;
+            bin.clusters += 1;
+            bin.items += size;
+            bin.pred_pairs += pairs(size);
+            bin.pure += largest;
+        },
+    );
+    drop(items);
+
+    // second pass: by class, then cluster
+    sized.sort_unstable();
+    let mut ref_pairs = 0;
+    let mut bc_rec = 0.0;
+    let mut inv_pure = 0;
+    runs(
+        &sized,
+        |(r, _, _)| *r,
+        |class| {
+            let size = class.len() as u64;
+            eval.classes += 1;
+            ref_pairs += pairs(size);
+            let mut largest = 0;
+            runs(
+                class,
+                |(r, c, _)| (*r, *c),
+                |cell| {
+                    let n_cr = cell.len() as u64;
+                    let rec = (n_cr * n_cr) as f64 / size as f64;
+                    bc_rec += rec;
+                    bins[size_bin(cell[0].2)].bc_rec += rec;
+                    largest = largest.max(n_cr);
+                },
+            );
+            inv_pure += largest;
+        },
+    );
+
+    let nf = n as f64;
+    eval.pairwise = PRF::new(
+        ratio(true_pairs as f64, pred_pairs as f64),
+        ratio(true_pairs as f64, ref_pairs as f64),
+    );
+    eval.bcubed = PRF::new(ratio(bc_prec, nf), ratio(bc_rec, nf));
+    eval.purity = ratio(pure as f64, nf);
+    eval.inverse_purity = ratio(inv_pure as f64, nf);
+    eval.by_size = bins
+        .into_iter()
+        .enumerate()
+        .filter(|(_, b)| b.clusters > 0)
+        .map(|(i, b)| SizeBin {
+            min_size: SIZE_BINS[i],
+            max_size: SIZE_BINS.get(i + 1).map(|s| s - 1),
+            clusters: b.clusters,
+            items: b.items,
+            pairwise_precision: ratio(b.true_pairs as f64, b.pred_pairs as f64),
+            bcubed_precision: ratio(b.bc_prec, b.items as f64),
+            bcubed_recall: ratio(b.bc_rec, b.items as f64),
+            purity: ratio(b.pure as f64, b.items as f64),
+        })
+        .collect();
+    eval
+}
+
+/// Recluster the saved book graph without the edges from one source.
+///
+/// This returns the graph nodes with their types and the clusters they would have
+/// if `source` contributed no edges, read from the last clustering's node and edge
+/// tables.
+pub fn recluster_without(source: &str) -> Result<DataFrame> {
+    let nodes = LazyFrame::scan_parquet(GRAPH_NODE_PATH, default())?
+        .select([col("book_code")])
+        .collect()?;
+    let edges = LazyFrame::scan_parquet(GRAPH_EDGE_PATH, default())?;
+    recluster_frames(&nodes, edges, source)
+}
+
+fn recluster_frames(nodes: &DataFrame, edges: LazyFrame, source: &str) -> Result<DataFrame> {
+    let codes = nodes.column("book_code")?.i32()?;
+    let index = CodeIndex::from_codes(codes.into_iter().flatten().collect());
+    let edges = edges
+        .filter(col("source").neq(lit(source)))
+        .select([col("src"), col("dst")])
+        .collect()?;
+    info!(
+        "reclustering {} nodes with {} edges",
+        index.len(),
+        edges.height()
+    );
+
+    let mut uf = UnionFind::new(index.len());
+    let srcs = edges.column("src")?.i32()?;
+    let dsts = edges.column("dst")?.i32()?;
+    for (src, dst) in srcs.into_iter().zip(dsts) {
+        if let (Some(src), Some(dst)) = (src, dst) {
+            let si = index
+                .lookup(src)
+                .ok_or_else(|| anyhow!("edge source {} is not a graph node", src))?;
+            let di = index
+                .lookup(dst)
+                .ok_or_else(|| anyhow!("edge target {} is not a graph node", dst))?;
+            uf.union(si, di);
+        }
+    }
+
+    let clusters = label_clusters(&index, uf);
+    node_frame(index, clusters)
+}
+
+#[test]
+fn test_eval_perfect() {
+    let eval = evaluate(vec![(1, 10), (1, 10), (2, 20), (2, 20), (2, 20)]);
+    assert_eq!(eval.items, 5);
+    assert_eq!(eval.clusters, 2);
+    assert_eq!(eval.classes, 2);
+    assert_eq!(eval.pairwise, PRF::new(1.0, 1.0));
+    assert_eq!(eval.bcubed, PRF::new(1.0, 1.0));
+    assert_eq!(eval.purity, 1.0);
+    assert_eq!(eval.inverse_purity, 1.0);
+}
+
+#[test]
+fn test_eval_merged() {
+    // one cluster merges two classes of two records each
+    let eval = evaluate(vec![(1, 10), (1, 10), (1, 20), (1, 20)]);
+    assert_eq!(eval.pairwise.precision, 2.0 / 6.0);
+    assert_eq!(eval.pairwise.recall, 1.0);
+    assert_eq!(eval.bcubed.precision, 0.5);
+    assert_eq!(eval.bcubed.recall, 1.0);
+    assert_eq!(eval.purity, 0.5);
+    assert_eq!(eval.inverse_purity, 1.0);
+    assert_eq!(eval.by_size.len(), 1);
+    assert_eq!(eval.by_size[0].min_size, 2);
+    assert_eq!(eval.by_size[0].max_size, Some(4));
+}
+
+#[test]
+fn test_eval_split() {
+    // one class split across a pair and a singleton
+    let eval = evaluate(vec![(1, 10), (1, 10), (2, 10)]);
+    assert_eq!(eval.pairwise.precision, 1.0);
+    assert_eq!(eval.pairwise.recall, 1.0 / 3.0);
+    assert_eq!(eval.bcubed.precision, 1.0);
+    assert!((eval.bcubed.recall - 5.0 / 9.0).abs() < 1e-12);
+    assert_eq!(eval.purity, 1.0);
+    assert_eq!(eval.inverse_purity, 2.0 / 3.0);
+    assert_eq!(eval.by_size.len(), 2);
+    assert_eq!(eval.by_size[0].items, 1);
+    assert_eq!(eval.by_size[0].bcubed_recall, 1.0 / 3.0);
+}
+
+#[test]
+fn test_eval_empty() {
+    let eval = evaluate(Vec::new());
+    assert_eq!(eval.items, 0);
+    assert_eq!(eval.pairwise, PRF::default());
+    assert!(eval.by_size.is_empty());
+}
+
+#[test]
+fn test_recluster_without() {
+    use crate::ids::codes::NS_GR_BOOK;
+    let (a, b, c) = (
+        NS_GR_BOOK.to_code(1),
+        NS_GR_BOOK.to_code(2),
+        NS_GR_BOOK.to_code(3),
+    );
+    let nodes = df!("book_code" => [a, b, c]).unwrap();
+    let edges = df!(
+        "src" => [a, b],
+        "dst" => [b, c],
+        "source" => ["GRWorks", "GRBooks"]
+    )
+    .unwrap();
+
+    let full = recluster_frames(&nodes, edges.clone().lazy(), "none").unwrap();
+    let full: Vec<_> = full
+        .column("cluster")
+        .unwrap()
+        .i32()
+        .unwrap()
+        .into_no_null_iter()
+        .collect();
+    assert_eq!(full, vec![a, a, a]);
+
+    let held = recluster_frames(&nodes, edges.lazy(), "GRWorks").unwrap();
+    let held: Vec<_> = held
+        .column("cluster")
+        .unwrap()
+        .i32()
+        .unwrap()
+        .into_no_null_iter()
+        .collect();
+    assert_eq!(held, vec![a, b, b]);
+}
@@ -0,0 +1,252 @@
+//! Export graphs in common interchange formats.
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use log::*;
+use petgraph::visit::*;
+use serde::Serialize;
+
+use crate::ids::codes::ns_of_book_code;
+
+use super::gml::save_gml;
+use super::IdGraph;
+
+/// Graph file formats we can export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    GML,
+    GraphML,
+    DOT,
+    /// Node-link JSON, as used by NetworkX and D3.
+    JSON,
+    /// An edge list without node attributes.
+    CSV,
+}
+
+impl GraphFormat {
+    /// Determine the graph format from a file's extension.
+    pub fn for_path<P: AsRef<Path>>(path: P) -> Result<GraphFormat> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        match ext.to_lowercase().as_str() {
+            "gml" => Ok(GraphFormat::GML),
+            "graphml" => Ok(GraphFormat::GraphML),
+            "dot" | "gv" => Ok(GraphFormat::DOT),
+            "json" => Ok(GraphFormat::JSON),
+            "csv" => Ok(GraphFormat::CSV),
+            _ => Err(anyhow!("unknown graph format for {}", path.display())),
+        }
+    }
+}
+
+/// Save a graph, with the format determined by the file extension.
+pub fn export_graph<P: AsRef<Path>>(graph: &IdGraph, path: P) -> Result<()> {
+    let path = path.as_ref();
+    let format = GraphFormat::for_path(path)?;
+    if format == GraphFormat::GML {
+        return save_gml(graph, path);
+    }
+
+    info!("saving graph to {} as {:?}", path.display(), format);
+    let out = File::create(path)?;
+    let mut out = BufWriter::new(out);
+    match format {
+        GraphFormat::GML => unreachable!(),
+        GraphFormat::GraphML => write_graphml(&mut out, graph)?,
+        GraphFormat::DOT => write_dot(&mut out, graph)?,
+        GraphFormat::JSON => write_json(&mut out, graph)?,
+        GraphFormat::CSV => write_csv(&mut out, graph)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn write_graphml<W: Write>(w: &mut W, graph: &IdGraph) -> Result<()> {
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        w,
+        r#"  <key id="namespace" for="node" attr.name="namespace" attr.type="string"/>"#
+    )?;
+    writeln!(
+        w,
+        r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#
+    )?;
+    writeln!(
+        w,
+        r#"  <key id="cluster" for="node" attr.name="cluster" attr.type="int"/>"#
+    )?;
+    writeln!(w, r#"  <graph id="G" edgedefault="undirected">"#)?;
+    for n in graph.node_indices() {
+        let node = graph.node_weight(n).unwrap();
+        let ns = ns_of_book_code(node.code).unwrap();
+        writeln!(w, r#"    <node id="{}">"#, node.code)?;
+        writeln!(w, r#"      <data key="namespace">{}</data>"#, ns.name())?;
+        if let Some(l) = &node.label {
+            writeln!(w, r#"      <data key="label">{}</data>"#, xml_escape(l))?;
+        }
+        writeln!(w, r#"      <data key="cluster">{}</data>"#, node.cluster)?;
+        writeln!(w, "    </node>")?;
+    }
+    for e in graph.edge_references() {
+        let src = graph.node_weight(e.source()).unwrap();
+        let dst = graph.node_weight(e.target()).unwrap();
+        writeln!(
+            w,
+            r#"    <edge source="{}" target="{}"/>"#,
+            src.code, dst.code
+        )?;
+    }
+    writeln!(w, "  </graph>")?;
+    writeln!(w, "</graphml>")?;
+    Ok(())
+}
+
+fn write_dot<W: Write>(w: &mut W, graph: &IdGraph) -> Result<()> {
+    writeln!(w, "graph G {{")?;
+    for n in graph.node_indices() {
+        let node = graph.node_weight(n).unwrap();
+        let ns = ns_of_book_code(node.code).unwrap();
+        write!(w, "  {} [namespace=\"{}\"", node.code, ns.name())?;
+        if let Some(l) = &node.label {
+            write!(w, ", label=\"{}\"", dot_escape(l))?;
+        }
+        writeln!(w, ", cluster={}];", node.cluster)?;
+    }
+    for e in graph.edge_references() {
+        let src = graph.node_weight(e.source()).unwrap();
+        let dst = graph.node_weight(e.target()).unwrap();
+        writeln!(w, "  {} -- {};", src.code, dst.code)?;
+    }
+    writeln!(w, "}}")?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct JsonGraph<'a> {
+    directed: bool,
+    multigraph: bool,
+    nodes: Vec<JsonNode<'a>>,
+    links: Vec<JsonLink>,
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    id: i32,
+    namespace: &'a str,
+    label: Option<&'a str>,
+    cluster: i32,
+}
+
+#[derive(Serialize)]
+struct JsonLink {
+    source: i32,
+    target: i32,
+}
+
+fn write_json<W: Write>(w: &mut W, graph: &IdGraph) -> Result<()> {
+    let nodes = graph
+        .node_weights()
+        .map(|node| JsonNode {
+            id: node.code,
+            namespace: ns_of_book_code(node.code).unwrap().name(),
+            label: node.label.as_deref(),
+            cluster: node.cluster,
+        })
+        .collect();
+    let links = graph
+        .edge_references()
+        .map(|e| JsonLink {
+            source: graph.node_weight(e.source()).unwrap().code,
+            target: graph.node_weight(e.target()).unwrap().code,
+        })
+        .collect();
+    let jg = JsonGraph {
+        directed: false,
+        multigraph: false,
+        nodes,
+        links,
+    };
+    serde_json::to_writer(w, &jg)?;
+    Ok(())
+}
+
+fn write_csv<W: Write>(w: &mut W, graph: &IdGraph) -> Result<()> {
+    let mut csvw = csv::Writer::from_writer(w);
+    csvw.write_record(["source", "target"])?;
+    for e in graph.edge_references() {
+        let src = graph.node_weight(e.source()).unwrap();
+        let dst = graph.node_weight(e.target()).unwrap();
+        csvw.serialize((src.code, dst.code))?;
+    }
+    csvw.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+use super::test_graph;
+
+#[test]
+fn test_format_for_path() {
+    assert_eq!(GraphFormat::for_path("foo.gml").unwrap(), GraphFormat::GML);
+    assert_eq!(
+        GraphFormat::for_path("foo.GraphML").unwrap(),
+        GraphFormat::GraphML
+    );
+    assert_eq!(GraphFormat::for_path("foo.gv").unwrap(), GraphFormat::DOT);
+    assert!(GraphFormat::for_path("foo.txt").is_err());
+    assert!(GraphFormat::for_path("foo").is_err());
+}
+
+#[test]
+fn test_xml_escape() {
+    assert_eq!(xml_escape("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+}
+
+#[test]
+fn test_dot_escape() {
+    assert_eq!(dot_escape(r#"say "hi" \ bye"#), r#"say \"hi\" \\ bye"#);
+}
+
+#[test]
+fn test_write_json() {
+    let (mut g, n) = test_graph(2, &[(0, 1)]);
+    g[n[0]].code = 900000001;
+    g[n[0]].label = Some("0123456789".into());
+    g[n[1]].code = 200000005;
+    let mut buf = Vec::new();
+    write_json(&mut buf, &g).expect("write failed");
+    let val: serde_json::Value = serde_json::from_slice(&buf).expect("invalid JSON");
+    assert_eq!(val["nodes"][0]["namespace"], "ISBN");
+    assert_eq!(val["nodes"][0]["label"], "0123456789");
+    assert_eq!(val["links"][0]["source"], 900000001);
+    assert_eq!(val["links"][0]["target"], 200000005);
+}
@@ -21,6 +21,20 @@ fn gml_end<W: Write>(w: &mut W) -> Result<()> {
     Ok(())
 }
 
+/// Escape a string for GML, which only allows 7-bit ASCII with no quotes.
+fn gml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("&quot;"),
+            '&' => out.push_str("&amp;"),
+            c if c.is_ascii() => out.push(c),
+            c => out.push_str(&format!("&#{};", c as u32)),
+        }
+    }
+    out
+}
+
 fn gml_node<W: Write>(w: &mut W, graph: &IdGraph, v: IdNode) -> Result<()> {
     let node = graph.node_weight(v).unwrap();
     writeln!(w, "  node [")?;
@@ -28,8 +42,9 @@ fn gml_node<W: Write>(w: &mut W, graph: &IdGraph, v: IdNode) -> Result<()> {
     let ns = ns_of_book_code(node.code).unwrap();
     writeln!(w, "    namespace \"{}\"", ns.name())?;
     if let Some(ref l) = node.label {
-        writeln!(w, "    label \"{}\"", l)?;
+        writeln!(w, "    label \"{}\"", gml_escape(l))?;
     }
+    writeln!(w, "    cluster {}", node.cluster)?;
     writeln!(w, "  ]")?;
     Ok(())
 }
@@ -59,3 +74,13 @@ pub fn save_gml<P: AsRef<Path>>(graph: &IdGraph, path: P) -> Result<()> {
     gml_end(&mut out)?;
     Ok(())
 }
+
+#[test]
+fn test_gml_escape() {
+    assert_eq!(gml_escape("plain"), "plain");
+    assert_eq!(
+        gml_escape("say \"hi\" & bye"),
+        "say &quot;hi&quot; &amp; bye"
+    );
+    assert_eq!(gml_escape("Père"), "P&#232;re");
+}
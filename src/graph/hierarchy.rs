@@ -0,0 +1,224 @@
+//! Work-level structure within book clusters.
+//!
+//! Clusters flatten works, editions, and ISBNs into one group.  This module
+//! recovers the work → edition → ISBN hierarchy inside each cluster from the
+//! graph: each OpenLibrary or GoodReads work, the editions (or books) linked to
+//! it, the ISBNs of those editions, and the other records (LOC records, ASINs,
+//! other sources' editions) linked to those ISBNs.  A record can belong to more
+//! than one work.
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use log::*;
+use parquet_derive::ParquetRecordWriter;
+use polars::prelude::*;
+
+use super::model::CLUSTER_HIERARCHY_PATH;
+use super::IdGraph;
+use crate::arrow::TableWriter;
+use crate::ids::codes::{
+    ns_of_book_code, NS, NS_EDITION, NS_GR_BOOK, NS_GR_WORK, NS_ISBN, NS_WORK,
+};
+use crate::io::object::ObjectWriter;
+use crate::util::default;
+
+/// A record's membership in a work within a cluster.
+#[derive(ParquetRecordWriter, Debug, PartialEq, Eq)]
+pub struct HierarchyRec {
+    pub cluster: i32,
+    pub work: i32,
+    pub book_code: i32,
+    /// The record's level under the work: `work`, `edition`, `isbn`, or `record`.
+    pub level: String,
+}
+
+/// Get the edition namespace for a work namespace.
+fn edition_ns(work: &NS<'_>) -> Option<&'static NS<'static>> {
+    if *work == NS_WORK {
+        Some(&NS_EDITION)
+    } else if *work == NS_GR_WORK {
+        Some(&NS_GR_BOOK)
+    } else {
+        None
+    }
+}
+
+fn is_work(ns: &NS<'_>) -> bool {
+    edition_ns(ns).is_some()
+}
+
+/// Walk the work hierarchy of a clustered graph.
+///
+/// The graph's nodes must have their clusters assigned.
+pub fn walk_hierarchy<F>(graph: &IdGraph, mut emit: F) -> Result<()>
+where
+    F: FnMut(HierarchyRec) -> Result<()>,
+{
+    for wi in graph.node_indices() {
+        let work = &graph[wi];
+        let ens = match ns_of_book_code(work.code).and_then(edition_ns) {
+            Some(ns) => ns,
+            None => continue,
+        };
+        let rec = |code: i32, level: &str| HierarchyRec {
+            cluster: work.cluster,
+            work: work.code,
+            book_code: code,
+            level: level.to_string(),
+        };
+        emit(rec(work.code, "work"))?;
+
+        let mut seen = HashSet::new();
+        for ei in graph.neighbors(wi) {
+            let ed = &graph[ei];
+            if ns_of_book_code(ed.code) != Some(ens) || !seen.insert(ed.code) {
+                continue;
+            }
+            emit(rec(ed.code, "edition"))?;
+            for ii in graph.neighbors(ei) {
+                let isbn = &graph[ii];
+                if ns_of_book_code(isbn.code) != Some(&NS_ISBN) || !seen.insert(isbn.code) {
+                    continue;
+                }
+                emit(rec(isbn.code, "isbn"))?;
+                for ri in graph.neighbors(ii) {
+                    let other = &graph[ri];
+                    let ons = ns_of_book_code(other.code);
+                    let skip = match ons {
+                        Some(ns) => is_work(ns) || ns == ens || ns == &NS_ISBN,
+                        None => true,
+                    };
+                    if !skip && seen.insert(other.code) {
+                        emit(rec(other.code, "record"))?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Save the work hierarchy of a clustered graph.
+pub fn save_cluster_hierarchy(graph: &IdGraph) -> Result<()> {
+    info!("writing cluster hierarchy to {}", CLUSTER_HIERARCHY_PATH);
+    let mut writer = TableWriter::open(CLUSTER_HIERARCHY_PATH)?;
+    let mut works = 0;
+    walk_hierarchy(graph, |rec| {
+        if rec.level == "work" {
+            works += 1;
+        }
+        writer.write_object(rec)
+    })?;
+    let n = writer.finish()?;
+    info!("wrote {} hierarchy records for {} works", n, works);
+    Ok(())
+}
+
+/// Remove the saved work hierarchy, if any.
+///
+/// The hierarchy is built from the full graph, so re-clustering without saving the
+/// graph would leave a hierarchy that no longer matches the clusters.
+pub(super) fn remove_cluster_hierarchy() -> Result<()> {
+    if Path::new(CLUSTER_HIERARCHY_PATH).exists() {
+        info!(
+            "removing stale cluster hierarchy {}",
+            CLUSTER_HIERARCHY_PATH
+        );
+        fs::remove_file(CLUSTER_HIERARCHY_PATH)?;
+    }
+    Ok(())
+}
+
+/// Scan the work each record belongs to, for aggregating by work.
+///
+/// The resulting frame has columns `book_code` and `work`.  Records in more than
+/// one work are assigned to the lowest work code, so OpenLibrary works take
+/// precedence over GoodReads works.  `root` is the prefix for paths relative to
+/// the data root.  It is an error if the hierarchy has not been saved since the
+/// last clustering.
+pub fn scan_record_works(root: &str) -> Result<LazyFrame> {
+    let path = format!("{}{}", root, CLUSTER_HIERARCHY_PATH);
+    if !Path::new(&path).exists() {
+        return Err(anyhow!(
+            "{} not found, run `cluster save-graph --hierarchy` after clustering",
+            path
+        ));
+    }
+    let hier = LazyFrame::scan_parquet(path, default())?;
+    Ok(hier.group_by([col("book_code")]).agg([col("work").min()]))
+}
+
+#[cfg(test)]
+fn hier_graph(codes: &[i32], edges: &[(usize, usize)]) -> IdGraph {
+    use super::BookID;
+    let mut g = IdGraph::new_undirected();
+    let nodes: Vec<_> = codes
+        .iter()
+        .map(|c| {
+            g.add_node(BookID {
+                code: *c,
+                label: None,
+                cluster: codes[0],
+            })
+        })
+        .collect();
+    for (s, d) in edges {
+        g.add_edge(nodes[*s], nodes[*d], ());
+    }
+    g
+}
+
+#[test]
+fn test_hierarchy_levels() {
+    use crate::ids::codes::NS_LOC_REC;
+    let work = NS_WORK.to_code(1);
+    let ed = NS_EDITION.to_code(10);
+    let isbn = NS_ISBN.to_code(100);
+    let loc = NS_LOC_REC.to_code(5);
+    let g = hier_graph(&[work, ed, isbn, loc], &[(0, 1), (1, 2), (2, 3)]);
+    let mut recs = Vec::new();
+    walk_hierarchy(&g, |r| {
+        recs.push((r.work, r.book_code, r.level));
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(
+        recs,
+        vec![
+            (work, work, "work".to_string()),
+            (work, ed, "edition".to_string()),
+            (work, isbn, "isbn".to_string()),
+            (work, loc, "record".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_hierarchy_two_works() {
+    // two GoodReads works sharing an ISBN; each keeps only its own book
+    let w1 = NS_GR_WORK.to_code(1);
+    let w2 = NS_GR_WORK.to_code(2);
+    let b1 = NS_GR_BOOK.to_code(1);
+    let b2 = NS_GR_BOOK.to_code(2);
+    let isbn = NS_ISBN.to_code(7);
+    let g = hier_graph(&[w1, w2, b1, b2, isbn], &[(0, 2), (1, 3), (2, 4), (3, 4)]);
+    let mut recs = Vec::new();
+    walk_hierarchy(&g, |r| {
+        recs.push((r.work, r.book_code));
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(
+        recs,
+        vec![
+            (w1, w1),
+            (w1, b1),
+            (w1, isbn),
+            (w2, w2),
+            (w2, b2),
+            (w2, isbn)
+        ]
+    );
+}
@@ -0,0 +1,535 @@
+//! Recluster books after some graph sources change.
+//!
+//! Rebuilding the clusters from scratch reads every edge source.  When only a few
+//! sources have changed, we can instead start from the previous run's nodes and
+//! edges, replace the edges from the changed sources, and recompute only the
+//! clusters that lost a node or an edge; every other old cluster is carried over
+//! as a unit, and new edges can only merge clusters.
+//!
+//! This relies on the previous outputs in [GRAPH_NODE_PATH] and [GRAPH_EDGE_PATH],
+//! whose edges record the sources they came from, and assumes the curation file is
+//! unchanged since they were written.  ISBN IDs are renumbered whenever the ISBN
+//! list changes, so old ISBN nodes are matched to new ones by their labels; the IDs
+//! of every other source are assumed stable unless the source is listed as changed.
+//! Sources computed from a changed source's records are re-read along with it.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use log::*;
+use parquet_derive::ParquetRecordWriter;
+use polars::prelude::*;
+
+use super::curation::*;
+use super::model::*;
+use super::sources::*;
+use super::stream::*;
+use super::unionfind::UnionFind;
+use crate::arrow::TableWriter;
+use crate::ids::codes::*;
+use crate::io::object::ObjectWriter;
+use crate::layout::Config;
+use crate::util::default;
+
+/// The nodes whose clusters changed in an incremental run.
+pub const CLUSTER_CHANGE_PATH: &str = "book-links/cluster-changes.parquet";
+
+/// A change to a node's cluster.
+///
+/// New nodes have no old cluster, and removed nodes have no new cluster.
+#[derive(ParquetRecordWriter, Debug)]
+pub struct ClusterChange {
+    pub book_code: i32,
+    pub old_cluster: Option<i32>,
+    pub new_cluster: Option<i32>,
+}
+
+/// Map old ISBN codes to the current ISBN codes, through their labels.
+struct ISBNRemap {
+    pairs: Vec<(i32, i32)>,
+}
+
+impl ISBNRemap {
+    fn load(old_nodes: &DataFrame) -> Result<ISBNRemap> {
+        let old = old_nodes
+            .clone()
+            .lazy()
+            .filter(col("node_type").eq(lit(NS_ISBN.name())))
+            .select([col("book_code"), col("label")]);
+        let isbns = LazyFrame::scan_parquet("book-links/all-isbns.parquet", default())?;
+        let isbns = isbns.select([
+            col("isbn"),
+            (col("isbn_id").cast(DataType::Int32) + lit(NS_ISBN.base())).alias("new_code"),
+        ]);
+        let df = old
+            .join(isbns, [col("label")], [col("isbn")], JoinType::Inner.into())
+            .select([col("book_code"), col("new_code")])
+            .collect()?;
+        let olds = df.column("book_code")?.i32()?;
+        let news = df.column("new_code")?.i32()?;
+        let mut pairs: Vec<(i32, i32)> = olds
+            .into_iter()
+            .zip(news)
+            .filter_map(|(o, n)| Some((o?, n?)))
+            .collect();
+        pairs.sort_unstable();
+        Ok(ISBNRemap { pairs })
+    }
+
+    /// Get the current code for an old node code.
+    fn remap(&self, code: i32) -> Option<i32> {
+        if ns_of_book_code(code).map(|ns| ns.code()) == Some(NS_ISBN.code()) {
+            self.pairs
+                .binary_search_by_key(&code, |(o, _)| *o)
+                .ok()
+                .map(|i| self.pairs[i].1)
+        } else {
+            Some(code)
+        }
+    }
+}
+
+/// Read the `src` and `dst` columns of an edge frame as code pairs.
+fn edge_pairs(df: LazyFrame) -> Result<Vec<(i32, i32)>> {
+    let df = df
+        .select([
+            col("src").cast(DataType::Int32),
+            col("dst").cast(DataType::Int32),
+        ])
+        .collect()?;
+    let srcs = df.column("src")?.i32()?;
+    let dsts = df.column("dst")?.i32()?;
+    Ok(srcs
+        .into_iter()
+        .zip(dsts)
+        .filter_map(|(s, d)| Some((s?, d?)))
+        .collect())
+}
+
+fn edge_key((a, b): (u32, u32)) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+/// Edge source names, numbered so edges can refer to them compactly.
+#[derive(Default)]
+struct SourceNames {
+    names: Vec<String>,
+    ids: HashMap<String, u16>,
+}
+
+impl SourceNames {
+    fn id(&mut self, name: &str) -> u16 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len() as u16;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    fn name(&self, id: u16) -> &str {
+        &self.names[id as usize]
+    }
+}
+
+/// An edge between two indexed nodes, with the number of its source.
+type SourcedEdge = (u32, u32, u16);
+
+/// The previous graph, matched to the current nodes.
+struct OldGraph {
+    /// The previous cluster of each current node, or `i32::MIN` for new nodes.
+    clusters: Vec<i32>,
+    /// The previous nodes that are no longer present, with their clusters.
+    removed: Vec<(i32, i32)>,
+    /// The previous edges, with `None` for endpoints that are no longer present.
+    edges: Vec<(Option<u32>, Option<u32>, u16)>,
+}
+
+impl OldGraph {
+    /// Match the previous nodes and edges to the current nodes.
+    ///
+    /// `remap` gives the current code for a previous node's code.
+    fn matched<F>(
+        index: &CodeIndex,
+        nodes: impl IntoIterator<Item = (i32, i32)>,
+        edges: impl IntoIterator<Item = (i32, i32, u16)>,
+        remap: F,
+    ) -> OldGraph
+    where
+        F: Fn(i32) -> Option<i32>,
+    {
+        let lookup = |code| remap(code).and_then(|c| index.lookup(c));
+        let mut clusters = vec![i32::MIN; index.len()];
+        let mut removed = Vec::new();
+        for (code, cluster) in nodes {
+            match lookup(code) {
+                Some(i) => clusters[i as usize] = cluster,
+                None => removed.push((code, cluster)),
+            }
+        }
+        let edges = edges
+            .into_iter()
+            .map(|(s, d, src)| (lookup(s), lookup(d), src))
+            .collect();
+        OldGraph {
+            clusters,
+            removed,
+            edges,
+        }
+    }
+}
+
+/// The updated graph.
+struct Update {
+    uf: UnionFind,
+    edges: Vec<SourcedEdge>,
+}
+
+/// Update the previous graph with the edges from the changed sources.
+///
+/// `new_edges` are the current edges of the `changed` sources, and have already
+/// been filtered by curation; `keep` checks the previous edges of other sources
+/// against curation.  Edges from `curation` are kept if they are still `forced`,
+/// and forced edges that are missing are added.
+fn update_graph<K>(
+    old: OldGraph,
+    mut new_edges: Vec<SourcedEdge>,
+    changed: &HashSet<u16>,
+    keep: K,
+    forced: &[(u32, u32)],
+    curation: u16,
+) -> Update
+where
+    K: Fn(u32, u32) -> bool,
+{
+    let n = old.clusters.len();
+    let old_cluster = &old.clusters;
+    let mut touched: HashSet<i32> = old.removed.iter().map(|(_, c)| *c).collect();
+    let forced_keys: HashSet<(u32, u32)> = forced.iter().map(|e| edge_key(*e)).collect();
+
+    new_edges.sort_unstable_by_key(|(s, d, _)| edge_key((*s, *d)));
+    new_edges.dedup_by_key(|(s, d, _)| edge_key((*s, *d)));
+
+    // sort the old edges into retained and removed
+    let mut retained = Vec::new();
+    let mut n_removed = 0;
+    for (si, di, src) in old.edges {
+        let (si, di) = match (si, di) {
+            (Some(si), Some(di)) => (si, di),
+            // the missing endpoint's removal already touched the cluster
+            _ => continue,
+        };
+        let kept = if changed.contains(&src) {
+            // the new edges replace it, if it is still there
+            if new_edges
+                .binary_search_by_key(&edge_key((si, di)), |(s, d, _)| edge_key((*s, *d)))
+                .is_ok()
+            {
+                continue;
+            }
+            false
+        } else if src == curation {
+            forced_keys.contains(&edge_key((si, di)))
+        } else {
+            keep(si, di)
+        };
+        if kept {
+            retained.push((si, di, src));
+        } else {
+            touched.insert(old_cluster[si as usize]);
+            n_removed += 1;
+        }
+    }
+    info!(
+        "retained {} edges, removed {} edges touching {} clusters",
+        retained.len(),
+        n_removed,
+        touched.len()
+    );
+
+    // untouched clusters are unions of their nodes; touched ones rebuild from edges
+    let mut uf = UnionFind::new(n);
+    let mut reps: HashMap<i32, u32> = HashMap::new();
+    for (i, c) in old_cluster.iter().enumerate() {
+        if *c != i32::MIN && !touched.contains(c) {
+            let r = *reps.entry(*c).or_insert(i as u32);
+            uf.union(r, i as u32);
+        }
+    }
+
+    let mut present = HashSet::new();
+    let mut edges = retained;
+    edges.extend(new_edges);
+    for (si, di, _) in &edges {
+        // edges within untouched clusters are redundant, but harmless
+        uf.union(*si, *di);
+        if forced_keys.contains(&edge_key((*si, *di))) {
+            present.insert(edge_key((*si, *di)));
+        }
+    }
+    let mut n_forced = 0;
+    for (a, b) in forced {
+        if present.insert(edge_key((*a, *b))) {
+            uf.union(*a, *b);
+            edges.push((*a, *b, curation));
+            n_forced += 1;
+        }
+    }
+    if n_forced > 0 {
+        info!("curation added {} edges", n_forced);
+    }
+
+    Update { uf, edges }
+}
+
+/// Update the clusters from a previous run after the named sources change.
+///
+/// This writes the same tables as [stream_clusters], along with the list of
+/// nodes whose clusters changed in [CLUSTER_CHANGE_PATH].
+pub fn incremental_clusters(cfg: &Config, curation: &Curation, changed: &[String]) -> Result<()> {
+    let mut names = HashSet::new();
+    for name in changed {
+        names.insert(resolve_source_name(cfg, name)?);
+    }
+    add_dependent_sources(&mut names);
+    info!("updating clusters for changed sources {:?}", names);
+
+    // we overwrite the old graph, so read it fully into memory first
+    info!("reading previous graph nodes");
+    let old_nodes = LazyFrame::scan_parquet(GRAPH_NODE_PATH, default())?
+        .select([
+            col("book_code"),
+            col("cluster"),
+            col("node_type"),
+            col("label"),
+        ])
+        .collect()?;
+    info!("reading previous graph edges");
+    let old_edges = LazyFrame::scan_parquet(GRAPH_EDGE_PATH, default())?;
+    if old_edges.schema()?.get("source").is_none() {
+        return Err(anyhow!(
+            "{} does not record edge sources, recluster without --incremental",
+            GRAPH_EDGE_PATH
+        ));
+    }
+    let old_edges = old_edges
+        .select([
+            col("src").cast(DataType::Int32),
+            col("dst").cast(DataType::Int32),
+            col("source"),
+        ])
+        .collect()?;
+    info!(
+        "previous graph has {} nodes and {} edges",
+        old_nodes.height(),
+        old_edges.height()
+    );
+    let remap = ISBNRemap::load(&old_nodes)?;
+
+    let index = CodeIndex::load(cfg)?;
+
+    let mut sources = SourceNames::default();
+    let old = {
+        let codes = old_nodes.column("book_code")?.i32()?;
+        let clusters = old_nodes.column("cluster")?.i32()?;
+        let nodes = codes
+            .into_iter()
+            .zip(clusters)
+            .filter_map(|(c, k)| Some((c?, k?)));
+        let srcs = old_edges.column("src")?.i32()?;
+        let dsts = old_edges.column("dst")?.i32()?;
+        let names = old_edges.column("source")?.str()?;
+        let mut edges = Vec::with_capacity(old_edges.height());
+        for ((s, d), name) in srcs.into_iter().zip(dsts).zip(names) {
+            if let (Some(s), Some(d), Some(name)) = (s, d, name) {
+                edges.push((s, d, sources.id(name)));
+            }
+        }
+        OldGraph::matched(&index, nodes, edges, |c| remap.remap(c))
+    };
+    drop(old_nodes);
+    drop(old_edges);
+    info!("{} previous nodes are no longer present", old.removed.len());
+
+    let resolved = resolve_curation(curation, &index)?;
+    let filter = EdgeFilter::new(&resolved);
+    let forced: Vec<(u32, u32)> = forced_edges(&resolved).collect();
+
+    // read the edges from the changed sources
+    let mut new_edges = Vec::new();
+    for (name, src) in named_edge_sources(cfg)? {
+        if !names.contains(&name) {
+            continue;
+        }
+        info!("scanning edges from {:?}", src);
+        let sid = sources.id(&name);
+        let mut n_added = 0;
+        for (sn, dn) in edge_pairs(src.read_edges()?)? {
+            let si = index
+                .lookup(sn)
+                .ok_or_else(|| anyhow!("unknown source node {}", sn))?;
+            let di = index
+                .lookup(dn)
+                .ok_or_else(|| anyhow!("unknown destination node {}", dn))?;
+            if filter.keep(si, di) {
+                new_edges.push((si, di, sid));
+                n_added += 1;
+            }
+        }
+        info!("read {} edges from {:?}", n_added, src);
+    }
+
+    let changed: HashSet<u16> = names.iter().map(|n| sources.id(n)).collect();
+    let curation_id = sources.id(CURATION_SOURCE);
+    let old_cluster = old.clusters.clone();
+    let removed_nodes = old.removed.clone();
+    let update = update_graph(
+        old,
+        new_edges,
+        &changed,
+        |a, b| filter.keep(a, b),
+        &forced,
+        curation_id,
+    );
+
+    let mut e_w = TableWriter::open(GRAPH_EDGE_PATH)?;
+    for (si, di, src) in &update.edges {
+        e_w.write_object(GraphEdge {
+            src: index.code(*si),
+            dst: index.code(*di),
+            source: sources.name(*src).to_string(),
+        })?;
+    }
+    e_w.finish()?;
+
+    let clusters = label_clusters(&index, update.uf);
+
+    info!("writing cluster changes");
+    let mut c_w = TableWriter::open(CLUSTER_CHANGE_PATH)?;
+    let mut n_changed = 0;
+    for (i, new) in clusters.iter().enumerate() {
+        let old = Some(old_cluster[i]).filter(|c| *c != i32::MIN);
+        if old != Some(*new) {
+            c_w.write_object(ClusterChange {
+                book_code: index.code(i as u32),
+                old_cluster: old,
+                new_cluster: Some(*new),
+            })?;
+            n_changed += 1;
+        }
+    }
+    for (code, cluster) in removed_nodes {
+        c_w.write_object(ClusterChange {
+            book_code: code,
+            old_cluster: Some(cluster),
+            new_cluster: None,
+        })?;
+        n_changed += 1;
+    }
+    c_w.finish()?;
+    info!("{} nodes changed clusters", n_changed);
+
+    write_clusters(cfg, index, clusters)
+}
+
+/// Label the clusters of a graph from scratch.
+#[cfg(test)]
+fn full_clusters(index: &CodeIndex, edges: &[(i32, i32)]) -> Vec<i32> {
+    let mut uf = UnionFind::new(index.len());
+    for (s, d) in edges {
+        uf.union(index.lookup(*s).unwrap(), index.lookup(*d).unwrap());
+    }
+    label_clusters(index, uf)
+}
+
+/// Update a graph whose edges come from sources 0 and 1 when source 1 changes,
+/// and check that the result matches clustering the new graph from scratch.
+#[cfg(test)]
+fn check_update(
+    old_codes: &[i32],
+    old_edges: &[(i32, i32, u16)],
+    codes: &[i32],
+    new_edges: &[(i32, i32)],
+) -> Vec<i32> {
+    let old_index = CodeIndex::from_codes(old_codes.to_vec());
+    let pairs: Vec<_> = old_edges.iter().map(|(s, d, _)| (*s, *d)).collect();
+    let old_clusters = full_clusters(&old_index, &pairs);
+    let nodes = (0..old_index.len()).map(|i| (old_index.code(i as u32), old_clusters[i]));
+
+    let index = CodeIndex::from_codes(codes.to_vec());
+    let old = OldGraph::matched(&index, nodes, old_edges.iter().copied(), Some);
+    let lookup = |c| index.lookup(c).unwrap();
+    let added: Vec<_> = new_edges
+        .iter()
+        .map(|(s, d)| (lookup(*s), lookup(*d), 1))
+        .collect();
+    let changed = [1].into_iter().collect();
+    let update = update_graph(old, added, &changed, |_, _| true, &[], 2);
+    let clusters = label_clusters(&index, update.uf);
+
+    let mut all: Vec<_> = old_edges
+        .iter()
+        .filter(|(s, d, src)| *src == 0 && index.lookup(*s).is_some() && index.lookup(*d).is_some())
+        .map(|(s, d, _)| (*s, *d))
+        .collect();
+    all.extend_from_slice(new_edges);
+    assert_eq!(clusters, full_clusters(&index, &all));
+    assert_eq!(update.edges.len(), all.len());
+    clusters
+}
+
+#[test]
+fn test_update_merges() {
+    let codes = [1, 2, 3, 4, 5];
+    let clusters = check_update(&codes, &[(1, 2, 0), (3, 4, 1)], &codes, &[(3, 4), (2, 3)]);
+    assert_eq!(clusters, vec![1, 1, 1, 1, 5]);
+}
+
+#[test]
+fn test_update_splits() {
+    let codes = [1, 2, 3, 4];
+    let clusters = check_update(
+        &codes,
+        &[(1, 2, 0), (2, 3, 1), (3, 4, 1)],
+        &codes,
+        &[(3, 4)],
+    );
+    assert_eq!(clusters, vec![1, 1, 3, 3]);
+}
+
+#[test]
+fn test_update_unchanged_source_kept() {
+    // source 0 links 2 and 3, so losing the source 1 edge does not split them
+    let codes = [1, 2, 3];
+    let clusters = check_update(
+        &codes,
+        &[(1, 2, 1), (2, 3, 1), (2, 3, 0)],
+        &codes,
+        &[(2, 3)],
+    );
+    assert_eq!(clusters, vec![1, 2, 2]);
+}
+
+#[test]
+fn test_update_node_removed() {
+    // record 2 bridged 1 and 3, and is gone along with its source 1 links
+    let clusters = check_update(
+        &[1, 2, 3, 4, 5],
+        &[(1, 2, 1), (2, 3, 1), (4, 5, 0)],
+        &[1, 3, 4, 5],
+        &[],
+    );
+    assert_eq!(clusters, vec![1, 3, 4, 4]);
+}
+
+#[test]
+fn test_update_forced() {
+    // a forced edge is kept, and one that is no longer forced is removed
+    let codes = [1, 2, 3, 4];
+    let index = CodeIndex::from_codes(codes.to_vec());
+    let nodes = [(1, 1), (2, 1), (3, 3), (4, 3)];
+    let old = OldGraph::matched(&index, nodes, [(1, 2, 2), (3, 4, 2)], Some);
+    let update = update_graph(old, vec![], &HashSet::new(), |_, _| true, &[(0, 1)], 2);
+    assert_eq!(update.edges, vec![(0, 1, 2)]);
+    assert_eq!(label_clusters(&index, update.uf), vec![1, 1, 3, 4]);
+}
@@ -0,0 +1,117 @@
+//! Link data set items to book clusters through the graph.
+//!
+//! Data sets whose items are nodes in the book graph (such as Amazon ASINs) look
+//! up their clusters in the graph node table, instead of matching ISBN strings.
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::Result;
+use log::*;
+use polars::prelude::*;
+use serde::Serialize;
+
+use super::model::{CLUSTER_STATS_PATH, GRAPH_NODE_PATH};
+use crate::ids::codes::NS;
+use crate::util::default;
+
+/// Statistics on how well a data set's items link to other book records.
+///
+/// An item is *linked* if its cluster contains a record from LOC, OpenLibrary,
+/// or GoodReads.
+#[derive(Serialize, Debug, Default)]
+pub struct LinkageStats {
+    pub items: u32,
+    pub linked_items: u32,
+    pub interactions: u32,
+    pub linked_interactions: u32,
+}
+
+/// Scan the clusters of the nodes in a namespace.
+///
+/// The resulting frame has columns `id` (the identifier within the namespace),
+/// `cluster`, and `linked`.  `root` is the prefix for paths relative to the data
+/// root (e.g. `../` when running from a data set's directory).
+pub fn scan_ns_clusters(ns: &NS<'_>, root: &str) -> Result<LazyFrame> {
+    let nodes = LazyFrame::scan_parquet(format!("{}{}", root, GRAPH_NODE_PATH), default())?;
+    let nodes = nodes.filter(col("node_type").eq(lit(ns.name()))).select([
+        (col("book_code") - lit(ns.base())).alias("id"),
+        col("cluster"),
+    ]);
+    let stats = LazyFrame::scan_parquet(format!("{}{}", root, CLUSTER_STATS_PATH), default())?;
+    let stats = stats.select([
+        col("cluster"),
+        (col("n_loc_recs")
+            + col("n_ol_editions")
+            + col("n_ol_works")
+            + col("n_gr_books")
+            + col("n_gr_works"))
+        .gt(lit(0))
+        .alias("linked"),
+    ]);
+    Ok(nodes.join(
+        stats,
+        [col("cluster")],
+        [col("cluster")],
+        JoinType::Inner.into(),
+    ))
+}
+
+impl LinkageStats {
+    /// Compute linkage statistics from interactions with `item` and `linked` columns.
+    pub fn compute(actions: LazyFrame) -> Result<LinkageStats> {
+        let df = actions
+            .select([
+                col("item").n_unique().cast(DataType::UInt32).alias("items"),
+                col("item")
+                    .filter(col("linked"))
+                    .n_unique()
+                    .cast(DataType::UInt32)
+                    .alias("linked_items"),
+                col("item")
+                    .count()
+                    .cast(DataType::UInt32)
+                    .alias("interactions"),
+                col("linked")
+                    .sum()
+                    .cast(DataType::UInt32)
+                    .alias("linked_interactions"),
+            ])
+            .collect()?;
+        let get = |c: &str| -> Result<u32> { Ok(df.column(c)?.u32()?.get(0).unwrap_or(0)) };
+        Ok(LinkageStats {
+            items: get("items")?,
+            linked_items: get("linked_items")?,
+            interactions: get("interactions")?,
+            linked_interactions: get("linked_interactions")?,
+        })
+    }
+
+    /// Log the linkage rates.
+    pub fn log(&self, name: &str) {
+        info!(
+            "{}: linked {} of {} items ({:.2}%), {} of {} interactions ({:.2}%)",
+            name,
+            self.linked_items,
+            self.items,
+            pct(self.linked_items, self.items),
+            self.linked_interactions,
+            self.interactions,
+            pct(self.linked_interactions, self.interactions),
+        );
+    }
+
+    /// Save the statistics as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let out = File::create(path)?;
+        serde_json::to_writer_pretty(out, self)?;
+        Ok(())
+    }
+}
+
+fn pct(n: u32, d: u32) -> f64 {
+    if d > 0 {
+        n as f64 * 100.0 / d as f64
+    } else {
+        0.0
+    }
+}
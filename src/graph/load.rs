@@ -4,7 +4,10 @@ use anyhow::{anyhow, Result};
 use log::*;
 
 use crate::layout::Config;
+use crate::util::default;
 
+use super::curation::Curation;
+use super::model::{GRAPH_EDGE_PATH, GRAPH_NODE_PATH};
 use super::sources::*;
 use super::{BookID, IdGraph, IdNode};
 use polars::prelude::*;
@@ -17,7 +20,7 @@ struct GraphBuilder {
 }
 
 impl GraphBuilder {
-    fn add_vertices<R: NodeRead>(&mut self, src: R) -> Result<()> {
+    fn add_vertices(&mut self, src: &dyn NodeRead) -> Result<()> {
         info!("scanning vertices from {:?}", src);
         let node_df = src.read_node_ids()?;
         debug!("node schema: {:?}", node_df.schema());
@@ -29,16 +32,22 @@ impl GraphBuilder {
         let code_s = code_s.cast(&DataType::Int32)?;
         let codes = code_s.i32()?;
         let labels = node_df.column("label").ok().map(|c| c.str()).transpose()?;
+        let clusters = node_df
+            .column("cluster")
+            .ok()
+            .map(|c| c.i32())
+            .transpose()?;
         for i in 0..codes.len() {
             let code = codes.get(i).unwrap();
             let label = labels.map(|c| c.get(i)).flatten();
             let label = label.map(|s| s.to_string());
+            let cluster = clusters.and_then(|c| c.get(i)).unwrap_or(0);
             let entry = self.nodes.entry(code);
             entry.or_insert_with(|| {
                 self.graph.add_node(BookID {
                     code,
                     label,
-                    cluster: 0,
+                    cluster,
                 })
             });
         }
@@ -52,7 +61,7 @@ impl GraphBuilder {
         Ok(())
     }
 
-    fn add_edges<R: EdgeRead>(&mut self, src: R) -> Result<()> {
+    fn add_edges(&mut self, src: &dyn EdgeRead) -> Result<()> {
         info!("scanning edges from {:?}", src);
         let edge_df = src.read_edges()?;
         debug!("edge schema: {:?}", edge_df.schema());
@@ -86,28 +95,107 @@ impl GraphBuilder {
     }
 }
 
-pub fn construct_graph(cfg: &Config) -> Result<IdGraph> {
+/// Read nodes and edges for selected clusters (or all of them) from the exported
+/// graph tables.
+#[derive(Debug)]
+struct ClusterTables<'a> {
+    clusters: Option<&'a [i32]>,
+}
+
+impl<'a> ClusterTables<'a> {
+    fn scan_nodes(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet(GRAPH_NODE_PATH, default())?;
+        if let Some(clusters) = self.clusters {
+            let filter = clusters
+                .iter()
+                .fold(lit(false), |f, c| f.or(col("cluster").eq(lit(*c))));
+            Ok(df.filter(filter))
+        } else {
+            Ok(df)
+        }
+    }
+}
+
+impl<'a> NodeRead for ClusterTables<'a> {
+    fn read_node_ids(&self) -> Result<LazyFrame> {
+        let df = self.scan_nodes()?;
+        let df = df.select([col("book_code").alias("code"), col("label"), col("cluster")]);
+        Ok(df)
+    }
+}
+
+impl<'a> EdgeRead for ClusterTables<'a> {
+    fn read_edges(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet(GRAPH_EDGE_PATH, default())?;
+        if self.clusters.is_none() {
+            return Ok(df.select([col("src"), col("dst")]));
+        }
+        let nodes = self.scan_nodes()?.select([col("book_code")]);
+        // edges never cross clusters, so checking the source is enough
+        let df = df.join(
+            nodes,
+            [col("src")],
+            [col("book_code")],
+            JoinType::Inner.into(),
+        );
+        Ok(df.select([col("src"), col("dst")]))
+    }
+}
+
+/// Load the subgraph for a set of clusters from the exported node and edge tables.
+///
+/// This is much cheaper than loading the whole saved graph when we only need to
+/// look at a few clusters.
+pub fn load_cluster_graph(clusters: &[i32]) -> Result<IdGraph> {
+    read_cluster_tables(Some(clusters))
+}
+
+/// Load the whole clustered graph from the exported node and edge tables.
+///
+/// The nodes keep the clusters they were assigned when the tables were written, so
+/// this rebuilds the graph saved by `cluster-books --save-graph` from the outputs of
+/// any of the clustering methods.
+pub fn load_clustered_graph() -> Result<IdGraph> {
+    let graph = read_cluster_tables(None)?;
+    info!(
+        "graph has {} nodes, {} edges",
+        graph.node_count(),
+        graph.edge_count()
+    );
+    Ok(graph)
+}
+
+fn read_cluster_tables(clusters: Option<&[i32]>) -> Result<IdGraph> {
+    let graph = IdGraph::new_undirected();
+    let nodes = NodeMap::new();
+    let mut gb = GraphBuilder { graph, nodes };
+    let src = ClusterTables { clusters };
+
+    gb.add_vertices(&src)?;
+    gb.add_edges(&src)?;
+
+    Ok(gb.graph)
+}
+
+/// Construct the book graph from the source tables, applying curation overrides.
+pub fn construct_graph(cfg: &Config, curation: &Curation) -> Result<IdGraph> {
     let graph = IdGraph::new_undirected();
     let nodes = NodeMap::new();
     let mut gb = GraphBuilder { graph, nodes };
 
     info!("loading nodes");
-    gb.add_vertices(ISBN)?;
-    gb.add_vertices(LOC)?;
-    gb.add_vertices(OLEditions)?;
-    gb.add_vertices(OLWorks)?;
-    if cfg.goodreads.enabled {
-        gb.add_vertices(GRBooks)?;
-        gb.add_vertices(GRWorks)?;
+    for src in node_sources(cfg)? {
+        gb.add_vertices(src.as_ref())?;
     }
 
     info!("loading edges");
-    gb.add_edges(LOC)?;
-    gb.add_edges(OLEditions)?;
-    gb.add_edges(OLWorks)?;
-    if cfg.goodreads.enabled {
-        gb.add_edges(GRBooks)?;
-        gb.add_edges(GRWorks)?;
+    for src in edge_sources(cfg)? {
+        gb.add_edges(src.as_ref())?;
+    }
+
+    if !curation.is_empty() {
+        info!("applying {} curation overrides", curation.overrides.len());
+        curation.apply(&mut gb.graph, &gb.nodes)?;
     }
 
     let graph = gb.graph;
@@ -21,16 +21,37 @@ pub struct BookID {
 pub type IdGraph = Graph<BookID, (), Undirected>;
 pub type IdNode = NodeIndex<DefaultIx>;
 
+pub mod columnar;
+pub mod curation;
+pub mod cuts;
+pub mod evaluate;
+mod export;
 mod gml;
+pub mod hierarchy;
+pub mod incremental;
+pub mod linkage;
 mod load;
 pub mod model;
+pub mod paths;
 mod sources;
+mod stream;
+pub mod suspects;
+pub mod titles;
+mod unionfind;
 
-pub use gml::save_gml;
-pub use load::construct_graph;
+pub use curation::Curation;
+pub use export::export_graph;
+pub use incremental::incremental_clusters;
+pub use load::{construct_graph, load_cluster_graph, load_clustered_graph};
+pub use stream::stream_clusters;
 
 /// Save a graph to a compressed, encoded file.
+///
+/// Files with the `.bdgraph` extension are saved in [columnar] format.
 pub fn save_graph<P: AsRef<Path>>(graph: &IdGraph, path: P) -> Result<()> {
+    if columnar::is_columnar_path(&path) {
+        return columnar::save_columnar(graph, path);
+    }
     let file = File::create(path)?;
     let mut out = Encoder::new(file, 4)?;
     out.multithread(4)?;
@@ -40,9 +61,33 @@ pub fn save_graph<P: AsRef<Path>>(graph: &IdGraph, path: P) -> Result<()> {
 }
 
 /// Load a graph from a compressed, encoded file.
+///
+/// Files with the `.bdgraph` extension are loaded from [columnar] format.
 pub fn load_graph<P: AsRef<Path>>(path: P) -> Result<IdGraph> {
+    if columnar::is_columnar_path(&path) {
+        return columnar::ColumnarGraph::open(path)?.to_graph();
+    }
     let file = File::open(path)?;
     let rdr = Decoder::new(file)?;
     let g = rmp_serde::decode::from_read(rdr)?;
     Ok(g)
 }
+
+/// Build a small graph with sequential codes for testing graph algorithms.
+#[cfg(test)]
+pub(crate) fn test_graph(n: usize, edges: &[(usize, usize)]) -> (IdGraph, Vec<IdNode>) {
+    let mut g = IdGraph::new_undirected();
+    let nodes: Vec<_> = (0..n)
+        .map(|i| {
+            g.add_node(BookID {
+                code: i as i32,
+                label: None,
+                cluster: 0,
+            })
+        })
+        .collect();
+    for (s, d) in edges {
+        g.add_edge(nodes[*s], nodes[*d], ());
+    }
+    (g, nodes)
+}
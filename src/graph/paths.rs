@@ -0,0 +1,123 @@
+//! Path search in the identifier graph.
+use std::collections::{HashSet, VecDeque};
+
+use petgraph::visit::EdgeRef;
+
+use super::{IdGraph, IdNode};
+
+/// Find shortest paths between two nodes.
+///
+/// This runs a breadth-first search from `src`, recording every predecessor that
+/// reaches a node at its minimum distance, and then enumerates up to `limit`
+/// distinct shortest paths from `src` to `dst`.  Returns an empty vector if the
+/// nodes are not connected.
+pub fn shortest_paths(graph: &IdGraph, src: IdNode, dst: IdNode, limit: usize) -> Vec<Vec<IdNode>> {
+    let n = graph.node_count();
+    let mut dist = vec![usize::MAX; n];
+    let mut preds: Vec<Vec<IdNode>> = vec![Vec::new(); n];
+    let mut queue = VecDeque::new();
+
+    dist[src.index()] = 0;
+    queue.push_back(src);
+    while let Some(v) = queue.pop_front() {
+        let dv = dist[v.index()];
+        if dv >= dist[dst.index()] {
+            // everything else is at least as far as the destination
+            break;
+        }
+        for e in graph.edges(v) {
+            let w = e.target();
+            let wi = w.index();
+            if dist[wi] == usize::MAX {
+                dist[wi] = dv + 1;
+                queue.push_back(w);
+            }
+            if dist[wi] == dv + 1 && !preds[wi].contains(&v) {
+                preds[wi].push(v);
+            }
+        }
+    }
+
+    let mut paths = Vec::new();
+    if dist[dst.index()] == usize::MAX {
+        return paths;
+    }
+
+    // walk predecessor lists back from the destination
+    let mut stack = vec![vec![dst]];
+    while let Some(partial) = stack.pop() {
+        if paths.len() >= limit {
+            break;
+        }
+        let head = *partial.last().unwrap();
+        if head == src {
+            let mut path = partial;
+            path.reverse();
+            paths.push(path);
+        } else {
+            for p in preds[head.index()].iter().rev() {
+                let mut next = partial.clone();
+                next.push(*p);
+                stack.push(next);
+            }
+        }
+    }
+
+    paths
+}
+
+/// Find the nodes within `radius` hops of a node, including the node itself.
+pub fn ego_nodes(graph: &IdGraph, center: IdNode, radius: usize) -> HashSet<IdNode> {
+    let mut seen = HashSet::new();
+    seen.insert(center);
+    let mut frontier = vec![center];
+    for _ in 0..radius {
+        let mut next = Vec::new();
+        for v in frontier {
+            for w in graph.neighbors(v) {
+                if seen.insert(w) {
+                    next.push(w);
+                }
+            }
+        }
+        frontier = next;
+    }
+    seen
+}
+
+#[cfg(test)]
+use super::test_graph;
+
+#[test]
+fn test_path_line() {
+    let (g, n) = test_graph(4, &[(0, 1), (1, 2), (2, 3)]);
+    let paths = shortest_paths(&g, n[0], n[3], 5);
+    assert_eq!(paths, vec![vec![n[0], n[1], n[2], n[3]]]);
+}
+
+#[test]
+fn test_path_diamond() {
+    let (g, n) = test_graph(4, &[(0, 1), (0, 2), (1, 3), (2, 3)]);
+    let paths = shortest_paths(&g, n[0], n[3], 5);
+    assert_eq!(paths.len(), 2);
+    let paths = shortest_paths(&g, n[0], n[3], 1);
+    assert_eq!(paths.len(), 1);
+}
+
+#[test]
+fn test_path_disconnected() {
+    let (g, n) = test_graph(4, &[(0, 1), (2, 3)]);
+    let paths = shortest_paths(&g, n[0], n[3], 5);
+    assert!(paths.is_empty());
+}
+
+#[test]
+fn test_ego_radius() {
+    let (g, n) = test_graph(5, &[(0, 1), (1, 2), (2, 3), (3, 4)]);
+    let ego = ego_nodes(&g, n[2], 0);
+    assert_eq!(ego.len(), 1);
+    let ego = ego_nodes(&g, n[2], 1);
+    assert_eq!(ego, [n[1], n[2], n[3]].into_iter().collect());
+    let ego = ego_nodes(&g, n[0], 10);
+    assert_eq!(ego.len(), 5);
+}
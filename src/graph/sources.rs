@@ -1,10 +1,15 @@
-use std::fmt::Debug;
+use std::collections::HashSet;
+use std::fmt::{self, Debug};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use polars::prelude::*;
 
+use super::titles::TITLE_LINK_PATH;
+use crate::cleaning::lccns::norm_lccn;
 use crate::ids::codes::*;
+use crate::layout::config::GraphSourceConfig;
+use crate::layout::Config;
 use crate::util::default;
 
 pub trait EdgeRead: Debug {
@@ -27,9 +32,230 @@ pub struct OLWorks;
 pub struct GRBooks;
 #[derive(Debug)]
 pub struct GRWorks;
+#[derive(Debug)]
+pub struct ASIN;
+#[derive(Debug)]
+pub struct BX;
+#[derive(Debug)]
+pub struct TitleLinks;
+#[derive(Debug)]
+pub struct OLLCCN;
+#[derive(Debug)]
+pub struct OLGoodReads;
+
+/// A graph source declared in the configuration.
+pub struct Declared {
+    name: String,
+    path: String,
+    ns: &'static NS<'static>,
+    id_column: String,
+    isbn_column: String,
+    label_column: Option<String>,
+}
+
+/// Names of the built-in sources that the configuration can disable.
+const BUILTIN_SOURCES: &[&str] = &[
+    "LOC",
+    "OLEditions",
+    "OLWorks",
+    "GRBooks",
+    "GRWorks",
+    "ASIN",
+    "BX",
+    "TitleLinks",
+    "OLLCCN",
+    "OLGoodReads",
+];
+
+/// Built-in sources that link records to each other directly, instead of through
+/// their ISBNs and works.
+pub const DIRECT_SOURCES: &[&str] = &["TitleLinks", "OLLCCN", "OLGoodReads"];
+
+/// Built-in sources that link records to their works.
+///
+/// The other sources, apart from [DIRECT_SOURCES], link ISBNs to records.
+pub const WORK_SOURCES: &[&str] = &["OLWorks", "GRWorks"];
+
+/// Built-in sources whose edges are computed from the records of other sources.
+///
+/// When one of these sources' inputs changes, its edges must be re-read as well.
+const SOURCE_DEPENDENCIES: &[(&str, &[&str])] = &[
+    ("TitleLinks", &["LOC", "OLEditions", "GRBooks"]),
+    ("OLLCCN", &["LOC", "OLEditions"]),
+    ("OLGoodReads", &["OLEditions", "GRBooks"]),
+];
+
+impl Declared {
+    fn from_config(sc: &GraphSourceConfig) -> Result<Declared> {
+        let path = sc.path.clone().unwrap();
+        if BUILTIN_SOURCES.contains(&sc.name.as_str()) {
+            return Err(anyhow!(
+                "source {} conflicts with a built-in source",
+                sc.name
+            ));
+        }
+        let ns = sc
+            .namespace
+            .as_ref()
+            .ok_or_else(|| anyhow!("source {} has no namespace", sc.name))?;
+        let ns = NS::by_name(ns)
+            .ok_or_else(|| anyhow!("source {}: unknown namespace {}", sc.name, ns))?;
+        if ns.code() == NS_ISBN.code() {
+            return Err(anyhow!("source {} cannot use the ISBN namespace", sc.name));
+        }
+        Ok(Declared {
+            name: sc.name.clone(),
+            path,
+            ns,
+            id_column: sc.id_column.clone().unwrap_or_else(|| "id".into()),
+            isbn_column: sc.isbn_column.clone().unwrap_or_else(|| "isbn_id".into()),
+            label_column: sc.label_column.clone(),
+        })
+    }
+}
+
+impl Debug for Declared {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.path)
+    }
+}
+
+/// Get the enabled sources declared in a configuration.
+///
+/// An entry without a path configures a built-in source, so it is an error for it
+/// to have any other name.
+fn declared_sources(cfg: &Config) -> Result<Vec<Declared>> {
+    let mut srcs = Vec::new();
+    for sc in &cfg.graph.sources {
+        if sc.path.is_none() {
+            if !BUILTIN_SOURCES.contains(&sc.name.as_str()) {
+                return Err(anyhow!(
+                    "source {} has no path and is not a built-in source",
+                    sc.name
+                ));
+            }
+        } else if sc.enabled {
+            srcs.push(Declared::from_config(sc)?);
+        }
+    }
+    Ok(srcs)
+}
+
+/// Get the node sources enabled by a configuration.
+pub fn node_sources(cfg: &Config) -> Result<Vec<Box<dyn NodeRead>>> {
+    let gc = &cfg.graph;
+    let gr = cfg.goodreads.enabled;
+    let az = cfg.az2014.enabled || cfg.az2018.enabled;
+    let mut srcs: Vec<Box<dyn NodeRead>> = vec![Box::new(ISBN)];
+    if gc.builtin_enabled("LOC", true) {
+        srcs.push(Box::new(LOC));
+    }
+    if gc.builtin_enabled("OLEditions", true) {
+        srcs.push(Box::new(OLEditions));
+    }
+    if gc.builtin_enabled("OLWorks", true) {
+        srcs.push(Box::new(OLWorks));
+    }
+    if gc.builtin_enabled("GRBooks", gr) {
+        srcs.push(Box::new(GRBooks));
+    }
+    if gc.builtin_enabled("GRWorks", gr) {
+        srcs.push(Box::new(GRWorks));
+    }
+    if gc.builtin_enabled("ASIN", az) {
+        srcs.push(Box::new(ASIN));
+    }
+    if gc.builtin_enabled("BX", cfg.bx.enabled) {
+        srcs.push(Box::new(BX));
+    }
+    for src in declared_sources(cfg)? {
+        srcs.push(Box::new(src));
+    }
+    Ok(srcs)
+}
+
+/// Get the edge sources enabled by a configuration.
+pub fn edge_sources(cfg: &Config) -> Result<Vec<Box<dyn EdgeRead>>> {
+    Ok(named_edge_sources(cfg)?
+        .into_iter()
+        .map(|(_, src)| src)
+        .collect())
+}
+
+/// Get the edge sources enabled by a configuration, with their names.
+///
+/// The name of each source is the one recorded with its edges in the graph edge
+/// table; declared sources are named by their namespace.
+pub fn named_edge_sources(cfg: &Config) -> Result<Vec<(String, Box<dyn EdgeRead>)>> {
+    let gc = &cfg.graph;
+    let gr = cfg.goodreads.enabled;
+    let az = cfg.az2014.enabled || cfg.az2018.enabled;
+    let mut srcs: Vec<(String, Box<dyn EdgeRead>)> = Vec::new();
+    if gc.builtin_enabled("LOC", true) {
+        srcs.push(("LOC".into(), Box::new(LOC)));
+    }
+    if gc.builtin_enabled("OLEditions", true) {
+        srcs.push(("OLEditions".into(), Box::new(OLEditions)));
+    }
+    if gc.builtin_enabled("OLWorks", true) {
+        srcs.push(("OLWorks".into(), Box::new(OLWorks)));
+    }
+    if gc.builtin_enabled("GRBooks", gr) {
+        srcs.push(("GRBooks".into(), Box::new(GRBooks)));
+    }
+    if gc.builtin_enabled("GRWorks", gr) {
+        srcs.push(("GRWorks".into(), Box::new(GRWorks)));
+    }
+    if gc.builtin_enabled("ASIN", az) {
+        srcs.push(("ASIN".into(), Box::new(ASIN)));
+    }
+    if gc.builtin_enabled("BX", cfg.bx.enabled) {
+        srcs.push(("BX".into(), Box::new(BX)));
+    }
+    if gc.builtin_enabled("TitleLinks", false) {
+        srcs.push(("TitleLinks".into(), Box::new(TitleLinks)));
+    }
+    let ol = gc.builtin_enabled("OLEditions", true);
+    if ol && gc.builtin_enabled("LOC", true) && gc.builtin_enabled("OLLCCN", true) {
+        srcs.push(("OLLCCN".into(), Box::new(OLLCCN)));
+    }
+    if ol && gc.builtin_enabled("GRBooks", gr) && gc.builtin_enabled("OLGoodReads", gr) {
+        srcs.push(("OLGoodReads".into(), Box::new(OLGoodReads)));
+    }
+    for src in declared_sources(cfg)? {
+        srcs.push((src.ns.name().to_string(), Box::new(src)));
+    }
+    Ok(srcs)
+}
+
+/// Resolve a source name given on the command line to the name of its edges.
+///
+/// Built-in sources are named directly; declared sources may be named either by
+/// their configured name or by their namespace.
+pub fn resolve_source_name(cfg: &Config, name: &str) -> Result<String> {
+    let declared = declared_sources(cfg)?;
+    if BUILTIN_SOURCES.contains(&name) {
+        return Ok(name.to_string());
+    }
+    for src in declared {
+        if src.name == name || src.ns.name() == name {
+            return Ok(src.ns.name().to_string());
+        }
+    }
+    Err(anyhow!("unknown graph source {}", name))
+}
+
+/// Add the sources whose edges are computed from any of a set of sources.
+pub fn add_dependent_sources(names: &mut HashSet<String>) {
+    for (src, deps) in SOURCE_DEPENDENCIES {
+        if deps.iter().any(|d| names.contains(*d)) {
+            names.insert(src.to_string());
+        }
+    }
+}
 
 /// Get an ID column and apply the appropriate namespace adjustment.
-fn id_col(name: &str, ns: NS<'_>) -> Expr {
+pub(super) fn id_col(name: &str, ns: &NS<'_>) -> Expr {
     col(name) + lit(ns.base())
 }
 
@@ -37,7 +263,7 @@ impl NodeRead for ISBN {
     fn read_node_ids(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("book-links/all-isbns.parquet", default())?;
         let df = df.select([
-            id_col("isbn_id", NS_ISBN).alias("code"),
+            id_col("isbn_id", &NS_ISBN).alias("code"),
             col("isbn").alias("label"),
         ]);
         Ok(df)
@@ -47,7 +273,7 @@ impl NodeRead for ISBN {
 impl NodeRead for LOC {
     fn read_node_ids(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("loc-mds/book-ids.parquet", default())?;
-        let df = df.select([id_col("rec_id", NS_LOC_REC).alias("code")]);
+        let df = df.select([id_col("rec_id", &NS_LOC_REC).alias("code")]);
         Ok(df)
     }
 }
@@ -56,8 +282,8 @@ impl EdgeRead for LOC {
     fn read_edges(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("loc-mds/book-isbn-ids.parquet", default())?;
         let df = df.select([
-            id_col("isbn_id", NS_ISBN).alias("src"),
-            id_col("rec_id", NS_LOC_REC).alias("dst"),
+            id_col("isbn_id", &NS_ISBN).alias("src"),
+            id_col("rec_id", &NS_LOC_REC).alias("dst"),
         ]);
         Ok(df)
     }
@@ -66,7 +292,7 @@ impl EdgeRead for LOC {
 impl NodeRead for OLEditions {
     fn read_node_ids(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("openlibrary/editions.parquet", default())?;
-        let df = df.select([id_col("id", NS_EDITION).alias("code")]);
+        let df = df.select([id_col("id", &NS_EDITION).alias("code")]);
         Ok(df)
     }
 }
@@ -75,8 +301,8 @@ impl EdgeRead for OLEditions {
     fn read_edges(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("openlibrary/edition-isbn-ids.parquet", default())?;
         let df = df.select([
-            id_col("isbn_id", NS_ISBN).alias("src"),
-            id_col("edition", NS_EDITION).alias("dst"),
+            id_col("isbn_id", &NS_ISBN).alias("src"),
+            id_col("edition", &NS_EDITION).alias("dst"),
         ]);
         Ok(df)
     }
@@ -85,11 +311,11 @@ impl EdgeRead for OLEditions {
 impl NodeRead for OLWorks {
     fn read_node_ids(&self) -> Result<LazyFrame> {
         let wdf = LazyFrame::scan_parquet("openlibrary/works.parquet", default())?.select([
-            id_col("id", NS_WORK).alias("code"),
+            id_col("id", &NS_WORK).alias("code"),
             col("key").alias("label"),
         ]);
         let ewdf = LazyFrame::scan_parquet("openlibrary/edition-works.parquet", default())?
-            .select([id_col("work", NS_WORK).alias("code")])
+            .select([id_col("work", &NS_WORK).alias("code")])
             .unique(None, UniqueKeepStrategy::Any);
         let df = wdf.join(
             ewdf,
@@ -105,8 +331,8 @@ impl EdgeRead for OLWorks {
     fn read_edges(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("openlibrary/edition-works.parquet", default())?;
         let df = df.select([
-            id_col("edition", NS_EDITION).alias("src"),
-            id_col("work", NS_WORK).alias("dst"),
+            id_col("edition", &NS_EDITION).alias("src"),
+            id_col("work", &NS_WORK).alias("dst"),
         ]);
         Ok(df)
     }
@@ -115,7 +341,7 @@ impl EdgeRead for OLWorks {
 impl NodeRead for GRBooks {
     fn read_node_ids(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("goodreads/gr-book-ids.parquet", default())?;
-        let df = df.select([id_col("book_id", NS_GR_BOOK).alias("code")]);
+        let df = df.select([id_col("book_id", &NS_GR_BOOK).alias("code")]);
         Ok(df)
     }
 }
@@ -124,8 +350,8 @@ impl EdgeRead for GRBooks {
     fn read_edges(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("goodreads/book-isbn-ids.parquet", default())?;
         let df = df.select([
-            id_col("isbn_id", NS_ISBN).alias("src"),
-            id_col("book_id", NS_GR_BOOK).alias("dst"),
+            id_col("isbn_id", &NS_ISBN).alias("src"),
+            id_col("book_id", &NS_GR_BOOK).alias("dst"),
         ]);
         Ok(df)
     }
@@ -135,7 +361,7 @@ impl NodeRead for GRWorks {
     fn read_node_ids(&self) -> Result<LazyFrame> {
         let df = LazyFrame::scan_parquet("goodreads/gr-book-ids.parquet", default())?;
         let df = df.filter(col("work_id").is_not_null());
-        let df = df.select([id_col("work_id", NS_GR_WORK).alias("code")]);
+        let df = df.select([id_col("work_id", &NS_GR_WORK).alias("code")]);
         Ok(df)
     }
 }
@@ -145,9 +371,247 @@ impl EdgeRead for GRWorks {
         let df = LazyFrame::scan_parquet("goodreads/gr-book-ids.parquet", default())?;
         let df = df.filter(col("work_id").is_not_null());
         let df = df.select([
-            id_col("book_id", NS_GR_BOOK).alias("src"),
-            id_col("work_id", NS_GR_WORK).alias("dst"),
+            id_col("book_id", &NS_GR_BOOK).alias("src"),
+            id_col("work_id", &NS_GR_WORK).alias("dst"),
+        ]);
+        Ok(df)
+    }
+}
+
+impl NodeRead for Declared {
+    fn read_node_ids(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet(&self.path, default())?;
+        let mut cols = vec![id_col(&self.id_column, self.ns).alias("code")];
+        if let Some(lc) = &self.label_column {
+            cols.push(col(lc).alias("label"));
+        }
+        let df = df
+            .select(cols)
+            .unique(Some(vec!["code".into()]), UniqueKeepStrategy::First);
+        Ok(df)
+    }
+}
+
+impl EdgeRead for Declared {
+    fn read_edges(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet(&self.path, default())?;
+        let df = df.filter(col(&self.isbn_column).is_not_null());
+        let df = df.select([
+            id_col(&self.isbn_column, &NS_ISBN).alias("src"),
+            id_col(&self.id_column, self.ns).alias("dst"),
         ]);
         Ok(df)
     }
 }
+
+impl NodeRead for ASIN {
+    fn read_node_ids(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet("book-links/asin-ids.parquet", default())?;
+        let df = df.select([
+            id_col("asin_id", &NS_ASIN).alias("code"),
+            col("asin").alias("label"),
+        ]);
+        Ok(df)
+    }
+}
+
+impl EdgeRead for ASIN {
+    fn read_edges(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet("book-links/asin-ids.parquet", default())?;
+        let df = df.filter(col("isbn_id").is_not_null());
+        let df = df.select([
+            id_col("isbn_id", &NS_ISBN).alias("src"),
+            id_col("asin_id", &NS_ASIN).alias("dst"),
+        ]);
+        Ok(df)
+    }
+}
+
+impl NodeRead for BX {
+    fn read_node_ids(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet("bx/bx-book-ids.parquet", default())?;
+        let df = df.select([
+            id_col("bx_id", &NS_BX).alias("code"),
+            col("isbn").alias("label"),
+        ]);
+        Ok(df)
+    }
+}
+
+impl EdgeRead for BX {
+    fn read_edges(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet("bx/bx-book-ids.parquet", default())?;
+        let df = df.filter(col("isbn_id").is_not_null());
+        let df = df.select([
+            id_col("isbn_id", &NS_ISBN).alias("src"),
+            id_col("bx_id", &NS_BX).alias("dst"),
+        ]);
+        Ok(df)
+    }
+}
+
+impl EdgeRead for TitleLinks {
+    fn read_edges(&self) -> Result<LazyFrame> {
+        let df = LazyFrame::scan_parquet(TITLE_LINK_PATH, default())?;
+        let df = df.select([col("src"), col("dst")]);
+        Ok(df)
+    }
+}
+
+/// Scan the OpenLibrary edition identifiers in a scheme.
+fn scan_edition_ids(scheme: &str) -> Result<LazyFrame> {
+    let df = LazyFrame::scan_parquet("openlibrary/edition-identifiers.parquet", default())?;
+    let df = df
+        .filter(col("scheme").eq(lit(scheme)))
+        .select([col("edition"), col("value")]);
+    Ok(df)
+}
+
+fn udf_norm_lccn(s: Series) -> PolarsResult<Option<Series>> {
+    let res: StringChunked = s
+        .str()?
+        .into_iter()
+        .map(|v| v.and_then(norm_lccn))
+        .collect();
+    Ok(Some(res.into_series()))
+}
+
+/// Parse a GoodReads book ID from the leading digits of an identifier.
+fn parse_gr_id(value: &str) -> Option<i32> {
+    let end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+fn udf_gr_id(s: Series) -> PolarsResult<Option<Series>> {
+    let res: Int32Chunked = s
+        .str()?
+        .into_iter()
+        .map(|v| v.and_then(parse_gr_id))
+        .collect();
+    Ok(Some(res.into_series()))
+}
+
+impl EdgeRead for OLLCCN {
+    fn read_edges(&self) -> Result<LazyFrame> {
+        let eds = scan_edition_ids("lccn")?.select([
+            col("edition"),
+            col("value")
+                .map(udf_norm_lccn, GetOutput::from_type(DataType::String))
+                .alias("lccn"),
+        ]);
+        let locs = LazyFrame::scan_parquet("loc-mds/book-ids.parquet", default())?;
+        let locs = locs
+            .select([
+                col("rec_id"),
+                col("lccn").map(udf_norm_lccn, GetOutput::from_type(DataType::String)),
+            ])
+            .filter(col("lccn").is_not_null());
+        // an LCCN on more than one LOC record does not identify a book
+        let unique = locs
+            .clone()
+            .group_by([col("lccn")])
+            .agg([col("rec_id").count().alias("n")])
+            .filter(col("n").eq(lit(1)))
+            .select([col("lccn")]);
+        let locs = locs.join(unique, [col("lccn")], [col("lccn")], JoinType::Inner.into());
+        let df = eds.join(locs, [col("lccn")], [col("lccn")], JoinType::Inner.into());
+        let df = df
+            .select([
+                id_col("edition", &NS_EDITION).alias("src"),
+                id_col("rec_id", &NS_LOC_REC).alias("dst"),
+            ])
+            .unique(None, UniqueKeepStrategy::Any);
+        Ok(df)
+    }
+}
+
+impl EdgeRead for OLGoodReads {
+    fn read_edges(&self) -> Result<LazyFrame> {
+        let eds = scan_edition_ids("goodreads")?.select([
+            col("edition"),
+            col("value")
+                .map(udf_gr_id, GetOutput::from_type(DataType::Int32))
+                .alias("book_id"),
+        ]);
+        // only link to books we have, so both ends are graph nodes
+        let books = LazyFrame::scan_parquet("goodreads/gr-book-ids.parquet", default())?
+            .select([col("book_id").cast(DataType::Int32)]);
+        let df = eds.join(
+            books,
+            [col("book_id")],
+            [col("book_id")],
+            JoinType::Inner.into(),
+        );
+        let df = df
+            .select([
+                id_col("edition", &NS_EDITION).alias("src"),
+                id_col("book_id", &NS_GR_BOOK).alias("dst"),
+            ])
+            .unique(None, UniqueKeepStrategy::Any);
+        Ok(df)
+    }
+}
+
+#[test]
+fn test_parse_gr_id() {
+    assert_eq!(parse_gr_id("12345"), Some(12345));
+    assert_eq!(parse_gr_id("12345.Some_Title"), Some(12345));
+    assert_eq!(parse_gr_id("abc"), None);
+}
+
+#[test]
+fn test_declared_sources_unknown_builtin() {
+    let cfg: Config = serde_yaml::from_str(
+        r#"
+bx:
+  enabled: false
+az2014:
+  enabled: false
+az2018:
+  enabled: false
+goodreads:
+  enabled: true
+graph:
+  sources:
+    - name: OLEditions
+      enabled: false
+"#,
+    )
+    .expect("parse failed");
+    assert!(declared_sources(&cfg).expect("valid sources").is_empty());
+
+    let cfg: Config = serde_yaml::from_str(
+        r#"
+bx:
+  enabled: false
+az2014:
+  enabled: false
+az2018:
+  enabled: false
+goodreads:
+  enabled: true
+graph:
+  sources:
+    - name: OLEdition
+      enabled: false
+"#,
+    )
+    .expect("parse failed");
+    assert!(declared_sources(&cfg).is_err());
+    assert!(resolve_source_name(&cfg, "OLEditions").is_err());
+}
+
+#[test]
+fn test_dependent_sources() {
+    let mut names: HashSet<String> = ["LOC".to_string()].into_iter().collect();
+    add_dependent_sources(&mut names);
+    assert!(names.contains("OLLCCN"));
+    assert!(names.contains("TitleLinks"));
+    assert!(!names.contains("OLGoodReads"));
+
+    let mut names: HashSet<String> = ["OLWorks".to_string()].into_iter().collect();
+    add_dependent_sources(&mut names);
+    assert_eq!(names.len(), 1);
+}
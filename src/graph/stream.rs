@@ -0,0 +1,480 @@
+//! Cluster books by streaming edges into a union-find.
+//!
+//! This computes the same clusters as building the full [IdGraph] and finding its
+//! connected components, but only keeps a sorted code list and a [UnionFind] in
+//! memory.  Node labels are joined back in with Polars when writing the outputs.
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+
+use anyhow::{anyhow, Result};
+use log::*;
+use polars::prelude::*;
+
+use super::curation::*;
+use super::hierarchy::remove_cluster_hierarchy;
+use super::model::*;
+use super::sources::*;
+use super::unionfind::UnionFind;
+use crate::arrow::writer::save_df_parquet_nonnull;
+use crate::arrow::TableWriter;
+use crate::ids::codes::*;
+use crate::io::object::ObjectWriter;
+use crate::layout::Config;
+use crate::util::default;
+
+/// Book codes with dense indices.
+pub(super) struct CodeIndex {
+    codes: Vec<i32>,
+}
+
+impl CodeIndex {
+    pub(super) fn load(cfg: &Config) -> Result<CodeIndex> {
+        let mut codes = Vec::new();
+        for src in node_sources(cfg)? {
+            info!("scanning vertices from {:?}", src);
+            let df = src.read_node_ids()?;
+            let df = df.select([col("code").cast(DataType::Int32)]).collect()?;
+            let col = df.column("code")?.i32()?;
+            codes.extend(col.into_iter().flatten());
+        }
+        let index = CodeIndex::from_codes(codes);
+        info!("found {} vertices", index.len());
+        Ok(index)
+    }
+
+    /// Index a list of codes, which may be unsorted and contain duplicates.
+    pub(super) fn from_codes(mut codes: Vec<i32>) -> CodeIndex {
+        codes.sort_unstable();
+        codes.dedup();
+        CodeIndex { codes }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    pub(super) fn lookup(&self, code: i32) -> Option<u32> {
+        self.codes.binary_search(&code).ok().map(|i| i as u32)
+    }
+
+    pub(super) fn code(&self, i: u32) -> i32 {
+        self.codes[i as usize]
+    }
+}
+
+/// Resolve curation overrides to node indices.
+pub(super) fn resolve_curation<'a>(
+    curation: &'a Curation,
+    index: &CodeIndex,
+) -> Result<Resolved<'a, u32>> {
+    let wanted = curation.isbns();
+    let mut isbns = HashMap::new();
+    if !wanted.is_empty() {
+        let filter = wanted
+            .iter()
+            .fold(lit(false), |f, i| f.or(col("isbn").eq(lit(*i))));
+        let df = LazyFrame::scan_parquet("book-links/all-isbns.parquet", default())?;
+        let df = df
+            .filter(filter)
+            .select([col("isbn"), col("isbn_id").cast(DataType::Int32)])
+            .collect()?;
+        let labels = df.column("isbn")?.str()?;
+        let ids = df.column("isbn_id")?.i32()?;
+        for (l, id) in labels.into_iter().zip(ids) {
+            if let (Some(l), Some(id)) = (l, id) {
+                isbns.insert(l.to_string(), NS_ISBN.to_code(id));
+            }
+        }
+    }
+
+    Ok(curation.resolve_with(|r| match r {
+        BookRef::ISBN(isbn) => isbns.get(isbn).and_then(|c| index.lookup(*c)),
+        r => r.code().and_then(|c| index.lookup(c)),
+    }))
+}
+
+/// Log the effects of curation, before forced edges are added.
+///
+/// At this point the union-find has the components of the curated graph without
+/// forced edges.  Every one of these that is part of a cluster affected by curation
+/// contains either an override's node or an endpoint of a removed edge, so we can
+/// compute the before and after clusters on just those components.
+fn log_curation(uf: &mut UnionFind, resolved: &Resolved<'_, u32>, removed: &[(u32, u32)]) {
+    let mut roots: Vec<u32> = resolved
+        .iter()
+        .flat_map(|(_, ns)| ns.iter().copied())
+        .chain(removed.iter().flat_map(|(a, b)| [*a, *b]))
+        .map(|n| uf.find(n))
+        .collect();
+    roots.sort_unstable();
+    roots.dedup();
+    let pos: HashMap<u32, u32> = roots
+        .iter()
+        .enumerate()
+        .map(|(i, r)| (*r, i as u32))
+        .collect();
+
+    let mut before_uf = UnionFind::new(roots.len());
+    for (a, b) in removed {
+        before_uf.union(pos[&uf.find(*a)], pos[&uf.find(*b)]);
+    }
+    let mut after_uf = UnionFind::new(roots.len());
+    for (a, b) in forced_edges(resolved) {
+        after_uf.union(pos[&uf.find(a)], pos[&uf.find(b)]);
+    }
+
+    let mut before = HashMap::new();
+    let mut after = HashMap::new();
+    for (r, i) in &pos {
+        before.insert(*r, before_uf.find(*i) as usize);
+        after.insert(*r, after_uf.find(*i) as usize);
+    }
+    let by_root: Resolved<'_, u32> = resolved
+        .iter()
+        .map(|(o, ns)| (*o, ns.iter().map(|n| uf.find(*n)).collect()))
+        .collect();
+    log_effects(&by_root, &before, &after);
+}
+
+/// Union the graph's edges, writing the ones that survive curation.
+fn union_edges(
+    cfg: &Config,
+    index: &CodeIndex,
+    curation: &Curation,
+    uf: &mut UnionFind,
+) -> Result<()> {
+    let resolved = resolve_curation(curation, index)?;
+    let filter = EdgeFilter::new(&resolved);
+    let forced: HashSet<(u32, u32)> = forced_edges(&resolved)
+        .map(|(a, b)| (a.min(b), a.max(b)))
+        .collect();
+    let mut present = HashSet::new();
+    let mut removed = Vec::new();
+
+    let mut e_w = TableWriter::open(GRAPH_EDGE_PATH)?;
+    for (name, src) in named_edge_sources(cfg)? {
+        info!("scanning edges from {:?}", src);
+        let df = src.read_edges()?;
+        let df = df
+            .select([
+                col("src").cast(DataType::Int32),
+                col("dst").cast(DataType::Int32),
+            ])
+            .collect()?;
+        let srcs = df.column("src")?.i32()?;
+        let dsts = df.column("dst")?.i32()?;
+
+        let mut n = 0;
+        for pair in srcs.into_iter().zip(dsts) {
+            if let (Some(sn), Some(dn)) = pair {
+                let si = index
+                    .lookup(sn)
+                    .ok_or_else(|| anyhow!("unknown source node {}", sn))?;
+                let di = index
+                    .lookup(dn)
+                    .ok_or_else(|| anyhow!("unknown destination node {}", dn))?;
+                if !filter.keep(si, di) {
+                    removed.push((si, di));
+                    continue;
+                }
+                if !forced.is_empty() && forced.contains(&(si.min(di), si.max(di))) {
+                    present.insert((si.min(di), si.max(di)));
+                }
+                uf.union(si, di);
+                e_w.write_object(GraphEdge {
+                    src: sn,
+                    dst: dn,
+                    source: name.clone(),
+                })?;
+                n += 1;
+            }
+        }
+        info!("added {} edges from {:?}", n, src);
+    }
+
+    if !resolved.is_empty() {
+        info!("curation removed {} edges", removed.len());
+        log_curation(uf, &resolved, &removed);
+        let mut n_forced = 0;
+        for (a, b) in forced_edges(&resolved) {
+            if present.insert((a.min(b), a.max(b))) {
+                uf.union(a, b);
+                e_w.write_object(GraphEdge {
+                    src: index.codes[a as usize],
+                    dst: index.codes[b as usize],
+                    source: CURATION_SOURCE.into(),
+                })?;
+                n_forced += 1;
+            }
+        }
+        info!("curation added {} edges", n_forced);
+    }
+
+    e_w.finish()?;
+    Ok(())
+}
+
+/// Scan the node labels provided by the node sources.
+fn scan_labels(cfg: &Config) -> Result<LazyFrame> {
+    let mut frames = Vec::new();
+    for src in node_sources(cfg)? {
+        let df = src.read_node_ids()?;
+        if df.schema()?.get("label").is_some() {
+            frames.push(df.select([col("code").cast(DataType::Int32), col("label")]));
+        }
+    }
+    Ok(concat(frames, UnionArgs::default())?)
+}
+
+/// Cluster books with a streaming union-find and write the cluster outputs.
+///
+/// This writes the ISBN clusters, graph nodes and edges, and cluster statistics,
+/// sorted by cluster.
+pub fn stream_clusters(cfg: &Config, curation: &Curation) -> Result<()> {
+    let index = CodeIndex::load(cfg)?;
+    let mut uf = UnionFind::new(index.len());
+    union_edges(cfg, &index, curation, &mut uf)?;
+    let clusters = label_clusters(&index, uf);
+    write_clusters(cfg, index, clusters)
+}
+
+/// Label each node with its cluster, the smallest code in its set.
+pub(super) fn label_clusters(index: &CodeIndex, mut uf: UnionFind) -> Vec<i32> {
+    // codes are sorted, so the first node we see from each set provides its label
+    info!("labeling clusters");
+    let mut clusters = vec![i32::MIN; index.len()];
+    for i in 0..index.len() {
+        let r = uf.find(i as u32) as usize;
+        if clusters[r] == i32::MIN {
+            clusters[r] = index.codes[i];
+        }
+        clusters[i] = clusters[r];
+    }
+    clusters
+}
+
+/// Make the table of nodes with their types and clusters, without labels.
+pub(super) fn node_frame(index: CodeIndex, clusters: Vec<i32>) -> Result<DataFrame> {
+    let types: Vec<&str> = index
+        .codes
+        .iter()
+        .map(|c| ns_of_book_code(*c).unwrap().name())
+        .collect();
+    Ok(DataFrame::new(vec![
+        Series::new("book_code", index.codes),
+        Series::new("cluster", clusters),
+        Series::new("node_type", types),
+    ])?)
+}
+
+/// Compute the statistics of each cluster from the node table.
+///
+/// This has the number of nodes in each cluster, and the number of each type,
+/// sorted by cluster.
+fn cluster_stats(nodes: LazyFrame) -> LazyFrame {
+    let count_type = |ns: NS<'_>, name: &str| {
+        col("node_type")
+            .eq(lit(ns.name()))
+            .sum()
+            .cast(DataType::UInt32)
+            .alias(name)
+    };
+    nodes
+        .group_by([col("cluster")])
+        .agg([
+            col("book_code")
+                .count()
+                .cast(DataType::UInt32)
+                .alias("n_nodes"),
+            count_type(NS_ISBN, "n_isbns"),
+            count_type(NS_LOC_REC, "n_loc_recs"),
+            count_type(NS_EDITION, "n_ol_editions"),
+            count_type(NS_WORK, "n_ol_works"),
+            count_type(NS_GR_BOOK, "n_gr_books"),
+            count_type(NS_GR_WORK, "n_gr_works"),
+            count_type(NS_ASIN, "n_asins"),
+            count_type(NS_BX, "n_bx"),
+        ])
+        .sort("cluster", default())
+}
+
+/// Write the graph nodes, ISBN clusters, and cluster statistics.
+///
+/// This also removes the work hierarchy of the previous clustering, which must be
+/// saved again from the new graph.
+pub(super) fn write_clusters(cfg: &Config, index: CodeIndex, clusters: Vec<i32>) -> Result<()> {
+    remove_cluster_hierarchy()?;
+    let nodes = node_frame(index, clusters)?;
+
+    info!("writing graph nodes");
+    let nodes = nodes.lazy().join(
+        scan_labels(cfg)?,
+        [col("book_code")],
+        [col("code")],
+        JoinType::Left.into(),
+    );
+    let nodes = nodes
+        .select([
+            col("book_code"),
+            col("cluster"),
+            col("node_type"),
+            col("label"),
+        ])
+        .sort_by_exprs(
+            [col("cluster"), col("book_code")],
+            [false, false],
+            false,
+            false,
+        )
+        .collect()?;
+    save_df_parquet_nonnull(nodes.clone(), GRAPH_NODE_PATH)?;
+
+    info!("writing ISBN clusters");
+    let isbns = nodes
+        .clone()
+        .lazy()
+        .filter(col("node_type").eq(lit(NS_ISBN.name())))
+        .select([
+            col("label").alias("isbn"),
+            (col("book_code") - lit(NS_ISBN.base())).alias("isbn_id"),
+            col("cluster"),
+        ])
+        .collect()?;
+    save_df_parquet_nonnull(isbns, ISBN_CLUSTER_PATH)?;
+
+    info!("writing cluster statistics");
+    let stats = cluster_stats(nodes.lazy()).collect()?;
+
+    let n_nodes = stats.column("n_nodes")?.u32()?;
+    let n_isbns = stats.column("n_isbns")?.u32()?;
+    let metrics = ClusteringStatistics {
+        clusters: stats.height(),
+        largest: n_nodes.max().unwrap_or(0) as usize,
+        max_isbns: n_isbns.max().unwrap_or(0) as usize,
+    };
+    info!(
+        "computed {} clusters, largest has {} nodes",
+        metrics.clusters, metrics.largest
+    );
+    save_df_parquet_nonnull(stats, CLUSTER_STATS_PATH)?;
+
+    info!("saving statistics");
+    let statf = File::create(CLUSTER_METRICS_PATH)?;
+    serde_json::to_writer(statf, &metrics)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_stream_matches_components() {
+    use petgraph::algo::kosaraju_scc;
+
+    // two editions with a work and an ISBN, an ISBN shared by LOC, GoodReads,
+    // ASIN, and BookCrossing records, and a lone GoodReads work
+    let codes = [
+        NS_EDITION.to_code(1),
+        NS_EDITION.to_code(2),
+        NS_WORK.to_code(1),
+        NS_ISBN.to_code(7),
+        NS_ISBN.to_code(3),
+        NS_LOC_REC.to_code(5),
+        NS_GR_BOOK.to_code(4),
+        NS_GR_WORK.to_code(9),
+        NS_ASIN.to_code(2),
+        NS_BX.to_code(6),
+    ];
+    let edges = [(0, 2), (1, 2), (1, 3), (4, 5), (4, 6), (4, 8), (4, 9)];
+    let (mut g, nodes) = super::test_graph(codes.len(), &edges);
+    for (node, code) in nodes.iter().zip(codes) {
+        g.node_weight_mut(*node).unwrap().code = code;
+    }
+
+    // the clusters of the full graph, labeled by their smallest codes
+    let mut labels = HashMap::new();
+    let mut expected = Vec::new();
+    for verts in kosaraju_scc(&g) {
+        let vids: Vec<_> = verts.iter().map(|v| g.node_weight(*v).unwrap()).collect();
+        let cluster = vids.iter().map(|b| b.code).min().unwrap();
+        for b in &vids {
+            labels.insert(b.code, cluster);
+        }
+        let count = |ns: NS<'_>| {
+            vids.iter()
+                .filter(|b| ns.from_code(b.code).is_some())
+                .count() as u32
+        };
+        expected.push((
+            cluster,
+            vids.len() as u32,
+            count(NS_ISBN),
+            count(NS_LOC_REC),
+            count(NS_EDITION),
+            count(NS_WORK),
+            count(NS_GR_BOOK),
+            count(NS_GR_WORK),
+            count(NS_ASIN),
+            count(NS_BX),
+        ));
+    }
+    expected.sort();
+
+    let index = CodeIndex::from_codes(codes.to_vec());
+    let mut uf = UnionFind::new(index.len());
+    for (s, d) in edges {
+        uf.union(
+            index.lookup(codes[s]).unwrap(),
+            index.lookup(codes[d]).unwrap(),
+        );
+    }
+    let clusters = label_clusters(&index, uf);
+
+    for (i, c) in clusters.iter().enumerate() {
+        assert_eq!(*c, labels[&index.code(i as u32)]);
+    }
+
+    let stats = cluster_stats(node_frame(index, clusters).unwrap().lazy())
+        .collect()
+        .expect("stats failed");
+    let col_vec = |name: &str| -> Vec<u32> {
+        stats
+            .column(name)
+            .unwrap()
+            .u32()
+            .unwrap()
+            .into_iter()
+            .map(|v| v.unwrap())
+            .collect()
+    };
+    let ids: Vec<i32> = stats
+        .column("cluster")
+        .unwrap()
+        .i32()
+        .unwrap()
+        .into_iter()
+        .map(|v| v.unwrap())
+        .collect();
+    let cols: Vec<_> = [
+        "n_nodes",
+        "n_isbns",
+        "n_loc_recs",
+        "n_ol_editions",
+        "n_ol_works",
+        "n_gr_books",
+        "n_gr_works",
+        "n_asins",
+        "n_bx",
+    ]
+    .iter()
+    .map(|c| col_vec(c))
+    .collect();
+    let actual: Vec<_> = (0..ids.len())
+        .map(|i| {
+            (
+                ids[i], cols[0][i], cols[1][i], cols[2][i], cols[3][i], cols[4][i], cols[5][i],
+                cols[6][i], cols[7][i], cols[8][i],
+            )
+        })
+        .collect();
+    assert_eq!(actual.len(), 3);
+    assert_eq!(actual, expected);
+}
@@ -0,0 +1,325 @@
+//! Detect promiscuous ISBN nodes.
+//!
+//! A reused or garbage ISBN shows up in the graph as an ISBN node linked to many
+//! records that otherwise have nothing in common.  This computes, for each ISBN,
+//! its degree from each source and the number of distinct titles and authors among
+//! its neighbors, and flags the ones that exceed configured thresholds.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use log::*;
+use parquet_derive::ParquetRecordWriter;
+use polars::prelude::*;
+
+use super::sources::*;
+use crate::cleaning::strings::fold_text;
+use crate::ids::codes::*;
+use crate::layout::Config;
+use crate::util::default;
+
+pub const SUSPECT_ISBN_PATH: &str = "book-links/suspect-isbns.parquet";
+
+/// Thresholds for flagging an ISBN as suspect.
+///
+/// An ISBN is flagged if it exceeds any of the maximums.
+#[derive(Debug, Clone)]
+pub struct SuspectThresholds {
+    /// Only examine neighbors of ISBNs with at least this many links.
+    pub min_degree: u32,
+    pub max_degree: u32,
+    pub max_titles: u32,
+    pub max_authors: u32,
+}
+
+/// An ISBN flagged as suspect.
+#[derive(ParquetRecordWriter, Debug)]
+pub struct SuspectISBN {
+    pub isbn_id: i32,
+    pub isbn: String,
+    pub degree: u32,
+    pub n_loc_recs: u32,
+    pub n_ol_editions: u32,
+    pub n_gr_books: u32,
+    pub n_titles: u32,
+    pub n_authors: u32,
+}
+
+impl SuspectThresholds {
+    fn flags(&self, s: &SuspectISBN) -> bool {
+        s.degree > self.max_degree || s.n_titles > self.max_titles || s.n_authors > self.max_authors
+    }
+}
+
+/// Check whether a named edge source links ISBNs to records.
+fn is_isbn_source(name: &str) -> bool {
+    !DIRECT_SOURCES.contains(&name) && !WORK_SOURCES.contains(&name)
+}
+
+/// Scan the ISBN edges of the configured sources, with a column naming the source of each.
+fn scan_isbn_edges(cfg: &Config) -> Result<LazyFrame> {
+    let mut frames = Vec::new();
+    for (name, src) in named_edge_sources(cfg)? {
+        if is_isbn_source(&name) {
+            debug!("scanning ISBN edges from {}", name);
+            frames.push(
+                src.read_edges()?
+                    .with_column(lit(name.as_str()).alias("source")),
+            );
+        }
+    }
+    let df = concat(frames, UnionArgs::default())?;
+    Ok(df.select([
+        col("src").cast(DataType::Int32),
+        col("dst").cast(DataType::Int32),
+        col("source"),
+    ]))
+}
+
+/// Scan the titles of records that link to ISBNs.
+fn scan_titles(cfg: &Config) -> Result<LazyFrame> {
+    let mut frames = vec![
+        LazyFrame::scan_parquet("openlibrary/editions.parquet", default())?
+            .select([id_col("id", &NS_EDITION).alias("code"), col("title")]),
+    ];
+    if cfg.goodreads.enabled {
+        frames.push(
+            LazyFrame::scan_parquet("goodreads/gr-book-info.parquet", default())?
+                .select([id_col("book_id", &NS_GR_BOOK).alias("code"), col("title")]),
+        );
+    }
+    let df = concat(frames, UnionArgs::default())?;
+    Ok(df.select([col("code").cast(DataType::Int32), col("title")]))
+}
+
+/// Scan the authors of records that link to ISBNs.
+fn scan_authors() -> Result<LazyFrame> {
+    let df = LazyFrame::scan_parquet("loc-mds/book-authors.parquet", default())?;
+    Ok(df.select([
+        id_col("rec_id", &NS_LOC_REC)
+            .cast(DataType::Int32)
+            .alias("code"),
+        col("author_name").alias("author"),
+    ]))
+}
+
+/// Count the distinct records linked to each ISBN, overall and from each source.
+///
+/// A record linked twice, by the same source or two different ones, only counts once.
+fn isbn_degrees(edges: LazyFrame, min_degree: u32) -> LazyFrame {
+    let from = |source: &str| {
+        // a source with no links gives a null count
+        col("dst")
+            .filter(col("source").eq(lit(source)))
+            .n_unique()
+            .fill_null(lit(0))
+    };
+    edges
+        .group_by([col("src")])
+        .agg([
+            col("dst").n_unique().alias("degree"),
+            from("LOC").alias("n_loc_recs"),
+            from("OLEditions").alias("n_ol_editions"),
+            from("GRBooks").alias("n_gr_books"),
+        ])
+        .filter(col("degree").gt_eq(lit(min_degree)))
+}
+
+/// Count the distinct folded values linked to each ISBN.
+fn count_distinct(edges: &LazyFrame, values: LazyFrame, column: &str) -> Result<HashMap<i32, u32>> {
+    let df = edges
+        .clone()
+        .join(values, [col("dst")], [col("code")], JoinType::Inner.into())
+        .select([col("src"), col(column)])
+        .collect()?;
+    let srcs = df.column("src")?.i32()?;
+    let vals = df.column(column)?.str()?;
+
+    let mut sets: HashMap<i32, HashSet<String>> = HashMap::new();
+    for (src, val) in srcs.into_iter().zip(vals) {
+        if let (Some(src), Some(val)) = (src, val) {
+            let val = fold_text(val);
+            if !val.is_empty() {
+                sets.entry(src).or_default().insert(val);
+            }
+        }
+    }
+
+    Ok(sets.into_iter().map(|(k, v)| (k, v.len() as u32)).collect())
+}
+
+/// Find suspect ISBNs in the book graph's source tables.
+pub fn find_suspect_isbns(
+    cfg: &Config,
+    thresholds: &SuspectThresholds,
+) -> Result<Vec<SuspectISBN>> {
+    let edges = scan_isbn_edges(cfg)?;
+
+    info!("computing ISBN degrees");
+    let degrees = isbn_degrees(edges.clone(), thresholds.min_degree);
+    let isbns = LazyFrame::scan_parquet("book-links/all-isbns.parquet", default())?.select([
+        id_col("isbn_id", &NS_ISBN)
+            .cast(DataType::Int32)
+            .alias("src"),
+        col("isbn"),
+    ]);
+    let degrees = degrees.join(isbns, [col("src")], [col("src")], JoinType::Inner.into());
+    let degrees = degrees.select([
+        col("src"),
+        col("isbn"),
+        col("degree").cast(DataType::UInt32),
+        col("n_loc_recs").cast(DataType::UInt32),
+        col("n_ol_editions").cast(DataType::UInt32),
+        col("n_gr_books").cast(DataType::UInt32),
+    ]);
+    let degrees = degrees.collect()?;
+    info!(
+        "{} ISBNs have at least {} links",
+        degrees.height(),
+        thresholds.min_degree
+    );
+
+    // only look up neighbors of the ISBNs we are examining
+    let cand = degrees.clone().lazy().select([col("src")]);
+    let edges = edges.join(cand, [col("src")], [col("src")], JoinType::Inner.into());
+
+    info!("counting neighbor titles");
+    let titles = count_distinct(&edges, scan_titles(cfg)?, "title")?;
+    info!("counting neighbor authors");
+    let authors = count_distinct(&edges, scan_authors()?, "author")?;
+
+    let srcs = degrees.column("src")?.i32()?;
+    let labels = degrees.column("isbn")?.str()?;
+    let degs = degrees.column("degree")?.u32()?;
+    let locs = degrees.column("n_loc_recs")?.u32()?;
+    let ols = degrees.column("n_ol_editions")?.u32()?;
+    let grs = degrees.column("n_gr_books")?.u32()?;
+
+    let mut suspects = Vec::new();
+    for i in 0..degrees.height() {
+        let code = srcs.get(i).unwrap();
+        let s = SuspectISBN {
+            isbn_id: NS_ISBN.from_code(code).unwrap(),
+            isbn: labels.get(i).unwrap_or_default().to_string(),
+            degree: degs.get(i).unwrap(),
+            n_loc_recs: locs.get(i).unwrap_or(0),
+            n_ol_editions: ols.get(i).unwrap_or(0),
+            n_gr_books: grs.get(i).unwrap_or(0),
+            n_titles: titles.get(&code).copied().unwrap_or(0),
+            n_authors: authors.get(&code).copied().unwrap_or(0),
+        };
+        if thresholds.flags(&s) {
+            suspects.push(s);
+        }
+    }
+    suspects.sort_by_key(|s| s.isbn_id);
+    info!("flagged {} suspect ISBNs", suspects.len());
+
+    Ok(suspects)
+}
+
+/// Load the IDs of previously-flagged suspect ISBNs.
+pub fn load_suspect_isbn_ids(path: &str) -> Result<Vec<i32>> {
+    let df = LazyFrame::scan_parquet(path, default())?
+        .select([col("isbn_id")])
+        .collect()?;
+    let ids = df.column("isbn_id")?.i32()?;
+    Ok(ids.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+fn test_edges() -> LazyFrame {
+    // ISBN 1 is linked to record 10 by two sources, and to record 11 twice by one
+    df!(
+        "src" => &[1, 1, 1, 1, 2],
+        "dst" => &[10, 10, 11, 11, 12],
+        "source" => &["LOC", "OLEditions", "OLEditions", "OLEditions", "GRBooks"],
+    )
+    .expect("invalid frame")
+    .lazy()
+}
+
+#[test]
+fn test_isbn_degrees_distinct() {
+    let df = isbn_degrees(test_edges(), 0)
+        .sort("src", default())
+        .select([
+            col("src"),
+            col("degree").cast(DataType::UInt32),
+            col("n_loc_recs").cast(DataType::UInt32),
+            col("n_ol_editions").cast(DataType::UInt32),
+            col("n_gr_books").cast(DataType::UInt32),
+        ])
+        .collect()
+        .expect("query failed");
+    let col_vec = |name: &str| -> Vec<Option<u32>> {
+        df.column(name)
+            .unwrap()
+            .u32()
+            .unwrap()
+            .into_iter()
+            .collect()
+    };
+    assert_eq!(col_vec("degree"), vec![Some(2), Some(1)]);
+    assert_eq!(col_vec("n_loc_recs"), vec![Some(1), Some(0)]);
+    assert_eq!(col_vec("n_ol_editions"), vec![Some(2), Some(0)]);
+    assert_eq!(col_vec("n_gr_books"), vec![Some(0), Some(1)]);
+
+    let df = isbn_degrees(test_edges(), 2)
+        .collect()
+        .expect("query failed");
+    assert_eq!(df.height(), 1);
+}
+
+#[test]
+fn test_isbn_sources() {
+    assert!(is_isbn_source("LOC"));
+    assert!(is_isbn_source("ASIN"));
+    assert!(is_isbn_source("BX"));
+    assert!(is_isbn_source("HT"));
+    assert!(!is_isbn_source("OLWorks"));
+    assert!(!is_isbn_source("TitleLinks"));
+    assert!(!is_isbn_source("OLLCCN"));
+}
+
+#[test]
+fn test_count_distinct_folds() {
+    let titles = df!(
+        "code" => &[10, 11, 12],
+        "title" => &["The Hobbit", "the  hobbit", ""],
+    )
+    .expect("invalid frame")
+    .lazy();
+    let counts = count_distinct(&test_edges(), titles, "title").expect("count failed");
+    assert_eq!(counts.get(&1), Some(&1));
+    // empty titles are not counted
+    assert_eq!(counts.get(&2), None);
+}
+
+#[test]
+fn test_thresholds_flag() {
+    let th = SuspectThresholds {
+        min_degree: 2,
+        max_degree: 10,
+        max_titles: 3,
+        max_authors: 3,
+    };
+    let mut s = SuspectISBN {
+        isbn_id: 1,
+        isbn: "9780000000002".into(),
+        degree: 10,
+        n_loc_recs: 4,
+        n_ol_editions: 6,
+        n_gr_books: 0,
+        n_titles: 3,
+        n_authors: 1,
+    };
+    assert!(!th.flags(&s));
+    s.n_titles = 4;
+    assert!(th.flags(&s));
+    s.n_titles = 1;
+    s.degree = 11;
+    assert!(th.flags(&s));
+    s.degree = 2;
+    s.n_authors = 5;
+    assert!(th.flags(&s));
+}
@@ -0,0 +1,534 @@
+//! Link records without ISBNs by title and author.
+//!
+//! Records from GoodReads, OpenLibrary, and the Library of Congress are grouped
+//! into blocks by their normalized title and first-author surname.  Each record
+//! without an ISBN is matched to the best-scoring other record in its block, and
+//! matches scoring at least the threshold become edges in the book graph (the
+//! `TitleLinks` source).
+//!
+//! To estimate precision, a sample of records *with* ISBNs is matched the same way
+//! (against other records with ISBNs), and each match is checked against the
+//! clusters formed by the ISBN and work links alone.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use anyhow::Result;
+use log::*;
+use parquet_derive::ParquetRecordWriter;
+use polars::prelude::*;
+use serde::Serialize;
+
+use super::sources::*;
+use super::stream::CodeIndex;
+use super::unionfind::UnionFind;
+use crate::arrow::TableWriter;
+use crate::cleaning::names::{clean_name, name_surname};
+use crate::cleaning::strings::{fold_text, norm_title};
+use crate::ids::codes::*;
+use crate::io::object::ObjectWriter;
+use crate::layout::Config;
+use crate::util::default;
+
+pub const TITLE_LINK_PATH: &str = "book-links/title-links.parquet";
+
+/// Options for title linking.
+#[derive(Debug, Clone)]
+pub struct TitleLinkOptions {
+    /// The minimum score for a match to become a link.
+    pub threshold: f32,
+    /// Skip blocks with more than this many records.
+    pub max_block: usize,
+    /// The fraction of records with ISBNs to hold out for estimating precision.
+    pub sample_rate: f64,
+}
+
+/// A link between two records with matching titles and authors.
+#[derive(ParquetRecordWriter, Debug, Clone)]
+pub struct TitleLink {
+    pub src: i32,
+    pub dst: i32,
+    pub score: f32,
+}
+
+/// Summary of a title-linking run.
+#[derive(Serialize, Debug, Default)]
+pub struct TitleLinkReport {
+    /// Records without ISBNs that have a usable title and author.
+    pub queries: usize,
+    /// Links emitted for records without ISBNs.
+    pub links: usize,
+    /// Held-out records with ISBNs.
+    pub sampled: usize,
+    /// Held-out records matched at or above the threshold.
+    pub sample_matched: usize,
+    /// Held-out matches in the same ISBN-linked cluster.
+    pub sample_correct: usize,
+    pub precision: f64,
+}
+
+/// A record with its comparison fields.
+#[derive(Debug)]
+struct Record {
+    code: i32,
+    has_isbn: bool,
+    title: String,
+    author: String,
+}
+
+/// Deterministically select a record for the held-out sample.
+fn sampled(code: i32, rate: f64) -> bool {
+    let h = (code as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+    (h as f64) < rate * (u32::MAX as f64)
+}
+
+/// Compute the blocking key for a title and author.
+fn block_key(title: &str, author: &str) -> Option<String> {
+    let title = norm_title(title);
+    let surname = name_surname(author);
+    if title.is_empty() || surname.is_empty() {
+        None
+    } else {
+        Some(format!("{}|{}", title, surname))
+    }
+}
+
+fn key_hash(key: &str) -> u64 {
+    let mut h = DefaultHasher::new();
+    key.hash(&mut h);
+    h.finish()
+}
+
+/// Jaccard similarity of the word sets of two folded strings.
+fn token_jaccard(a: &str, b: &str) -> f32 {
+    let a: HashSet<&str> = a.split_whitespace().collect();
+    let b: HashSet<&str> = b.split_whitespace().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        0.0
+    } else {
+        a.intersection(&b).count() as f32 / union as f32
+    }
+}
+
+/// Score a candidate pair by the similarity of their full titles and authors.
+fn score(a: &Record, b: &Record) -> f32 {
+    (token_jaccard(&a.title, &b.title) + token_jaccard(&a.author, &b.author)) / 2.0
+}
+
+/// Find the best match for a record in its block.
+///
+/// Ties prefer records with ISBNs, then lower codes.
+fn best_match<'a>(rec: &Record, block: &'a [Record], isbn_only: bool) -> Option<(&'a Record, f32)> {
+    let mut best: Option<(&Record, f32)> = None;
+    for cand in block {
+        if cand.code == rec.code || (isbn_only && !cand.has_isbn) {
+            continue;
+        }
+        let s = score(rec, cand);
+        let better = match best {
+            None => true,
+            Some((b, bs)) => {
+                s > bs
+                    || (s == bs && cand.has_isbn && !b.has_isbn)
+                    || (s == bs && cand.has_isbn == b.has_isbn && cand.code < b.code)
+            }
+        };
+        if better {
+            best = Some((cand, s));
+        }
+    }
+    best
+}
+
+/// Scan the GoodReads books with their titles and first authors.
+fn scan_goodreads() -> Result<LazyFrame> {
+    let books = LazyFrame::scan_parquet("goodreads/gr-book-info.parquet", default())?;
+    let authors = LazyFrame::scan_parquet("goodreads/gr-book-authors.parquet", default())?
+        .group_by_stable([col("book_id")])
+        .agg([col("author_id").first()]);
+    let names = LazyFrame::scan_parquet("goodreads/gr-author-info.parquet", default())?;
+    let isbns = LazyFrame::scan_parquet("goodreads/book-isbn-ids.parquet", default())?
+        .select([col("book_id"), lit(true).alias("has_isbn")])
+        .unique(None, UniqueKeepStrategy::Any);
+
+    let df = books
+        .join(
+            authors,
+            [col("book_id")],
+            [col("book_id")],
+            JoinType::Inner.into(),
+        )
+        .join(
+            names,
+            [col("author_id")],
+            [col("author_id")],
+            JoinType::Inner.into(),
+        )
+        .join(
+            isbns,
+            [col("book_id")],
+            [col("book_id")],
+            JoinType::Left.into(),
+        );
+    Ok(df.select([
+        id_col("book_id", &NS_GR_BOOK).alias("code"),
+        col("title"),
+        col("name").alias("author"),
+        col("has_isbn"),
+    ]))
+}
+
+/// Scan the OpenLibrary editions with their titles and first authors.
+fn scan_openlib() -> Result<LazyFrame> {
+    let editions = LazyFrame::scan_parquet("openlibrary/editions.parquet", default())?;
+    let authors = LazyFrame::scan_parquet("openlibrary/edition-authors.parquet", default())?
+        .filter(col("pos").eq(lit(0)))
+        .select([col("edition"), col("author")]);
+    let names = LazyFrame::scan_parquet("openlibrary/author-names.parquet", default())?
+        .group_by_stable([col("id")])
+        .agg([col("name").first()]);
+    let isbns = LazyFrame::scan_parquet("openlibrary/edition-isbn-ids.parquet", default())?
+        .select([col("edition"), lit(true).alias("has_isbn")])
+        .unique(None, UniqueKeepStrategy::Any);
+
+    let df = editions
+        .join(
+            authors,
+            [col("id")],
+            [col("edition")],
+            JoinType::Inner.into(),
+        )
+        .join(names, [col("author")], [col("id")], JoinType::Inner.into())
+        .join(isbns, [col("id")], [col("edition")], JoinType::Left.into());
+    Ok(df.select([
+        id_col("id", &NS_EDITION).alias("code"),
+        col("title"),
+        col("name").alias("author"),
+        col("has_isbn"),
+    ]))
+}
+
+/// Scan the LOC book records with their titles (MARC 245$a) and first authors.
+fn scan_loc() -> Result<LazyFrame> {
+    let titles = LazyFrame::scan_parquet("loc-mds/book-fields.parquet", default())?
+        .filter(
+            col("tag")
+                .eq(lit(245))
+                .and(col("sf_code").eq(lit(b'a' as i32))),
+        )
+        .group_by_stable([col("rec_id")])
+        .agg([col("contents").first().alias("title")]);
+    let authors = LazyFrame::scan_parquet("loc-mds/book-authors.parquet", default())?
+        .filter(col("author_name").is_not_null())
+        .group_by_stable([col("rec_id")])
+        .agg([col("author_name").first().alias("author")]);
+    let isbns = LazyFrame::scan_parquet("loc-mds/book-isbn-ids.parquet", default())?
+        .select([col("rec_id"), lit(true).alias("has_isbn")])
+        .unique(None, UniqueKeepStrategy::Any);
+
+    let df = titles
+        .join(
+            authors,
+            [col("rec_id")],
+            [col("rec_id")],
+            JoinType::Inner.into(),
+        )
+        .join(
+            isbns,
+            [col("rec_id")],
+            [col("rec_id")],
+            JoinType::Left.into(),
+        );
+    Ok(df.select([
+        id_col("rec_id", &NS_LOC_REC).alias("code"),
+        col("title"),
+        col("author"),
+        col("has_isbn"),
+    ]))
+}
+
+/// Get the names of the record sources enabled in the graph.
+///
+/// Title links can only connect records that are graph nodes, so this uses the
+/// same checks as [node_sources].
+fn record_source_names(cfg: &Config) -> Vec<&'static str> {
+    let gc = &cfg.graph;
+    let mut names = Vec::new();
+    if gc.builtin_enabled("LOC", true) {
+        names.push("LOC");
+    }
+    if gc.builtin_enabled("OLEditions", true) {
+        names.push("OLEditions");
+    }
+    if gc.builtin_enabled("GRBooks", cfg.goodreads.enabled) {
+        names.push("GRBooks");
+    }
+    names
+}
+
+/// Get the record sources enabled by the configuration.
+fn record_sources(cfg: &Config) -> Result<Vec<LazyFrame>> {
+    let mut srcs = Vec::new();
+    for name in record_source_names(cfg) {
+        srcs.push(match name {
+            "LOC" => scan_loc()?,
+            "OLEditions" => scan_openlib()?,
+            "GRBooks" => scan_goodreads()?,
+            other => unreachable!("unknown record source {}", other),
+        });
+    }
+    Ok(srcs)
+}
+
+/// Scan a record source, calling a function with each record's fields and key.
+fn scan_records<F>(src: LazyFrame, mut f: F) -> Result<()>
+where
+    F: FnMut(i32, bool, &str, &str, String),
+{
+    let df = src
+        .select([
+            col("code").cast(DataType::Int32),
+            col("has_isbn").fill_null(lit(false)),
+            col("title"),
+            col("author"),
+        ])
+        .collect()?;
+    let codes = df.column("code")?.i32()?;
+    let has = df.column("has_isbn")?.bool()?;
+    let titles = df.column("title")?.str()?;
+    let authors = df.column("author")?.str()?;
+    for i in 0..df.height() {
+        if let (Some(code), Some(title), Some(author)) =
+            (codes.get(i), titles.get(i), authors.get(i))
+        {
+            if let Some(key) = block_key(title, author) {
+                f(code, has.get(i).unwrap_or(false), title, author, key);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Label the nodes of interest with their clusters in the ISBN and work graph.
+///
+/// This uses the enabled edge sources, except for the ones in [DIRECT_SOURCES].
+fn isbn_clusters(cfg: &Config, wanted: &HashSet<i32>) -> Result<HashMap<i32, u32>> {
+    let srcs: Vec<_> = named_edge_sources(cfg)?
+        .into_iter()
+        .filter(|(name, _)| !DIRECT_SOURCES.contains(&name.as_str()))
+        .map(|(_, src)| src)
+        .collect();
+
+    let mut frames = Vec::new();
+    for src in &srcs {
+        frames.push(src.read_edges()?.select([
+            col("src").cast(DataType::Int32),
+            col("dst").cast(DataType::Int32),
+        ]));
+    }
+    let edges = concat(frames, UnionArgs::default())?.collect()?;
+    let srcs = edges.column("src")?.i32()?;
+    let dsts = edges.column("dst")?.i32()?;
+
+    let codes = srcs.into_iter().chain(dsts).flatten().collect();
+    let index = CodeIndex::from_codes(codes);
+    let mut uf = UnionFind::new(index.len());
+    for pair in srcs.into_iter().zip(dsts) {
+        if let (Some(s), Some(d)) = pair {
+            uf.union(index.lookup(s).unwrap(), index.lookup(d).unwrap());
+        }
+    }
+
+    let mut labels = HashMap::new();
+    for code in wanted {
+        if let Some(i) = index.lookup(*code) {
+            labels.insert(*code, uf.find(i));
+        }
+    }
+    Ok(labels)
+}
+
+/// Link records by title and author, writing the links and returning a report.
+pub fn link_titles(cfg: &Config, opts: &TitleLinkOptions, out: &str) -> Result<TitleLinkReport> {
+    let mut report = TitleLinkReport::default();
+
+    // first pass: find the keys of the blocks we need
+    info!("scanning record titles for query keys");
+    let mut wanted = HashSet::new();
+    for src in record_sources(cfg)? {
+        scan_records(src, |code, has_isbn, _t, _a, key| {
+            if !has_isbn {
+                report.queries += 1;
+                wanted.insert(key_hash(&key));
+            } else if sampled(code, opts.sample_rate) {
+                report.sampled += 1;
+                wanted.insert(key_hash(&key));
+            }
+        })?;
+    }
+    info!(
+        "found {} query records and {} held-out records in {} blocks",
+        report.queries,
+        report.sampled,
+        wanted.len()
+    );
+
+    // second pass: load the records in those blocks
+    info!("loading candidate blocks");
+    let mut blocks: HashMap<String, Vec<Record>> = HashMap::new();
+    for src in record_sources(cfg)? {
+        scan_records(src, |code, has_isbn, title, author, key| {
+            if wanted.contains(&key_hash(&key)) {
+                blocks.entry(key).or_default().push(Record {
+                    code,
+                    has_isbn,
+                    title: fold_text(title),
+                    author: fold_text(&clean_name(author)),
+                });
+            }
+        })?;
+    }
+    drop(wanted);
+
+    info!("matching records in {} blocks", blocks.len());
+    let mut links = Vec::new();
+    let mut held_out = Vec::new();
+    let mut n_skipped = 0;
+    for block in blocks.values() {
+        if block.len() < 2 {
+            continue;
+        }
+        if block.len() > opts.max_block {
+            n_skipped += 1;
+            continue;
+        }
+        for rec in block {
+            if !rec.has_isbn {
+                if let Some((m, s)) = best_match(rec, block, false) {
+                    if s >= opts.threshold {
+                        let (src, dst) = (rec.code.min(m.code), rec.code.max(m.code));
+                        links.push(TitleLink { src, dst, score: s });
+                    }
+                }
+            } else if sampled(rec.code, opts.sample_rate) {
+                if let Some((m, s)) = best_match(rec, block, true) {
+                    if s >= opts.threshold {
+                        held_out.push((rec.code, m.code));
+                    }
+                }
+            }
+        }
+    }
+    drop(blocks);
+    info!(
+        "skipped {} blocks with more than {} records",
+        n_skipped, opts.max_block
+    );
+
+    links.sort_by_key(|l| (l.src, l.dst));
+    links.dedup_by_key(|l| (l.src, l.dst));
+    report.links = links.len();
+    info!("writing {} title links to {}", links.len(), out);
+    let mut writer = TableWriter::open(out)?;
+    for link in links {
+        writer.write_object(link)?;
+    }
+    writer.finish()?;
+
+    report.sample_matched = held_out.len();
+    if !held_out.is_empty() {
+        info!("checking {} held-out matches", held_out.len());
+        let wanted: HashSet<i32> = held_out.iter().flat_map(|(a, b)| [*a, *b]).collect();
+        let labels = isbn_clusters(cfg, &wanted)?;
+        report.sample_correct = held_out
+            .iter()
+            .filter(|(a, b)| labels.contains_key(a) && labels.get(a) == labels.get(b))
+            .count();
+        report.precision = report.sample_correct as f64 / report.sample_matched as f64;
+    }
+    info!(
+        "held-out precision: {} of {} matches correct ({:.2}%)",
+        report.sample_correct,
+        report.sample_matched,
+        report.precision * 100.0
+    );
+
+    Ok(report)
+}
+
+#[cfg(test)]
+fn test_record(code: i32, has_isbn: bool, title: &str, author: &str) -> Record {
+    Record {
+        code,
+        has_isbn,
+        title: fold_text(title),
+        author: fold_text(&clean_name(author)),
+    }
+}
+
+#[test]
+fn test_record_sources_disabled() {
+    let cfg: Config = serde_yaml::from_str(
+        r#"
+bx:
+  enabled: false
+az2014:
+  enabled: false
+az2018:
+  enabled: false
+goodreads:
+  enabled: true
+graph:
+  sources:
+    - name: LOC
+      enabled: false
+"#,
+    )
+    .expect("parse failed");
+    assert_eq!(record_source_names(&cfg), vec!["OLEditions", "GRBooks"]);
+}
+
+#[test]
+fn test_block_key() {
+    assert_eq!(
+        block_key("The Hobbit: or, There and Back Again", "Tolkien, J. R. R."),
+        Some("hobbit|tolkien".into())
+    );
+    assert_eq!(
+        block_key("Hobbit /", "J.R.R. Tolkien"),
+        Some("hobbit|tolkien".into())
+    );
+    assert_eq!(block_key("[Untitled]", "Tolkien"), None);
+}
+
+#[test]
+fn test_token_jaccard() {
+    assert_eq!(token_jaccard("the hobbit", "the hobbit"), 1.0);
+    assert_eq!(token_jaccard("the hobbit", "hobbit"), 0.5);
+    assert_eq!(token_jaccard("", ""), 0.0);
+}
+
+#[test]
+fn test_best_match_prefers_isbn() {
+    let block = vec![
+        test_record(100, false, "The Hobbit", "J. R. R. Tolkien"),
+        test_record(200, false, "The Hobbit", "Tolkien, J. R. R."),
+        test_record(300, true, "The Hobbit", "Tolkien, J.R.R."),
+        test_record(400, true, "The Hobbit: Illustrated", "Tolkien, J.R.R."),
+    ];
+    let (m, s) = best_match(&block[0], &block, false).expect("no match");
+    assert_eq!(m.code, 300);
+    assert_eq!(s, 1.0);
+
+    let (m, s) = best_match(&block[2], &block, true).expect("no match");
+    assert_eq!(m.code, 400);
+    assert!(s < 1.0);
+}
+
+#[test]
+fn test_sampled_rate() {
+    let n = (0..10000).filter(|c| sampled(*c, 0.1)).count();
+    assert!(n > 800 && n < 1200, "sampled {} of 10000", n);
+    assert!(!(0..1000).any(|c| sampled(c, 0.0)));
+}
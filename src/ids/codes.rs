@@ -8,8 +8,17 @@
 //! into different regions.
 //!
 //! [bc]: https://bookdata.piret.info/data/ids.html#book-codes
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use thiserror::Error;
+
+use crate::cleaning::isbns::clean_isbn_chars;
 
 /// The "number space" structure for identifier spaces.
+#[derive(Debug, PartialEq, Eq)]
 pub struct NS<'a> {
     /// The name of this numberspace.
     pub name: &'a str,
@@ -39,6 +48,10 @@ pub const NS_LOC_WORK: NS<'static> = NS::new("LOC-W", "loc_work", 6);
 pub const NS_LOC_INSTANCE: NS<'static> = NS::new("LOC-I", "loc_instance", 7);
 #[allow(dead_code)]
 pub const NS_ISBN: NS<'static> = NS::new("ISBN", "isbn", 9);
+#[allow(dead_code)]
+pub const NS_ASIN: NS<'static> = NS::new("ASIN", "asin", 10);
+#[allow(dead_code)]
+pub const NS_BX: NS<'static> = NS::new("BX", "bx_book", 11);
 
 const NAMESPACES: &'static [&'static NS<'static>] = &[
     &NS_WORK,
@@ -49,8 +62,27 @@ const NAMESPACES: &'static [&'static NS<'static>] = &[
     &NS_LOC_WORK,
     &NS_LOC_INSTANCE,
     &NS_ISBN,
+    &NS_ASIN,
+    &NS_BX,
 ];
 
+/// The range of numeric codes available for number spaces declared in the
+/// configuration.  The upper bound keeps the whole space within an `i32`.
+pub const CUSTOM_NS_CODES: std::ops::RangeInclusive<i32> = 12..=20;
+
+lazy_static! {
+    static ref CUSTOM_NAMESPACES: RwLock<Vec<&'static NS<'static>>> = RwLock::new(Vec::new());
+}
+
+/// Error registering a number space.
+#[derive(Error, Debug)]
+pub enum NSError {
+    #[error("number space code {0} is not available for custom namespaces")]
+    ReservedCode(i32),
+    #[error("number space {0} conflicts with an existing namespace")]
+    Conflict(String),
+}
+
 #[cfg(test)]
 use quickcheck::quickcheck;
 
@@ -109,7 +141,38 @@ impl NS<'static> {
             }
         }
 
-        None
+        let custom = CUSTOM_NAMESPACES.read().unwrap();
+        custom.iter().find(|ns| ns.name() == name).copied()
+    }
+
+    /// Register a custom number space, such as one declared in the configuration.
+    ///
+    /// Registering the same name and code again returns the existing space.
+    pub fn register(name: &str, code: i32) -> Result<&'static NS<'static>, NSError> {
+        if !CUSTOM_NS_CODES.contains(&code) {
+            return Err(NSError::ReservedCode(code));
+        }
+        if NAMESPACES.iter().any(|ns| ns.name() == name) {
+            return Err(NSError::Conflict(name.into()));
+        }
+
+        let mut custom = CUSTOM_NAMESPACES.write().unwrap();
+        for ns in custom.iter() {
+            if ns.name() == name && ns.code() == code {
+                return Ok(ns);
+            } else if ns.name() == name || ns.code() == code {
+                return Err(NSError::Conflict(name.into()));
+            }
+        }
+
+        let fn_name = name.to_lowercase().replace('-', "_");
+        let ns = Box::leak(Box::new(NS {
+            name: Box::leak(name.to_string().into_boxed_str()),
+            fn_name: Box::leak(fn_name.into_boxed_str()),
+            code,
+        }));
+        custom.push(ns);
+        Ok(ns)
     }
 }
 
@@ -122,11 +185,92 @@ pub fn ns_of_book_code(code: i32) -> Option<&'static NS<'static>> {
                 return Some(ns);
             }
         }
+
+        let custom = CUSTOM_NAMESPACES.read().unwrap();
+        return custom.iter().find(|ns| ns.code() == pfx).copied();
     }
 
     None
 }
 
+/// A user-supplied reference to a book record.
+///
+/// This is used by commands that take book identifiers on the command line, and
+/// accepts three forms:
+///
+/// - a numspaced book code (up to 9 digits, e.g. `300012345`)
+/// - a native identifier prefixed with its namespace (e.g. `GR-B:1234`)
+/// - an ISBN (anything else, cleaned of non-ISBN characters)
+///
+/// Bare numbers with 10 or more digits are always ISBNs, so records in namespaces
+/// whose codes have 10 digits (such as ASINs and BookCrossing books) must be given
+/// with their namespace (e.g. `ASIN:42`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookRef {
+    Code(i32),
+    Native(&'static NS<'static>, i32),
+    ISBN(String),
+}
+
+/// Error parsing a book reference.
+#[derive(Error, Debug)]
+pub enum BookRefError {
+    #[error("unknown namespace {0}")]
+    UnknownNamespace(String),
+    #[error("invalid identifier {0}")]
+    InvalidId(String),
+    #[error("{0} is not a valid book code")]
+    InvalidCode(i32),
+}
+
+impl FromStr for BookRef {
+    type Err = BookRefError;
+
+    fn from_str(s: &str) -> Result<BookRef, BookRefError> {
+        let s = s.trim();
+        if let Some((ns, id)) = s.split_once(':') {
+            let ns = NS::by_name(ns).ok_or_else(|| BookRefError::UnknownNamespace(ns.into()))?;
+            let num: i32 = id.parse().map_err(|_| BookRefError::InvalidId(id.into()))?;
+            // identifiers must fit in the namespace to have a book code
+            if !(0..NS_MULT_BASE).contains(&num) {
+                return Err(BookRefError::InvalidId(id.into()));
+            }
+            Ok(BookRef::Native(ns, num))
+        } else if s.len() <= 9 && s.bytes().all(|b| b.is_ascii_digit()) {
+            // ISBNs have at least 10 digits, so short numbers are book codes
+            let code = s.parse().map_err(|_| BookRefError::InvalidId(s.into()))?;
+            if ns_of_book_code(code).is_some() {
+                Ok(BookRef::Code(code))
+            } else {
+                Err(BookRefError::InvalidCode(code))
+            }
+        } else {
+            Ok(BookRef::ISBN(clean_isbn_chars(s)))
+        }
+    }
+}
+
+impl fmt::Display for BookRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookRef::Code(c) => write!(f, "{}", c),
+            BookRef::Native(ns, id) => write!(f, "{}:{}", ns.name(), id),
+            BookRef::ISBN(isbn) => write!(f, "ISBN {}", isbn),
+        }
+    }
+}
+
+impl BookRef {
+    /// Get the book code for this reference, if it can be computed without a lookup.
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            BookRef::Code(c) => Some(*c),
+            BookRef::Native(ns, id) => Some(ns.to_code(*id)),
+            BookRef::ISBN(_) => None,
+        }
+    }
+}
+
 #[cfg(test)]
 quickcheck! {
   fn prop_code_looks_up(code: i32) -> bool {
@@ -156,3 +300,68 @@ fn test_from_code() {
     assert_eq!(NS_EDITION.from_code(code), None);
     assert_eq!(NS_ISBN.from_code(code), None);
 }
+
+#[test]
+fn test_parse_ref_code() {
+    let r: BookRef = "300000042".parse().expect("parse failed");
+    assert_eq!(r, BookRef::Code(NS_LOC_REC.to_code(42)));
+    assert_eq!(r.code(), Some(300000042));
+}
+
+#[test]
+fn test_parse_ref_long_number() {
+    // Penguin ISBN-10 whose leading digits would be the ASIN number space
+    let r: BookRef = "1101987863".parse().expect("parse failed");
+    assert_eq!(r, BookRef::ISBN("1101987863".into()));
+    let r: BookRef = "1000000042".parse().expect("parse failed");
+    assert_eq!(r, BookRef::ISBN("1000000042".into()));
+    let r: BookRef = "ASIN:42".parse().expect("parse failed");
+    assert_eq!(r.code(), Some(NS_ASIN.to_code(42)));
+    let r: BookRef = "BX:42".parse().expect("parse failed");
+    assert_eq!(r.code(), Some(NS_BX.to_code(42)));
+}
+
+#[test]
+fn test_parse_ref_native() {
+    let r: BookRef = "GR-B:1234".parse().expect("parse failed");
+    assert_eq!(r.code(), Some(NS_GR_BOOK.to_code(1234)));
+}
+
+#[test]
+fn test_parse_ref_isbn() {
+    let r: BookRef = "0-553-21311-3".parse().expect("parse failed");
+    assert_eq!(r, BookRef::ISBN("0553213113".into()));
+    assert_eq!(r.code(), None);
+}
+
+#[test]
+fn test_parse_ref_native_range() {
+    let r: Result<BookRef, _> = "GR-B:-5".parse();
+    assert!(matches!(r, Err(BookRefError::InvalidId(_))));
+    let r: Result<BookRef, _> = "GR-B:999999999".parse();
+    assert!(matches!(r, Err(BookRefError::InvalidId(_))));
+    let r: BookRef = "GR-B:99999999".parse().expect("parse failed");
+    assert_eq!(r.code(), Some(NS_GR_BOOK.to_code(99999999)));
+}
+
+#[test]
+fn test_parse_ref_bad_ns() {
+    let r: Result<BookRef, _> = "FOO:12".parse();
+    assert!(r.is_err());
+}
+
+#[test]
+fn test_register_namespace() {
+    let ns = NS::register("TEST-REG", 19).expect("register failed");
+    assert_eq!(ns.fn_name, "test_reg");
+    assert_eq!(NS::by_name("TEST-REG"), Some(ns));
+    assert_eq!(ns_of_book_code(ns.to_code(42)), Some(ns));
+    let again = NS::register("TEST-REG", 19).expect("re-register failed");
+    assert!(std::ptr::eq(ns, again));
+
+    assert!(NS::register("TEST-OTHER", 19).is_err());
+    assert!(NS::register("ISBN", 18).is_err());
+    assert!(NS::register("TEST-LOW", 9).is_err());
+    assert!(NS::register("TEST-BX", NS_BX.code()).is_err());
+    assert!(NS::register("TEST-HIGH", 21).is_err());
+}
@@ -1,58 +1,135 @@
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::io::prelude::*;
 use std::io::Lines;
-use std::marker::PhantomData;
+use std::mem::{replace, take};
 use std::path::Path;
 use std::str::FromStr;
+use std::thread::{available_parallelism, scope, ScopedJoinHandle};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use crossbeam::channel::{bounded, Receiver, Sender};
 use indicatif::ProgressBar;
 use log::*;
 use serde::de::DeserializeOwned;
 
 use super::compress::open_gzin_progress;
+use super::rejects::{LineSummary, Rejects};
 use super::ObjectWriter;
+use crate::util::logging::{measure_and_recv, measure_and_send, meter_bar};
+
+const CHUNK_LINES: usize = 5000;
+const CHUNK_BUFFER_SIZE: usize = 20;
+/// Maximum number of chunks read but not yet sequenced.
+///
+/// This bounds the parsed chunks held back waiting for an earlier, slower chunk.
+const SEQUENCE_WINDOW: usize = 64;
 
 /// Read lines from a file with buffering, decompression, and parsing.
+///
+/// Lines are parsed in parallel, but their records are delivered in input order.
+/// Lines that fail to parse or are not valid UTF-8 are handled by the processor's
+/// [Rejects]; by default, parsing stops at the first bad line.
 pub struct LineProcessor {
-    reader: Box<dyn BufRead>,
+    reader: Box<dyn BufRead + Send>,
+    rejects: Rejects,
 }
 
-pub struct Records<R> {
-    lines: Lines<Box<dyn BufRead>>,
-    phantom: PhantomData<R>,
+/// A chunk of raw input lines, numbered by its position in the input.
+///
+/// Lines are decoded by the workers, so undecodable lines go to the rejects.
+struct LineChunk {
+    seq: usize,
+    lines: Vec<Vec<u8>>,
 }
 
-impl<R: FromStr> Iterator for Records<R>
-where
-    R::Err: 'static + Error + Send + Sync,
-{
-    type Item = Result<R>;
+/// A chunk of parsed lines.  Lines that failed keep their text for the rejects file.
+struct ParsedChunk<R> {
+    seq: usize,
+    recs: Vec<Result<R, (String, anyhow::Error)>>,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.lines.next().map(|l| {
-            let line = l?;
-            let rec = line.parse()?;
-            Ok(rec)
-        })
+/// Read a line, without its line ending, into a buffer.
+///
+/// Returns `false` at the end of the input.
+fn read_line(reader: &mut dyn BufRead, buf: &mut Vec<u8>) -> std::io::Result<bool> {
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(false);
+    }
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
     }
+    Ok(true)
 }
 
-pub struct JSONRecords<R> {
-    lines: Lines<Box<dyn BufRead>>,
-    phantom: PhantomData<R>,
+/// Decode and parse a line, keeping its text if either fails.
+fn parse_line<R, P>(parse: &P, line: Vec<u8>) -> Result<R, (String, anyhow::Error)>
+where
+    P: Fn(&str) -> Result<R>,
+{
+    match String::from_utf8(line) {
+        Ok(text) => parse(&text).map_err(|e| (text, e)),
+        Err(e) => {
+            let text = String::from_utf8_lossy(e.as_bytes()).into_owned();
+            Err((text, e.into()))
+        }
+    }
 }
 
-impl<R: DeserializeOwned> Iterator for JSONRecords<R> {
-    type Item = Result<R>;
+/// Get the number of threads to use for parsing.
+fn parse_threads() -> usize {
+    // leave room for decompression, reading lines, and writing output
+    let ncpus = available_parallelism().map(|n| n.get()).unwrap_or(4);
+    ncpus.saturating_sub(3).clamp(1, 8)
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.lines.next().map(|l| {
-            let line = l?;
-            let rec = serde_json::from_str(&line)?;
-            Ok(rec)
-        })
+/// Restore the input order of parsed chunks, and send their records to the sink.
+///
+/// Each sequenced chunk returns a token to `window`, allowing the reader to send
+/// another chunk.
+fn sequence<R, F>(
+    rx: Receiver<ParsedChunk<R>>,
+    window: Sender<()>,
+    rejects: &mut Rejects,
+    sink: &mut F,
+) -> Result<()>
+where
+    F: FnMut(R) -> Result<()>,
+{
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    for chunk in rx {
+        pending.insert(chunk.seq, chunk.recs);
+        while let Some(recs) = pending.remove(&next) {
+            next += 1;
+            // the reader may have finished, in which case it needs no more tokens
+            let _ = window.send(());
+            for rec in recs {
+                let rec = match rec {
+                    Ok(rec) => rejects.check("", Ok(rec))?,
+                    Err((line, e)) => rejects.check(&line, Err(e))?,
+                };
+                if let Some(rec) = rec {
+                    sink(rec).map_err(|e| {
+                        error!("error writing line {}: {:?}", rejects.line_number(), e);
+                        e
+                    })?;
+                }
+            }
+        }
+    }
+    if !pending.is_empty() {
+        error!(
+            "{} parsed chunks are waiting for chunk {}",
+            pending.len(),
+            next
+        );
+        return Err(anyhow!("input chunk {} was never parsed", next));
     }
+    Ok(())
 }
 
 impl LineProcessor {
@@ -61,56 +138,230 @@ impl LineProcessor {
         let read = open_gzin_progress(path, pb)?;
         Ok(LineProcessor {
             reader: Box::new(read),
+            rejects: Rejects::abort(),
         })
     }
 
+    /// Handle lines that fail to parse with the specified [Rejects].
+    pub fn with_rejects(self, rejects: Rejects) -> LineProcessor {
+        LineProcessor { rejects, ..self }
+    }
+
     /// Get the lines as strings.
     #[allow(dead_code)]
-    pub fn lines(self) -> Lines<Box<dyn BufRead>> {
+    pub fn lines(self) -> Lines<Box<dyn BufRead + Send>> {
         self.reader.lines()
     }
 
-    /// Get the lines as records.
-    pub fn records<R: FromStr>(self) -> Records<R> {
-        Records {
-            lines: self.reader.lines(),
-            phantom: PhantomData,
+    /// Parse lines in parallel and pass the results, in order, to a sink.
+    ///
+    /// A reader thread splits the input into chunks of lines, worker threads parse
+    /// the chunks, and this thread puts the parsed chunks back in input order before
+    /// passing their records to `sink`.  Sinks that assign IDs as they see records
+    /// therefore produce the same IDs as a sequential scan.
+    pub fn parse_parallel<R, P, F>(self, parse: P, sink: F) -> Result<LineSummary>
+    where
+        R: Send,
+        P: Fn(&str) -> Result<R> + Sync,
+        F: FnMut(R) -> Result<()>,
+    {
+        let LineProcessor {
+            reader,
+            mut rejects,
+        } = self;
+        let mut sink = sink;
+        let parse = &parse;
+        let nthreads = parse_threads();
+        let fill = meter_bar(CHUNK_BUFFER_SIZE, "input chunks");
+        let (chunk_tx, chunk_rx) = bounded(CHUNK_BUFFER_SIZE);
+        let (parsed_tx, parsed_rx) = bounded(CHUNK_BUFFER_SIZE);
+        // the reader takes a token for each chunk, and the sequencer returns it
+        let (window_tx, window_rx) = bounded(SEQUENCE_WINDOW);
+        for _ in 0..SEQUENCE_WINDOW {
+            window_tx.send(())?;
         }
+
+        scope(|s| {
+            // background thread getting lines
+            let fpb = fill.clone();
+            let bg_read: ScopedJoinHandle<'_, Result<()>> = s.spawn(move || {
+                let mut reader = reader;
+                let mut lines = Vec::with_capacity(CHUNK_LINES);
+                let mut seq = 0;
+                let mut buf = Vec::new();
+                while read_line(&mut reader, &mut buf)? {
+                    lines.push(take(&mut buf));
+                    if lines.len() >= CHUNK_LINES {
+                        let lines = replace(&mut lines, Vec::with_capacity(CHUNK_LINES));
+                        if window_rx.recv().is_err()
+                            || measure_and_send(&chunk_tx, LineChunk { seq, lines }, &fpb).is_err()
+                        {
+                            // the sequencer stopped early, and reports why
+                            return Ok(());
+                        }
+                        seq += 1;
+                    }
+                }
+                if !lines.is_empty() && window_rx.recv().is_ok() {
+                    let _ = chunk_tx.send(LineChunk { seq, lines });
+                }
+                Ok(())
+            });
+
+            debug!("spawning {} parser threads", nthreads);
+            for _ in 0..nthreads {
+                let rx = chunk_rx.clone();
+                let tx = parsed_tx.clone();
+                let fill = fill.clone();
+                s.spawn(move || {
+                    while let Some(chunk) = measure_and_recv(&rx, &fill) {
+                        let recs = chunk
+                            .lines
+                            .into_iter()
+                            .map(|l| parse_line(parse, l))
+                            .collect();
+                        if tx
+                            .send(ParsedChunk {
+                                seq: chunk.seq,
+                                recs,
+                            })
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+            // the workers hold the remaining ends, so the channels close when they finish
+            drop(chunk_rx);
+            drop(parsed_tx);
+
+            // if sequencing fails, dropping its receiver shuts down the workers and reader
+            let res = sequence(parsed_rx, window_tx, &mut rejects, &mut sink);
+            bg_read.join().map_err(std::panic::resume_unwind)??;
+            res
+        })?;
+        fill.finish_and_clear();
+
+        rejects.finish()
     }
 
-    /// Get the lines as JSON records.
-    pub fn json_records<R: DeserializeOwned>(self) -> JSONRecords<R> {
-        JSONRecords {
-            lines: self.reader.lines(),
-            phantom: PhantomData,
-        }
+    /// Parse lines with [FromStr] and pass the records, in order, to a function.
+    pub fn for_each_record<R, F>(self, f: F) -> Result<LineSummary>
+    where
+        R: FromStr + Send,
+        R::Err: 'static + Error + Send + Sync,
+        F: FnMut(R) -> Result<()>,
+    {
+        self.parse_parallel(|l| Ok(l.parse()?), f)
+    }
+
+    /// Parse lines as JSON and pass the records, in order, to a function.
+    pub fn for_each_json<R, F>(self, f: F) -> Result<LineSummary>
+    where
+        R: DeserializeOwned + Send,
+        F: FnMut(R) -> Result<()>,
+    {
+        self.parse_parallel(|l| Ok(serde_json::from_str(l)?), f)
     }
 
     /// Process JSON rows into an object writer.
     ///
     /// This parses each line of the data set, deserializes it with JSON, and passes the resulting object
     /// to the specified [ObjectWriter].  It produces useful error messages with line numbers when there
-    /// is a failure. It does **not** call [ObjectWriter::finish] when it is done - the caller needs to do that.
-    pub fn process_json<W, R>(self, writer: &mut W) -> Result<usize>
+    /// is a failure, and applies the processor's rejected-line policy. It does **not** call
+    /// [ObjectWriter::finish] when it is done - the caller needs to do that.
+    pub fn process_json<W, R>(self, writer: &mut W) -> Result<LineSummary>
     where
-        R: DeserializeOwned,
+        R: DeserializeOwned + Send,
         W: ObjectWriter<R>,
     {
-        let mut line_no = 0;
-        for line in self.json_records() {
-            line_no += 1;
-            let obj: R = line.map_err(|e| {
-                error!("error parsing line {}: {:?}", line_no, e);
-                e
-            })?;
-            writer.write_object(obj).map_err(|e| {
-                error!("error writing line {}: {:?}", line_no, e);
-                e
-            })?;
-        }
+        self.for_each_json(|obj| writer.write_object(obj))
+    }
+}
 
-        debug!("read {} lines", line_no);
+#[test]
+fn test_parse_parallel_order() {
+    use std::io::Cursor;
 
-        Ok(line_no)
-    }
+    let n = CHUNK_LINES * 7 + 13;
+    let text: String = (0..n).map(|i| format!("{}\n", i)).collect();
+    let proc = LineProcessor {
+        reader: Box::new(Cursor::new(text)),
+        rejects: Rejects::abort(),
+    };
+    let mut seen = Vec::with_capacity(n);
+    let summary = proc
+        .for_each_record(|i: usize| {
+            seen.push(i);
+            Ok(())
+        })
+        .expect("parse failed");
+    assert_eq!(summary.lines, n);
+    assert_eq!(seen, (0..n).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_sequence_missing_chunk() {
+    let (tx, rx) = bounded(4);
+    let (window_tx, _window_rx) = bounded(4);
+    tx.send(ParsedChunk {
+        seq: 1,
+        recs: vec![Ok(1)],
+    })
+    .unwrap();
+    drop(tx);
+    let mut rejects = Rejects::abort();
+    let mut seen = Vec::new();
+    let res = sequence(rx, window_tx, &mut rejects, &mut |i: usize| {
+        seen.push(i);
+        Ok(())
+    });
+    assert!(res.is_err());
+    assert!(seen.is_empty());
+}
+
+#[test]
+fn test_parse_parallel_abort() {
+    use std::io::Cursor;
+
+    let proc = LineProcessor {
+        reader: Box::new(Cursor::new("1\n2\nthree\n4\n")),
+        rejects: Rejects::abort(),
+    };
+    let mut seen = Vec::new();
+    let res = proc.for_each_record(|i: usize| {
+        seen.push(i);
+        Ok(())
+    });
+    assert!(res.is_err());
+    assert_eq!(seen, vec![1, 2]);
+}
+
+#[test]
+fn test_parse_parallel_bad_utf8() {
+    use std::io::Cursor;
+
+    use super::rejects::{ErrorOpts, ErrorPolicy};
+
+    let opts = ErrorOpts {
+        on_error: ErrorPolicy::Skip,
+        rejects: None,
+        max_error_rate: 0.5,
+    };
+    let rejects = Rejects::open(&opts, Path::new("bad.txt.gz")).expect("open failed");
+    let proc = LineProcessor {
+        reader: Box::new(Cursor::new(b"1\r\n\xff2\n3".to_vec())),
+        rejects,
+    };
+    let mut seen = Vec::new();
+    let summary = proc
+        .for_each_record(|i: usize| {
+            seen.push(i);
+            Ok(())
+        })
+        .expect("parse failed");
+    assert_eq!(seen, vec![1, 3]);
+    assert_eq!(summary.lines, 3);
+    assert_eq!(summary.rejected, 1);
 }
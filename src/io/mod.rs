@@ -10,10 +10,12 @@ pub mod compress;
 pub mod ext;
 pub mod lines;
 pub mod object;
+pub mod rejects;
 
 pub use compress::open_gzin_progress;
 pub use lines::LineProcessor;
 pub use object::ObjectWriter;
+pub use rejects::{ErrorOpts, Rejects};
 
 /// Trait for data processing sinks with input and ouptut files.
 pub trait DataSink {
@@ -0,0 +1,292 @@
+//! Policies for input lines that fail to parse.
+//!
+//! By default, line-oriented scans stop at the first malformed line.  They can
+//! instead skip bad lines, or quarantine them: skip them and write each one,
+//! with its line number and error message, to a compressed rejects file.
+//! Either way, the scan still fails if too many lines are rejected.
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use flate2::write::GzEncoder;
+use friendly::scalar;
+use lazy_static::lazy_static;
+use log::*;
+use parse_display::{Display, FromStr};
+use serde::Serialize;
+
+/// Number of rejected lines to log individually before going quiet.
+const LOG_LIMIT: usize = 10;
+
+lazy_static! {
+    /// Explicit rejects files opened by this process, to append the later inputs.
+    static ref OPENED: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+}
+
+/// What to do with a line that fails to parse.
+#[derive(Display, FromStr, Debug, Clone, Copy, PartialEq, Eq)]
+#[display(style = "lowercase")]
+pub enum ErrorPolicy {
+    /// Fail on the first bad line.
+    Abort,
+    /// Log and skip bad lines.
+    Skip,
+    /// Skip bad lines and write them to a rejects file.
+    Quarantine,
+}
+
+/// Options for handling malformed input lines.
+#[derive(Args, Debug, Clone)]
+pub struct ErrorOpts {
+    /// Handle malformed lines with POLICY (abort, skip, or quarantine).
+    #[arg(long = "on-error", name = "POLICY", default_value = "abort")]
+    pub on_error: ErrorPolicy,
+
+    /// Write quarantined lines from all inputs to REJECTS.
+    #[arg(long = "rejects", name = "REJECTS")]
+    pub rejects: Option<PathBuf>,
+
+    /// Fail if more than fraction RATE of the lines are rejected.
+    #[arg(long = "max-error-rate", name = "RATE", default_value_t = 0.001)]
+    pub max_error_rate: f64,
+}
+
+impl ErrorOpts {
+    /// Get the rejects file for an input file.
+    ///
+    /// Unless one is specified, this is the input's base name with `-rejects.jsonl.gz`,
+    /// in the current directory.
+    pub fn rejects_path(&self, input: &Path) -> PathBuf {
+        if let Some(path) = &self.rejects {
+            return path.clone();
+        }
+        let name = input
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let stem = name.split('.').next().unwrap_or_default();
+        PathBuf::from(format!("{}-rejects.jsonl.gz", stem))
+    }
+}
+
+/// A rejected line in the rejects file.
+#[derive(Serialize)]
+struct RejectRec<'a> {
+    input: &'a str,
+    line: usize,
+    error: String,
+    text: &'a str,
+}
+
+/// Counts of the lines read by a scan.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineSummary {
+    pub lines: usize,
+    pub rejected: usize,
+}
+
+impl LineSummary {
+    /// The fraction of lines that were rejected.
+    pub fn error_rate(&self) -> f64 {
+        if self.lines > 0 {
+            self.rejected as f64 / self.lines as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Apply an error policy to the lines of an input file.
+pub struct Rejects {
+    policy: ErrorPolicy,
+    max_rate: f64,
+    input: String,
+    path: Option<PathBuf>,
+    out: Option<GzEncoder<File>>,
+    summary: LineSummary,
+}
+
+impl Rejects {
+    /// Fail on the first bad line.
+    pub fn abort() -> Rejects {
+        Rejects {
+            policy: ErrorPolicy::Abort,
+            max_rate: 0.0,
+            input: String::new(),
+            path: None,
+            out: None,
+            summary: LineSummary::default(),
+        }
+    }
+
+    /// Set up rejected-line handling for an input file.
+    ///
+    /// A rejects file given with `--rejects` is shared by all inputs of the command:
+    /// the first input to open it truncates it, and later ones append to it.
+    pub fn open(opts: &ErrorOpts, input: &Path) -> Result<Rejects> {
+        let mut rejects = Rejects::abort();
+        rejects.policy = opts.on_error;
+        rejects.max_rate = opts.max_error_rate;
+        rejects.input = input.display().to_string();
+        if opts.on_error == ErrorPolicy::Quarantine {
+            let path = opts.rejects_path(input);
+            let append = opts.rejects.is_some() && !OPENED.lock().unwrap().insert(path.clone());
+            let out = if append {
+                info!("appending rejected lines to {}", path.display());
+                OpenOptions::new().append(true).open(&path)?
+            } else {
+                info!("writing rejected lines to {}", path.display());
+                File::create(&path)?
+            };
+            rejects.out = Some(GzEncoder::new(out, flate2::Compression::fast()));
+            rejects.path = Some(path);
+        }
+        Ok(rejects)
+    }
+
+    /// Get the number of lines checked so far.
+    pub fn line_number(&self) -> usize {
+        self.summary.lines
+    }
+
+    /// Check the result of parsing a line.
+    ///
+    /// Returns the record if it parsed, and `None` if the line was rejected.
+    pub fn check<R>(&mut self, text: &str, res: Result<R>) -> Result<Option<R>> {
+        self.summary.lines += 1;
+        let line = self.summary.lines;
+        let err = match res {
+            Ok(rec) => return Ok(Some(rec)),
+            Err(e) => e,
+        };
+
+        if self.policy == ErrorPolicy::Abort {
+            error!("error parsing line {}: {:?}", line, err);
+            return Err(err);
+        }
+
+        self.summary.rejected += 1;
+        if self.summary.rejected <= LOG_LIMIT {
+            warn!("rejecting line {}: {}", line, err);
+        } else {
+            debug!("rejecting line {}: {}", line, err);
+        }
+        if let Some(out) = &mut self.out {
+            serde_json::to_writer(
+                &mut *out,
+                &RejectRec {
+                    input: &self.input,
+                    line,
+                    error: format!("{:#}", err),
+                    text,
+                },
+            )?;
+            out.write_all(b"\n")?;
+        }
+
+        Ok(None)
+    }
+
+    /// Finish checking lines, and fail if too many were rejected.
+    pub fn finish(self) -> Result<LineSummary> {
+        let summary = self.summary;
+        if let Some(out) = self.out {
+            out.finish()?;
+        }
+
+        if summary.rejected > 0 {
+            info!(
+                "read {} lines, rejected {} ({:.3}%)",
+                scalar(summary.lines),
+                scalar(summary.rejected),
+                summary.error_rate() * 100.0
+            );
+            if let Some(path) = &self.path {
+                info!("rejected lines are in {}", path.display());
+            }
+        } else {
+            info!("read {} lines", scalar(summary.lines));
+        }
+
+        if summary.error_rate() > self.max_rate {
+            error!(
+                "error rate {:.3}% exceeds limit of {:.3}%",
+                summary.error_rate() * 100.0,
+                self.max_rate * 100.0
+            );
+            return Err(anyhow!(
+                "rejected {} of {} lines",
+                summary.rejected,
+                summary.lines
+            ));
+        }
+
+        Ok(summary)
+    }
+}
+
+#[test]
+fn test_reject_rate() {
+    let opts = ErrorOpts {
+        on_error: ErrorPolicy::Skip,
+        rejects: None,
+        max_error_rate: 0.4,
+    };
+    let mut rejects = Rejects::open(&opts, Path::new("bob.json.gz")).expect("open failed");
+    assert_eq!(rejects.check("1", Ok(1)).expect("check failed"), Some(1));
+    let bad: Result<i32> = Err(anyhow!("bad line"));
+    assert_eq!(rejects.check("x", bad).expect("check failed"), None);
+    assert_eq!(rejects.check("3", Ok(3)).expect("check failed"), Some(3));
+    let summary = rejects.finish().expect("finish failed");
+    assert_eq!(summary.lines, 3);
+    assert_eq!(summary.rejected, 1);
+
+    let mut rejects = Rejects::abort();
+    let bad: Result<i32> = Err(anyhow!("bad line"));
+    assert!(rejects.check("x", bad).is_err());
+}
+
+#[test]
+fn test_rejects_path() {
+    let opts = ErrorOpts {
+        on_error: ErrorPolicy::Quarantine,
+        rejects: None,
+        max_error_rate: 0.001,
+    };
+    let path = opts.rejects_path(Path::new("../data/openlib/ol_dump_works.txt.gz"));
+    assert_eq!(path, PathBuf::from("ol_dump_works-rejects.jsonl.gz"));
+}
+
+#[test]
+fn test_shared_rejects() {
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+
+    let dir = tempfile::tempdir().expect("tempdir failed");
+    let path = dir.path().join("rejects.jsonl.gz");
+    let opts = ErrorOpts {
+        on_error: ErrorPolicy::Quarantine,
+        rejects: Some(path.clone()),
+        max_error_rate: 1.0,
+    };
+    for input in ["a.txt.gz", "b.txt.gz"] {
+        let mut rejects = Rejects::open(&opts, Path::new(input)).expect("open failed");
+        let bad: Result<i32> = Err(anyhow!("bad line"));
+        assert_eq!(rejects.check("x", bad).expect("check failed"), None);
+        rejects.finish().expect("finish failed");
+    }
+
+    let mut text = String::new();
+    MultiGzDecoder::new(File::open(&path).expect("open failed"))
+        .read_to_string(&mut text)
+        .expect("read failed");
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("a.txt.gz"));
+    assert!(lines[1].contains("b.txt.gz"));
+}
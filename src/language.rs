@@ -148,3 +148,146 @@ impl LanguageBag {
         &self.resolved
     }
 }
+
+/// ISO 639-1 two-letter codes and their MARC (ISO 639-2/B) equivalents.
+const ISO_639_1_MARC: &[(&str, &str)] = &[
+    ("af", "afr"),
+    ("ar", "ara"),
+    ("be", "bel"),
+    ("bg", "bul"),
+    ("bn", "ben"),
+    ("bo", "tib"),
+    ("bs", "bos"),
+    ("ca", "cat"),
+    ("cs", "cze"),
+    ("cy", "wel"),
+    ("da", "dan"),
+    ("de", "ger"),
+    ("el", "gre"),
+    ("en", "eng"),
+    ("eo", "epo"),
+    ("es", "spa"),
+    ("et", "est"),
+    ("eu", "baq"),
+    ("fa", "per"),
+    ("fi", "fin"),
+    ("fr", "fre"),
+    ("ga", "gle"),
+    ("gl", "glg"),
+    ("gu", "guj"),
+    ("he", "heb"),
+    ("hi", "hin"),
+    ("hr", "hrv"),
+    ("hu", "hun"),
+    ("hy", "arm"),
+    ("id", "ind"),
+    ("is", "ice"),
+    ("it", "ita"),
+    ("ja", "jpn"),
+    ("ka", "geo"),
+    ("kn", "kan"),
+    ("ko", "kor"),
+    ("la", "lat"),
+    ("lt", "lit"),
+    ("lv", "lav"),
+    ("mk", "mac"),
+    ("ml", "mal"),
+    ("mr", "mar"),
+    ("ms", "may"),
+    ("my", "bur"),
+    ("nb", "nob"),
+    ("nl", "dut"),
+    ("nn", "nno"),
+    ("no", "nor"),
+    ("pa", "pan"),
+    ("pl", "pol"),
+    ("pt", "por"),
+    ("ro", "rum"),
+    ("ru", "rus"),
+    ("sk", "slo"),
+    ("sl", "slv"),
+    ("sq", "alb"),
+    ("sr", "srp"),
+    ("sv", "swe"),
+    ("sw", "swa"),
+    ("ta", "tam"),
+    ("te", "tel"),
+    ("th", "tha"),
+    ("tl", "tgl"),
+    ("tr", "tur"),
+    ("uk", "ukr"),
+    ("ur", "urd"),
+    ("vi", "vie"),
+    ("yi", "yid"),
+    ("zh", "chi"),
+];
+
+/// ISO 639-2 terminology codes whose bibliographic (MARC) codes differ.
+const ISO_639_2_T_MARC: &[(&str, &str)] = &[
+    ("bod", "tib"),
+    ("ces", "cze"),
+    ("cym", "wel"),
+    ("deu", "ger"),
+    ("ell", "gre"),
+    ("eus", "baq"),
+    ("fas", "per"),
+    ("fra", "fre"),
+    ("hye", "arm"),
+    ("isl", "ice"),
+    ("kat", "geo"),
+    ("mkd", "mac"),
+    ("mri", "mao"),
+    ("msa", "may"),
+    ("mya", "bur"),
+    ("nld", "dut"),
+    ("ron", "rum"),
+    ("slk", "slo"),
+    ("sqi", "alb"),
+    ("zho", "chi"),
+];
+
+/// Normalize a language code to a MARC (ISO 639-2/B) language code.
+///
+/// This accepts ISO 639-1 codes, IETF tags such as `en-US`, and ISO 639-2 codes
+/// in either the bibliographic or terminology form.  Codes it cannot read as a
+/// language code, and two-letter codes it does not know, give [None].
+pub fn marc_language(code: &str) -> Option<String> {
+    let code = code.trim().to_lowercase();
+    let primary = code.split(['-', '_']).next().unwrap_or_default();
+    if !primary.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    match primary.len() {
+        2 => ISO_639_1_MARC
+            .iter()
+            .find(|(k, _)| *k == primary)
+            .map(|(_, m)| m.to_string()),
+        3 => Some(
+            ISO_639_2_T_MARC
+                .iter()
+                .find(|(k, _)| *k == primary)
+                .map(|(_, m)| m.to_string())
+                .unwrap_or_else(|| primary.to_string()),
+        ),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_marc_language_codes() {
+    assert_eq!(marc_language("eng").as_deref(), Some("eng"));
+    assert_eq!(marc_language("en").as_deref(), Some("eng"));
+    assert_eq!(marc_language("en-US").as_deref(), Some("eng"));
+    assert_eq!(marc_language("en-GB").as_deref(), Some("eng"));
+    assert_eq!(marc_language("ES").as_deref(), Some("spa"));
+    assert_eq!(marc_language("fra").as_deref(), Some("fre"));
+    assert_eq!(marc_language("zh_CN").as_deref(), Some("chi"));
+}
+
+#[test]
+fn test_marc_language_bad_codes() {
+    assert_eq!(marc_language(""), None);
+    assert_eq!(marc_language("english"), None);
+    assert_eq!(marc_language("e1"), None);
+    assert_eq!(marc_language("xx"), None);
+}
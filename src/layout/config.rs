@@ -5,6 +5,7 @@ use log::*;
 use serde::Deserialize;
 
 use super::path::BDPath;
+use crate::ids::codes::NS;
 
 const CFG_PATH: BDPath<'static> = BDPath::new("config.yaml");
 
@@ -18,12 +19,70 @@ pub struct GRConfig {
     pub enabled: bool,
 }
 
+/// A custom number space for book codes.
+#[derive(Debug, Deserialize, Clone)]
+pub struct NSConfig {
+    pub name: String,
+    pub code: i32,
+}
+
+/// A node and edge source for the book graph.
+///
+/// An entry naming a built-in source without a `path` only enables or disables
+/// that source.  Otherwise, the source reads the Parquet file at `path`, with one
+/// row per link from a record (in `id_column`) to an ISBN (in `isbn_column`).
+#[derive(Debug, Deserialize, Clone)]
+pub struct GraphSourceConfig {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub path: Option<String>,
+    pub namespace: Option<String>,
+    pub id_column: Option<String>,
+    pub isbn_column: Option<String>,
+    pub label_column: Option<String>,
+}
+
+/// Configuration for the book graph.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct GraphConfig {
+    #[serde(default)]
+    pub namespaces: Vec<NSConfig>,
+    #[serde(default)]
+    pub sources: Vec<GraphSourceConfig>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub bx: DSConfig,
     pub az2014: DSConfig,
     pub az2018: DSConfig,
     pub goodreads: GRConfig,
+    #[serde(default)]
+    pub graph: GraphConfig,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl GraphConfig {
+    /// Register the custom number spaces declared in the configuration.
+    pub fn register_namespaces(&self) -> Result<()> {
+        for ns in &self.namespaces {
+            NS::register(&ns.name, ns.code)?;
+        }
+        Ok(())
+    }
+
+    /// Query whether a built-in graph source is enabled.
+    pub fn builtin_enabled(&self, name: &str, default: bool) -> bool {
+        self.sources
+            .iter()
+            .find(|s| s.name == name && s.path.is_none())
+            .map(|s| s.enabled)
+            .unwrap_or(default)
+    }
 }
 
 impl Config {
@@ -51,5 +110,34 @@ pub fn load_config() -> Result<Config> {
     debug!("reading configuration {}", path.display());
     let f = read_to_string(&path)?;
     let cfg: Config = serde_yaml::from_str(&f)?;
+    cfg.graph.register_namespaces()?;
     Ok(cfg)
 }
+
+#[test]
+fn test_graph_config() {
+    let cfg: Config = serde_yaml::from_str(
+        r#"
+bx:
+  enabled: false
+az2014:
+  enabled: true
+az2018:
+  enabled: true
+goodreads:
+  enabled: true
+graph:
+  sources:
+    - name: GRWorks
+      enabled: false
+    - name: Extra
+      path: extra/extra-isbn-ids.parquet
+      namespace: EX
+"#,
+    )
+    .expect("parse failed");
+    assert!(!cfg.graph.builtin_enabled("GRWorks", true));
+    assert!(cfg.graph.builtin_enabled("LOC", true));
+    assert!(!cfg.graph.builtin_enabled("Extra", false));
+    assert!(cfg.graph.sources[1].enabled);
+}
@@ -0,0 +1,47 @@
+//! Fixed-length data elements of bibliographic records.
+//!
+//! The 008 control field packs several codes into fixed character positions;
+//! see the [LOC documentation](https://www.loc.gov/marc/bibliographic/bd008a.html).
+
+/// The tag of the fixed-length data elements field.
+pub const FIXED_TAG: i16 = 8;
+
+/// Get the first publication date (positions 7–10) from an 008 field.
+///
+/// Dates with unknown digits (e.g. `19uu`) or blanks are treated as missing.
+pub fn date1(field: &str) -> Option<i16> {
+    let date = field.get(7..11)?;
+    if date.bytes().all(|b| b.is_ascii_digit()) {
+        date.parse().ok().filter(|y| *y > 0)
+    } else {
+        None
+    }
+}
+
+/// Get the language code (positions 35–37) from an 008 field.
+///
+/// Blank, undetermined (`und`), and fill (`|||`) codes are treated as missing.
+pub fn language(field: &str) -> Option<&str> {
+    let lang = field.get(35..38)?.trim();
+    if lang.len() == 3 && lang.bytes().all(|b| b.is_ascii_lowercase()) && lang != "und" {
+        Some(lang)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn test_fixed_fields() {
+    let fld = "850101s1984    nyu           000 1 eng  ";
+    assert_eq!(date1(fld), Some(1984));
+    assert_eq!(language(fld), Some("eng"));
+}
+
+#[test]
+fn test_fixed_missing() {
+    let fld = "850101s19uu    nyu           000 1 |||  ";
+    assert_eq!(date1(fld), None);
+    assert_eq!(language(fld), None);
+    assert_eq!(date1("short"), None);
+    assert_eq!(language("short"), None);
+}
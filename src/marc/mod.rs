@@ -4,6 +4,7 @@
 //! Library of Congress and VIAF formats), and for storing MARC data in
 //! Parquet files as a flat table of MARC fields.
 pub mod book_fields;
+pub mod fixed;
 pub mod flat_fields;
 pub mod parse;
 pub mod record;
@@ -6,9 +6,10 @@ use crate::cleaning::names::clean_name;
 use crate::cleaning::strings::norm_unicode;
 use crate::prelude::*;
 
+use super::edition::{clean_text, parse_year};
 use super::key::{parse_ol_key, KS_AUTHOR};
 pub use super::source::OLAuthorSource;
-use super::source::Row;
+use super::source::{Row, Texts};
 
 /// An author record in the extracted Parquet.
 #[derive(ParquetRecordWriter)]
@@ -26,6 +27,44 @@ pub struct AuthorNameRec {
     pub name: String,
 }
 
+/// Descriptive information for an author in the extracted Parquet.
+///
+/// The dates are free text; the years are the first four-digit year in each.
+#[derive(ParquetRecordWriter)]
+pub struct AuthorInfoRec {
+    pub id: u32,
+    pub fuller_name: Option<String>,
+    pub title: Option<String>,
+    pub birth_date: Option<String>,
+    pub death_date: Option<String>,
+    pub birth_year: Option<i16>,
+    pub death_year: Option<i16>,
+}
+
+/// An author's identifier in another authority file.
+#[derive(ParquetRecordWriter)]
+pub struct AuthorRemoteIdRec {
+    pub id: u32,
+    pub scheme: String,
+    pub value: String,
+}
+
+impl AuthorInfoRec {
+    fn create(id: u32, src: &mut OLAuthorSource) -> AuthorInfoRec {
+        let birth_date = clean_text(src.birth_date.take());
+        let death_date = clean_text(src.death_date.take());
+        AuthorInfoRec {
+            id,
+            fuller_name: clean_text(src.fuller_name.take()),
+            title: clean_text(src.title.take()),
+            birth_year: birth_date.as_deref().and_then(parse_year),
+            death_year: death_date.as_deref().and_then(parse_year),
+            birth_date,
+            death_date,
+        }
+    }
+}
+
 /// Get a list of author name records for an author.
 pub fn author_name_records(src: &OLAuthorSource, id: u32) -> Vec<AuthorNameRec> {
     let mut names = Vec::new();
@@ -61,6 +100,8 @@ pub fn author_name_records(src: &OLAuthorSource, id: u32) -> Vec<AuthorNameRec>
 pub struct AuthorProcessor {
     rec_writer: TableWriter<AuthorRec>,
     name_writer: TableWriter<AuthorNameRec>,
+    info_writer: TableWriter<AuthorInfoRec>,
+    id_writer: TableWriter<AuthorRemoteIdRec>,
 }
 
 impl AuthorProcessor {
@@ -68,12 +109,30 @@ impl AuthorProcessor {
         Ok(AuthorProcessor {
             rec_writer: TableWriter::open("authors.parquet")?,
             name_writer: TableWriter::open("author-names.parquet")?,
+            info_writer: TableWriter::open("author-info.parquet")?,
+            id_writer: TableWriter::open("author-remote-ids.parquet")?,
         })
     }
+
+    fn save_ids(&mut self, id: u32, scheme: &str, values: Texts) -> Result<()> {
+        let values: Vec<String> = values.into();
+        for value in values {
+            let value = value.trim();
+            if !value.is_empty() {
+                self.id_writer.write_object(AuthorRemoteIdRec {
+                    id,
+                    scheme: scheme.to_string(),
+                    value: value.to_string(),
+                })?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl ObjectWriter<Row<OLAuthorSource>> for AuthorProcessor {
-    fn write_object(&mut self, row: Row<OLAuthorSource>) -> Result<()> {
+    fn write_object(&mut self, mut row: Row<OLAuthorSource>) -> Result<()> {
         let id = parse_ol_key(&row.key, KS_AUTHOR)?;
 
         self.rec_writer.write_object(AuthorRec {
@@ -90,12 +149,37 @@ impl ObjectWriter<Row<OLAuthorSource>> for AuthorProcessor {
             self.name_writer.write_object(name)?;
         }
 
+        self.info_writer
+            .write_object(AuthorInfoRec::create(id, &mut row.record))?;
+        let remote_ids = std::mem::take(&mut row.record.remote_ids);
+        for (scheme, values) in remote_ids {
+            self.save_ids(id, &scheme, values)?;
+        }
+
         Ok(())
     }
 
     fn finish(self) -> Result<usize> {
         let nr = self.rec_writer.finish()?;
         self.name_writer.finish()?;
+        self.info_writer.finish()?;
+        self.id_writer.finish()?;
         Ok(nr)
     }
 }
+
+#[test]
+fn test_author_info() {
+    let mut src: OLAuthorSource = serde_json::from_str(
+        r#"{"name": "Jane Doe", "birth_date": "3 May 1901", "death_date": " ",
+            "fuller_name": "Jane Q. Doe", "remote_ids": {"viaf": "12345"}}"#,
+    )
+    .expect("parse failed");
+    let info = AuthorInfoRec::create(7, &mut src);
+    assert_eq!(info.fuller_name.as_deref(), Some("Jane Q. Doe"));
+    assert_eq!(info.birth_date.as_deref(), Some("3 May 1901"));
+    assert_eq!(info.birth_year, Some(1901));
+    assert_eq!(info.death_date, None);
+    assert_eq!(info.death_year, None);
+    assert!(src.remote_ids.contains_key("viaf"));
+}
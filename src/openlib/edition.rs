@@ -8,9 +8,10 @@ use crate::cleaning::isbns::clean_isbn_chars;
 use crate::prelude::*;
 
 use super::key::parse_ol_key;
-use super::key::KS_WORK;
+use super::key::{KS_AUTHOR, KS_WORK};
+use super::redirect::{LinkRepairs, Redirects, REDIRECT_FILE};
 pub use super::source::OLEditionRecord;
-use super::source::Row;
+use super::source::{OLEditionInfo, Row, Text, Texts};
 use super::subject::SubjectEntry;
 
 /// An edition row in the extracted Parquet.
@@ -19,7 +20,25 @@ pub struct EditionRec {
     pub id: u32,
     pub key: String,
     pub title: Option<String>,
-    pub language_flag: u8, // added for master thesis - check meaning below
+}
+
+/// Descriptive metadata for an edition.
+///
+/// Fields with several values (such as publishers) are joined with `; `.
+#[derive(ParquetRecordWriter)]
+pub struct EditionInfoRec {
+    pub edition: u32,
+    pub subtitle: Option<String>,
+    pub by_statement: Option<String>,
+    pub edition_name: Option<String>,
+    pub publishers: Option<String>,
+    pub publish_date: Option<String>,
+    pub publish_year: Option<i16>,
+    pub publish_places: Option<String>,
+    pub number_of_pages: Option<u32>,
+    pub pagination: Option<String>,
+    pub physical_format: Option<String>,
+    pub series: Option<String>,
 }
 
 /// Link between edition and work.
@@ -36,6 +55,23 @@ pub struct ISBNrec {
     pub isbn: String,
 }
 
+/// Edition identifier record, linking an edition to its ID in another scheme.
+#[derive(ParquetRecordWriter)]
+pub struct EditionIdRec {
+    pub edition: u32,
+    pub scheme: String,
+    pub value: String,
+}
+
+/// Edition language record.
+#[derive(ParquetRecordWriter)]
+pub struct EditionLangRec {
+    pub edition: u32,
+    pub pos: i16,
+    /// The ISO 639-2 (MARC) language code.
+    pub language: String,
+}
+
 /// Edition author record.
 #[derive(ParquetRecordWriter)]
 pub struct EditionAuthorRec {
@@ -62,14 +98,79 @@ impl From<SubjectEntry> for EditionSubjectRec {
     }
 }
 
+/// Parse a publication year leniently from a free-text date.
+///
+/// This takes the first run of exactly four digits, so it handles forms like
+/// `1999`, `March 3, 1999`, `c1985`, and `1999-03-01`, but not `[19--]`.
+pub fn parse_year(date: &str) -> Option<i16> {
+    date.split(|c: char| !c.is_ascii_digit())
+        .find(|run| run.len() == 4)
+        .and_then(|run| run.parse().ok())
+        .filter(|y| *y > 0)
+}
+
+/// Get the language code from an OpenLibrary language key (`/languages/eng`).
+fn lang_code(key: &str) -> Option<&str> {
+    key.strip_prefix("/languages/")
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+}
+
+pub(super) fn clean_text(text: Option<Text>) -> Option<String> {
+    text.map(|t| t.into())
+        .map(|t: String| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+fn join_texts(texts: Texts) -> Option<String> {
+    let texts: Vec<String> = texts.into();
+    let texts: Vec<_> = texts
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if texts.is_empty() {
+        None
+    } else {
+        Some(texts.join("; "))
+    }
+}
+
+impl EditionInfoRec {
+    fn create(edition: u32, info: OLEditionInfo) -> EditionInfoRec {
+        let publish_date = clean_text(info.publish_date);
+        EditionInfoRec {
+            edition,
+            subtitle: clean_text(info.subtitle),
+            by_statement: clean_text(info.by_statement),
+            edition_name: clean_text(info.edition_name),
+            publishers: join_texts(info.publishers),
+            publish_year: publish_date.as_deref().and_then(parse_year),
+            publish_date,
+            publish_places: join_texts(info.publish_places),
+            number_of_pages: info.number_of_pages.and_then(|n| n.count()),
+            pagination: clean_text(info.pagination),
+            physical_format: clean_text(info.physical_format),
+            series: join_texts(info.series),
+        }
+    }
+}
+
 /// Process edition records into Parquet.
 ///
-/// This must be run **after** the author and work processors.
+/// This must be run **after** the author and work processors, and after the
+/// redirects have been collected.
 pub struct EditionProcessor {
     last_id: u32,
+    redirects: Redirects,
+    work_repairs: LinkRepairs,
+    author_repairs: LinkRepairs,
     rec_writer: TableWriter<EditionRec>,
+    info_writer: TableWriter<EditionInfoRec>,
     link_writer: TableWriter<LinkRec>,
     isbn_writer: TableWriter<ISBNrec>,
+    id_writer: TableWriter<EditionIdRec>,
+    lang_writer: TableWriter<EditionLangRec>,
     author_writer: TableWriter<EditionAuthorRec>,
     subject_writer: TableWriter<EditionSubjectRec>,
 }
@@ -78,9 +179,15 @@ impl EditionProcessor {
     pub fn new() -> Result<EditionProcessor> {
         Ok(EditionProcessor {
             last_id: 0,
+            redirects: Redirects::load(REDIRECT_FILE)?,
+            work_repairs: LinkRepairs::default(),
+            author_repairs: LinkRepairs::default(),
             rec_writer: TableWriter::open("editions.parquet")?,
+            info_writer: TableWriter::open("edition-info.parquet")?,
             link_writer: TableWriter::open("edition-works.parquet")?,
             isbn_writer: TableWriter::open("edition-isbns.parquet")?,
+            id_writer: TableWriter::open("edition-identifiers.parquet")?,
+            lang_writer: TableWriter::open("edition-languages.parquet")?,
             author_writer: TableWriter::open("edition-authors.parquet")?,
             subject_writer: TableWriter::open("edition-subjects.parquet")?,
         })
@@ -102,57 +209,72 @@ impl EditionProcessor {
 
         Ok(())
     }
+
+    fn save_ids(&mut self, edition: u32, scheme: &str, values: Texts) -> Result<()> {
+        let values: Vec<String> = values.into();
+        for value in values {
+            let value = value.trim();
+            if !value.is_empty() {
+                self.id_writer.write_object(EditionIdRec {
+                    edition,
+                    scheme: scheme.to_string(),
+                    value: value.to_string(),
+                })?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl ObjectWriter<Row<OLEditionRecord>> for EditionProcessor {
     fn write_object(&mut self, row: Row<OLEditionRecord>) -> Result<()> {
         self.last_id += 1;
         let id = self.last_id;
-        
-        // Changes done for master thesis
-        // Determine language flag
-        let language_flag = row.record.languages.as_ref().map_or(0, |langs| {
-            let mut has_eng = false;
-            let mut has_other = false;
-
-            for lang in langs {
-                if let Some(code) = lang.key.strip_prefix("/languages/") {
-                    match code {
-                        "eng" => has_eng = true,
-                        _ => has_other = true,
-                    }
-                }
-            }
-
-            match (has_eng, has_other) {
-                (false, false) => 0, // No languages
-                (true, false) => 1,  // Only English
-                (true, true) => 2,   // English and others
-                (false, true) => 3,  // Only others
-            }
-        });
 
         self.rec_writer.write_object(EditionRec {
             id,
             key: row.key.clone(),
             title: row.record.title.clone(),
-            language_flag,
         })?;
+        self.info_writer
+            .write_object(EditionInfoRec::create(id, row.record.info))?;
 
         self.save_isbns(id, row.record.isbn_10, clean_isbn_chars)?;
         self.save_isbns(id, row.record.isbn_13, clean_isbn_chars)?;
         self.save_isbns(id, row.record.asin, clean_asin_chars)?;
 
+        self.save_ids(id, "lccn", row.record.lccn)?;
+        self.save_ids(id, "oclc", row.record.oclc_numbers)?;
+        for (scheme, values) in row.record.identifiers {
+            self.save_ids(id, &scheme, values)?;
+        }
+
+        let languages = row.record.languages.unwrap_or_default();
+        for (pos, lang) in languages.iter().enumerate() {
+            if let Some(language) = lang_code(&lang.key) {
+                self.lang_writer.write_object(EditionLangRec {
+                    edition: id,
+                    pos: pos as i16,
+                    language: language.to_string(),
+                })?;
+            }
+        }
+
         for work in row.record.works {
             let key = work.key;
-            let work = parse_ol_key(&key, KS_WORK)?;
-            self.link_writer
-                .write_object(LinkRec { edition: id, work })?;
+            if let Some(key) = self.work_repairs.resolve(&self.redirects, &key) {
+                let work = parse_ol_key(key, KS_WORK)?;
+                self.link_writer
+                    .write_object(LinkRec { edition: id, work })?;
+            }
         }
 
         for pos in 0..row.record.authors.len() {
-            let author = row.record.authors[pos].id()?;
-            if let Some(aid) = author {
+            let key = row.record.authors[pos].key();
+            let key = key.and_then(|k| self.author_repairs.resolve(&self.redirects, k));
+            if let Some(key) = key {
+                let aid = parse_ol_key(key, KS_AUTHOR)?;
                 let pos = pos as i16;
                 self.author_writer.write_object(EditionAuthorRec {
                     edition: id,
@@ -172,16 +294,71 @@ impl ObjectWriter<Row<OLEditionRecord>> for EditionProcessor {
     fn finish(self) -> Result<usize> {
         let n = self.rec_writer.finish()?;
         info!("wrote {} edition records", scalar(n));
+        let n = self.info_writer.finish()?;
+        info!("wrote {} edition-info records", scalar(n));
         let n = self.author_writer.finish()?;
         info!("wrote {} edition-author records", scalar(n));
         let n = self.link_writer.finish()?;
         info!("wrote {} edition-work records", scalar(n));
         let n = self.isbn_writer.finish()?;
         info!("wrote {} edition-isbn records", scalar(n));
+        let n = self.id_writer.finish()?;
+        info!("wrote {} edition-identifier records", scalar(n));
+        let n = self.lang_writer.finish()?;
+        info!("wrote {} edition-language records", scalar(n));
         let n = self.subject_writer.finish()?;
         info!("wrote {} edition-subject records", scalar(n));
+        info!(
+            "repaired {} work links and {} author links through redirects",
+            scalar(self.work_repairs.repaired),
+            scalar(self.author_repairs.repaired)
+        );
+        info!(
+            "dropped {} work links and {} author links to deleted records",
+            scalar(self.work_repairs.dropped),
+            scalar(self.author_repairs.dropped)
+        );
         Ok(self.last_id as usize)
     }
 }
 
 impl EditionProcessor {}
+
+#[test]
+fn test_parse_year() {
+    assert_eq!(parse_year("1999"), Some(1999));
+    assert_eq!(parse_year("March 3, 1999"), Some(1999));
+    assert_eq!(parse_year("c1985."), Some(1985));
+    assert_eq!(parse_year("1999-03-01"), Some(1999));
+    assert_eq!(parse_year("[19--]"), None);
+    assert_eq!(parse_year(""), None);
+}
+
+#[test]
+fn test_edition_info() {
+    let rec: OLEditionRecord = serde_json::from_str(
+        r#"{
+            "title": "Example",
+            "publishers": ["Penguin", " "],
+            "publish_date": "June 1, 2004",
+            "number_of_pages": "312",
+            "series": "Penguin classics",
+            "by_statement": {"type": "/type/text", "value": "by A. Writer"}
+        }"#,
+    )
+    .unwrap();
+    let info = EditionInfoRec::create(1, rec.info);
+    assert_eq!(info.publishers.as_deref(), Some("Penguin"));
+    assert_eq!(info.publish_year, Some(2004));
+    assert_eq!(info.number_of_pages, Some(312));
+    assert_eq!(info.series.as_deref(), Some("Penguin classics"));
+    assert_eq!(info.by_statement.as_deref(), Some("by A. Writer"));
+    assert_eq!(info.subtitle, None);
+}
+
+#[test]
+fn test_lang_code() {
+    assert_eq!(lang_code("/languages/eng"), Some("eng"));
+    assert_eq!(lang_code("/languages/"), None);
+    assert_eq!(lang_code("eng"), None);
+}
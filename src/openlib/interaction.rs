@@ -0,0 +1,251 @@
+//! OpenLibrary ratings and reading-log schemas.
+//!
+//! The ratings and reading-log dumps are tab-separated, with a work key, an
+//! optional edition key, the rating or shelf, and the date.  The public dumps
+//! are anonymized; rows that start with a user key before the work key are
+//! also accepted, and their users are interned into numeric IDs.
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+use friendly::scalar;
+use parquet_derive::ParquetRecordWriter;
+use parse_display::Display;
+use thiserror::Error;
+
+use crate::arrow::*;
+use crate::ids::index::IdIndex;
+use crate::prelude::*;
+
+use super::key::{parse_ol_key, KS_WORK};
+
+/// Error parsing a ratings or reading-log row.
+#[derive(Error, Debug)]
+pub enum InteractionError {
+    #[error("interaction row has only {0} fields")]
+    TooFewFields(usize),
+    #[error("interaction row has no work key")]
+    NoWork,
+}
+
+/// A raw row from a ratings or reading-log dump.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RawInteraction {
+    pub user: Option<String>,
+    pub work: String,
+    pub value: String,
+    pub date: String,
+}
+
+impl FromStr for RawInteraction {
+    type Err = InteractionError;
+
+    fn from_str(s: &str) -> Result<RawInteraction, InteractionError> {
+        let fields: Vec<&str> = s.trim_end_matches(['\r', '\n']).split('\t').collect();
+        let n = fields.len();
+        if n < 3 {
+            return Err(InteractionError::TooFewFields(n));
+        }
+        let (user, work) = if fields[0].starts_with("/works/") {
+            (None, fields[0])
+        } else if n >= 4 {
+            (Some(fields[0].to_string()), fields[1])
+        } else {
+            return Err(InteractionError::NoWork);
+        };
+        Ok(RawInteraction {
+            user,
+            work: work.to_string(),
+            value: fields[n - 2].trim().to_string(),
+            date: fields[n - 1].trim().to_string(),
+        })
+    }
+}
+
+/// A reading-log shelf.
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq)]
+#[display(style = "kebab-case")]
+pub enum Shelf {
+    WantToRead,
+    CurrentlyReading,
+    AlreadyRead,
+}
+
+impl FromStr for Shelf {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Shelf> {
+        match s.trim().to_lowercase().replace(' ', "-").as_str() {
+            "want-to-read" => Ok(Shelf::WantToRead),
+            "currently-reading" => Ok(Shelf::CurrentlyReading),
+            "already-read" => Ok(Shelf::AlreadyRead),
+            _ => Err(anyhow!("unknown shelf {}", s)),
+        }
+    }
+}
+
+/// Parse a dump date (`YYYY-MM-DD`) into a UNIX timestamp.
+fn parse_date(date: &str) -> Result<i64> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")?;
+    let date = date.and_hms_opt(0, 0, 0).expect("invalid midnight");
+    Ok(date.and_utc().timestamp())
+}
+
+/// A rating in the extracted Parquet.
+#[derive(ParquetRecordWriter)]
+pub struct RatingRecord {
+    pub rec_id: u32,
+    pub user: Option<i32>,
+    pub work: u32,
+    pub rating: f32,
+    pub timestamp: i64,
+}
+
+/// A reading-log entry in the extracted Parquet.
+#[derive(ParquetRecordWriter)]
+pub struct ReadingLogRecord {
+    pub rec_id: u32,
+    pub user: Option<i32>,
+    pub work: u32,
+    pub shelf: String,
+    pub timestamp: i64,
+}
+
+/// Shared state for writing interaction records.
+struct IntState {
+    users: IdIndex<String>,
+    user_file: &'static str,
+    n_recs: u32,
+}
+
+impl IntState {
+    fn new(user_file: &'static str) -> IntState {
+        IntState {
+            users: IdIndex::new(),
+            user_file,
+            n_recs: 0,
+        }
+    }
+
+    /// Start a record, returning its record ID, user, work, and timestamp.
+    fn start(&mut self, row: &RawInteraction) -> Result<(u32, Option<i32>, u32, i64)> {
+        self.n_recs += 1;
+        let user = row
+            .user
+            .as_deref()
+            .map(|u| self.users.intern(u))
+            .transpose()?;
+        let work = parse_ol_key(&row.work, KS_WORK)?;
+        let ts = parse_date(&row.date)?;
+        Ok((self.n_recs, user, work, ts))
+    }
+
+    fn finish(self, kind: &str) -> Result<()> {
+        info!(
+            "wrote {} {} for {} users",
+            scalar(self.n_recs),
+            kind,
+            scalar(self.users.len())
+        );
+        self.users.save(self.user_file, "user", "user_key")?;
+        Ok(())
+    }
+}
+
+/// Process rating rows into Parquet.
+pub struct RatingProcessor {
+    state: IntState,
+    writer: TableWriter<RatingRecord>,
+}
+
+impl RatingProcessor {
+    pub fn new() -> Result<RatingProcessor> {
+        Ok(RatingProcessor {
+            state: IntState::new("rating-users.parquet"),
+            writer: TableWriter::open("ratings.parquet")?,
+        })
+    }
+}
+
+impl ObjectWriter<RawInteraction> for RatingProcessor {
+    fn write_object(&mut self, row: RawInteraction) -> Result<()> {
+        let (rec_id, user, work, timestamp) = self.state.start(&row)?;
+        let rating: f32 = row.value.parse()?;
+        self.writer.write_object(RatingRecord {
+            rec_id,
+            user,
+            work,
+            rating,
+            timestamp,
+        })
+    }
+
+    fn finish(self) -> Result<usize> {
+        let n = self.writer.finish()?;
+        self.state.finish("ratings")?;
+        Ok(n)
+    }
+}
+
+/// Process reading-log rows into Parquet.
+pub struct ReadingLogProcessor {
+    state: IntState,
+    writer: TableWriter<ReadingLogRecord>,
+}
+
+impl ReadingLogProcessor {
+    pub fn new() -> Result<ReadingLogProcessor> {
+        Ok(ReadingLogProcessor {
+            state: IntState::new("reading-log-users.parquet"),
+            writer: TableWriter::open("reading-log.parquet")?,
+        })
+    }
+}
+
+impl ObjectWriter<RawInteraction> for ReadingLogProcessor {
+    fn write_object(&mut self, row: RawInteraction) -> Result<()> {
+        let (rec_id, user, work, timestamp) = self.state.start(&row)?;
+        let shelf: Shelf = row.value.parse()?;
+        self.writer.write_object(ReadingLogRecord {
+            rec_id,
+            user,
+            work,
+            shelf: shelf.to_string(),
+            timestamp,
+        })
+    }
+
+    fn finish(self) -> Result<usize> {
+        let n = self.writer.finish()?;
+        self.state.finish("reading-log entries")?;
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_parse_anonymous_row() {
+    let row: RawInteraction = "/works/OL10W\t/books/OL5M\t4\t2019-11-21"
+        .parse()
+        .expect("parse failed");
+    assert_eq!(row.user, None);
+    assert_eq!(row.work, "/works/OL10W");
+    assert_eq!(row.value, "4");
+    assert_eq!(row.date, "2019-11-21");
+}
+
+#[test]
+fn test_parse_user_row() {
+    let row: RawInteraction = "/people/someone\t/works/OL10W\t\tWant to Read\t2020-03-11"
+        .parse()
+        .expect("parse failed");
+    assert_eq!(row.user.as_deref(), Some("/people/someone"));
+    assert_eq!(row.work, "/works/OL10W");
+    assert_eq!(row.value, "Want to Read");
+}
+
+#[test]
+fn test_parse_shelf() {
+    let shelf: Shelf = "Currently Reading".parse().expect("parse failed");
+    assert_eq!(shelf, Shelf::CurrentlyReading);
+    assert_eq!(shelf.to_string(), "currently-reading");
+    assert_eq!(parse_date("1970-01-02").expect("bad date"), 86400);
+}
@@ -1,12 +1,16 @@
 //! OpenLibrary data layouts and parsing logic.
 pub mod author;
 pub mod edition;
+pub mod interaction;
 pub mod key;
+pub mod redirect;
 pub mod source;
 pub mod subject;
 pub mod work;
 
 pub use author::AuthorProcessor;
 pub use edition::EditionProcessor;
-pub use source::Row;
+pub use interaction::{RatingProcessor, ReadingLogProcessor};
+pub use redirect::RedirectProcessor;
+pub use source::{Entry, Row};
 pub use work::WorkProcessor;
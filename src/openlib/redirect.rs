@@ -0,0 +1,193 @@
+//! OpenLibrary redirects and deletions.
+//!
+//! When OpenLibrary merges records, the merged record's key becomes a redirect to
+//! the surviving record, and other records may still refer to the old key.  We
+//! collect the redirects and deletions from the dumps so those references can be
+//! resolved to the records they now point to.
+use std::collections::{HashMap, HashSet};
+
+use friendly::scalar;
+use parquet_derive::ParquetRecordWriter;
+use polars::prelude::*;
+use serde::de::IgnoredAny;
+
+use crate::arrow::*;
+use crate::prelude::*;
+
+use super::source::Entry;
+
+/// The file of redirects, relative to the OpenLibrary data directory.
+pub const REDIRECT_FILE: &str = "redirects.parquet";
+
+/// A redirect or deletion in the extracted Parquet.
+///
+/// Deleted keys have no location.
+#[derive(ParquetRecordWriter)]
+pub struct RedirectRec {
+    pub key: String,
+    pub location: Option<String>,
+}
+
+/// Collect redirect and delete rows into Parquet, ignoring ordinary records.
+pub struct RedirectProcessor {
+    writer: TableWriter<RedirectRec>,
+    n_records: usize,
+}
+
+impl RedirectProcessor {
+    pub fn new() -> Result<RedirectProcessor> {
+        Ok(RedirectProcessor {
+            writer: TableWriter::open(REDIRECT_FILE)?,
+            n_records: 0,
+        })
+    }
+}
+
+impl ObjectWriter<Entry<IgnoredAny>> for RedirectProcessor {
+    fn write_object(&mut self, entry: Entry<IgnoredAny>) -> Result<()> {
+        match entry {
+            Entry::Record(_) => {
+                self.n_records += 1;
+                Ok(())
+            }
+            Entry::Redirect { key, location } => {
+                self.writer.write_object(RedirectRec { key, location })
+            }
+            Entry::Delete { key } => self.writer.write_object(RedirectRec {
+                key,
+                location: None,
+            }),
+        }
+    }
+
+    fn finish(self) -> Result<usize> {
+        let n = self.writer.finish()?;
+        info!(
+            "wrote {} redirects and deletions, skipped {} records",
+            scalar(n),
+            scalar(self.n_records)
+        );
+        Ok(n)
+    }
+}
+
+/// The result of resolving a key through the redirects.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resolved<'a> {
+    /// The key is not redirected.
+    Current,
+    /// The key redirects to another key.
+    Moved(&'a str),
+    /// The key was deleted, or redirects to a deleted key or into a cycle.
+    Dropped,
+}
+
+/// Redirect chains for resolving OpenLibrary keys.
+#[derive(Default)]
+pub struct Redirects {
+    map: HashMap<String, Option<String>>,
+}
+
+impl Redirects {
+    /// Load the redirects from a Parquet file.
+    pub fn load(path: &str) -> Result<Redirects> {
+        info!("loading redirects from {}", path);
+        let df = LazyFrame::scan_parquet(path, default())?.collect()?;
+        let keys = df.column("key")?.str()?;
+        let locs = df.column("location")?.str()?;
+        let mut map = HashMap::with_capacity(df.height());
+        for (k, l) in keys.into_iter().zip(locs) {
+            if let Some(k) = k {
+                map.insert(k.to_string(), l.map(|s| s.to_string()));
+            }
+        }
+        info!("loaded {} redirects", scalar(map.len()));
+        Ok(Redirects { map })
+    }
+
+    /// Add a redirect (or, with no location, a deletion).
+    #[cfg(test)]
+    pub fn insert(&mut self, key: &str, location: Option<&str>) {
+        self.map
+            .insert(key.to_string(), location.map(|s| s.to_string()));
+    }
+
+    /// Resolve a key by following its redirect chain.
+    pub fn resolve<'a>(&'a self, key: &'a str) -> Resolved<'a> {
+        let mut cur = key;
+        let mut seen = HashSet::new();
+        while let Some(loc) = self.map.get(cur) {
+            if !seen.insert(cur) {
+                warn!("redirect cycle at {}", key);
+                return Resolved::Dropped;
+            }
+            match loc {
+                Some(loc) => cur = loc.as_str(),
+                None => return Resolved::Dropped,
+            }
+        }
+        if cur == key {
+            Resolved::Current
+        } else {
+            Resolved::Moved(cur)
+        }
+    }
+}
+
+/// Counts of record links resolved through redirects.
+#[derive(Default)]
+pub struct LinkRepairs {
+    pub repaired: usize,
+    pub dropped: usize,
+}
+
+impl LinkRepairs {
+    /// Resolve a key, counting it if it was redirected or dropped.
+    pub fn resolve<'a>(&mut self, redirects: &'a Redirects, key: &'a str) -> Option<&'a str> {
+        match redirects.resolve(key) {
+            Resolved::Current => Some(key),
+            Resolved::Moved(loc) => {
+                self.repaired += 1;
+                Some(loc)
+            }
+            Resolved::Dropped => {
+                self.dropped += 1;
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resolve_chain() {
+    let mut r = Redirects::default();
+    r.insert("/works/OL1W", Some("/works/OL2W"));
+    r.insert("/works/OL2W", Some("/works/OL3W"));
+    assert_eq!(r.resolve("/works/OL1W"), Resolved::Moved("/works/OL3W"));
+    assert_eq!(r.resolve("/works/OL2W"), Resolved::Moved("/works/OL3W"));
+    assert_eq!(r.resolve("/works/OL3W"), Resolved::Current);
+}
+
+#[test]
+fn test_resolve_dropped() {
+    let mut r = Redirects::default();
+    r.insert("/works/OL1W", Some("/works/OL2W"));
+    r.insert("/works/OL2W", None);
+    r.insert("/works/OL5W", Some("/works/OL6W"));
+    r.insert("/works/OL6W", Some("/works/OL5W"));
+    assert_eq!(r.resolve("/works/OL1W"), Resolved::Dropped);
+    assert_eq!(r.resolve("/works/OL5W"), Resolved::Dropped);
+}
+
+#[test]
+fn test_link_repairs() {
+    let mut r = Redirects::default();
+    r.insert("/authors/OL1A", Some("/authors/OL2A"));
+    r.insert("/authors/OL3A", None);
+    let mut lr = LinkRepairs::default();
+    assert_eq!(lr.resolve(&r, "/authors/OL1A"), Some("/authors/OL2A"));
+    assert_eq!(lr.resolve(&r, "/authors/OL2A"), Some("/authors/OL2A"));
+    assert_eq!(lr.resolve(&r, "/authors/OL3A"), None);
+    assert_eq!(lr.repaired, 1);
+    assert_eq!(lr.dropped, 1);
+}
@@ -1,4 +1,5 @@
 //! Parse OpenLibrary JSON.
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
 use log::*;
@@ -29,22 +30,62 @@ pub enum RowError {
     ParseError(#[from] serde_json::Error),
 }
 
+/// Type of redirect rows in the OpenLibrary dump.
+pub const TYPE_REDIRECT: &str = "/type/redirect";
+/// Type of delete rows in the OpenLibrary dump.
+pub const TYPE_DELETE: &str = "/type/delete";
+
+/// An entry in an OpenLibrary dump file.
+///
+/// Besides the records themselves, the dumps contain redirects (from a merged
+/// record's key to its new location) and deletions; these are not records of the
+/// dump's type, so they are separated out by the row's type column.
+pub enum Entry<T> {
+    Record(Row<T>),
+    Redirect {
+        key: String,
+        location: Option<String>,
+    },
+    Delete {
+        key: String,
+    },
+}
+
+/// The JSON of a redirect row.
+#[derive(Deserialize)]
+struct OLRedirectRecord {
+    #[serde(default)]
+    location: Option<String>,
+}
+
+/// Split a dump row into its type, key, and JSON data.
+fn split_row(s: &str) -> Result<(&str, &str, &str), RowError> {
+    let (rtype, rest) = split_first(s).ok_or(RowError::FieldError(1))?;
+    let (key, rest) = split_first(rest).ok_or(RowError::FieldError(2))?;
+    let (_, rest) = split_first(rest).ok_or(RowError::FieldError(3))?;
+    let (_, data) = split_first(rest).ok_or(RowError::FieldError(4))?;
+    Ok((rtype, key, data))
+}
+
+fn parse_json<T: de::DeserializeOwned>(key: &str, data: &str) -> Result<T, RowError> {
+    let record = serde_json::from_str(data).map_err(|e| {
+        debug!("invalid JSON in record {}: {:?}", key, e);
+        // the data may not be JSON at all, so fall back to the raw text
+        let jsp = serde_json::from_str::<serde_json::Value>(data)
+            .and_then(|jsv| serde_json::to_string_pretty(&jsv))
+            .unwrap_or_else(|_| data.to_string());
+        debug!("offending JSON: {}", jsp);
+        e
+    })?;
+    Ok(record)
+}
+
 impl<T: de::DeserializeOwned> FromStr for Row<T> {
     type Err = RowError;
 
     fn from_str(s: &str) -> Result<Row<T>, RowError> {
-        // split row into columns
-        let (_, rest) = split_first(s).ok_or(RowError::FieldError(1))?;
-        let (key, rest) = split_first(rest).ok_or(RowError::FieldError(2))?;
-        let (_, rest) = split_first(rest).ok_or(RowError::FieldError(3))?;
-        let (_, data) = split_first(rest).ok_or(RowError::FieldError(4))?;
-        let record = serde_json::from_str(data).map_err(|e| {
-            error!("invalid JSON in record {}: {:?}", key, e);
-            let jsv: serde_json::Value = serde_json::from_str(data).expect("invalid JSON");
-            let jsp = serde_json::to_string_pretty(&jsv).expect("uhh");
-            info!("offending JSON: {}", jsp);
-            e
-        })?;
+        let (_, key, data) = split_row(s)?;
+        let record = parse_json(key, data)?;
         Ok(Row {
             key: key.to_owned(),
             record,
@@ -52,6 +93,29 @@ impl<T: de::DeserializeOwned> FromStr for Row<T> {
     }
 }
 
+impl<T: de::DeserializeOwned> FromStr for Entry<T> {
+    type Err = RowError;
+
+    fn from_str(s: &str) -> Result<Entry<T>, RowError> {
+        let (rtype, key, data) = split_row(s)?;
+        let key = key.to_owned();
+        match rtype {
+            TYPE_REDIRECT => {
+                let rec: OLRedirectRecord = parse_json(&key, data)?;
+                Ok(Entry::Redirect {
+                    key,
+                    location: rec.location,
+                })
+            }
+            TYPE_DELETE => Ok(Entry::Delete { key }),
+            _ => {
+                let record = parse_json(&key, data)?;
+                Ok(Entry::Record(Row { key, record }))
+            }
+        }
+    }
+}
+
 /// Struct representing an author link in OL.
 ///
 /// There are several different formats in which we can find author references.
@@ -85,6 +149,7 @@ impl Author {
     }
 
     /// Get the numeric ID for this author.
+    #[allow(dead_code)]
     pub fn id(&self) -> Result<Option<u32>, OLKeyError> {
         self.key().map(|k| parse_ol_key(k, KS_AUTHOR)).transpose()
     }
@@ -99,6 +164,17 @@ pub struct OLAuthorSource {
     pub personal_name: Option<String>,
     #[serde(default)]
     pub alternate_names: Vec<String>,
+    #[serde(default)]
+    pub fuller_name: Option<Text>,
+    #[serde(default)]
+    pub title: Option<Text>,
+    #[serde(default)]
+    pub birth_date: Option<Text>,
+    #[serde(default)]
+    pub death_date: Option<Text>,
+    /// Identifiers in other authority files (such as `viaf`), keyed by scheme.
+    #[serde(default)]
+    pub remote_ids: BTreeMap<String, Texts>,
 }
 
 /// An edition record parsed from OpenLibrary JSON.
@@ -110,6 +186,13 @@ pub struct OLEditionRecord {
     pub isbn_13: Vec<String>,
     #[serde(default)]
     pub asin: Vec<String>,
+    #[serde(default)]
+    pub lccn: Texts,
+    #[serde(default)]
+    pub oclc_numbers: Texts,
+    /// Identifiers in other systems (such as `goodreads`), keyed by scheme.
+    #[serde(default)]
+    pub identifiers: BTreeMap<String, Texts>,
 
     #[serde(default)]
     pub title: Option<String>,
@@ -122,6 +205,33 @@ pub struct OLEditionRecord {
     pub languages: Option<Vec<Keyed>>, // added for language thesis
     #[serde(flatten)]
     pub subjects: OLSubjects,
+    #[serde(flatten)]
+    pub info: OLEditionInfo,
+}
+
+/// Descriptive metadata from an edition record.
+#[derive(Deserialize, Default)]
+pub struct OLEditionInfo {
+    #[serde(default)]
+    pub subtitle: Option<Text>,
+    #[serde(default)]
+    pub by_statement: Option<Text>,
+    #[serde(default)]
+    pub edition_name: Option<Text>,
+    #[serde(default)]
+    pub publishers: Texts,
+    #[serde(default)]
+    pub publish_date: Option<Text>,
+    #[serde(default)]
+    pub publish_places: Texts,
+    #[serde(default)]
+    pub number_of_pages: Option<Number>,
+    #[serde(default)]
+    pub pagination: Option<Text>,
+    #[serde(default)]
+    pub physical_format: Option<Text>,
+    #[serde(default)]
+    pub series: Texts,
 }
 
 /// An author record parsed from OpenLibrary JSON.
@@ -152,6 +262,50 @@ impl Into<String> for Text {
     }
 }
 
+/// Text fields that may hold one entry or a list of them.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Texts {
+    One(Text),
+    Many(Vec<Text>),
+}
+
+impl Default for Texts {
+    fn default() -> Texts {
+        Texts::Many(Vec::new())
+    }
+}
+
+impl From<Texts> for Vec<String> {
+    fn from(texts: Texts) -> Vec<String> {
+        match texts {
+            Texts::One(t) => vec![t.into()],
+            Texts::Many(ts) => ts.into_iter().map(|t| t.into()).collect(),
+        }
+    }
+}
+
+/// Numeric entries, which are sometimes recorded as strings.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl Number {
+    /// Get the number as a positive count, if it is one.
+    pub fn count(&self) -> Option<u32> {
+        let n = match self {
+            Number::Int(n) => *n,
+            Number::Float(f) => *f as i64,
+            Number::String(s) => s.trim().parse().ok()?,
+        };
+        u32::try_from(n).ok().filter(|n| *n > 0)
+    }
+}
+
 /// Information about a work or edition's subjects.
 #[derive(Deserialize)]
 pub struct OLSubjects {
@@ -164,3 +318,21 @@ pub struct OLSubjects {
     #[serde(default)]
     pub subject_times: Vec<Text>,
 }
+
+#[test]
+fn test_parse_redirect_entry() {
+    let line =
+        "/type/redirect\t/works/OL1W\t3\t2020-01-01T00:00:00\t{\"location\": \"/works/OL2W\"}";
+    let entry: Entry<OLWorkRecord> = line.parse().expect("parse failed");
+    match entry {
+        Entry::Redirect { key, location } => {
+            assert_eq!(key, "/works/OL1W");
+            assert_eq!(location.as_deref(), Some("/works/OL2W"));
+        }
+        _ => panic!("not a redirect"),
+    }
+
+    let line = "/type/delete\t/works/OL3W\t2\t2020-01-01T00:00:00\t{}";
+    let entry: Entry<OLWorkRecord> = line.parse().expect("parse failed");
+    assert!(matches!(entry, Entry::Delete { .. }));
+}
@@ -6,7 +6,8 @@ use crate::arrow::*;
 use crate::prelude::*;
 
 use super::key::parse_ol_key;
-use super::key::KS_WORK;
+use super::key::{KS_AUTHOR, KS_WORK};
+use super::redirect::{LinkRepairs, Redirects, REDIRECT_FILE};
 pub use super::source::OLWorkRecord;
 use super::source::Row;
 use super::subject::SubjectEntry;
@@ -45,10 +46,13 @@ impl From<SubjectEntry> for WorkSubjectRec {
     }
 }
 
-/// Process author source records into Parquet.
+/// Process work source records into Parquet.
 ///
-/// This must be run **after** the author processor.
+/// This must be run **after** the author processor, and after the redirects have
+/// been collected.
 pub struct WorkProcessor {
+    redirects: Redirects,
+    author_repairs: LinkRepairs,
     rec_writer: TableWriter<WorkRec>,
     author_writer: TableWriter<WorkAuthorRec>,
     subject_writer: TableWriter<WorkSubjectRec>,
@@ -58,6 +62,8 @@ impl WorkProcessor {
     /// Create a new work processor.
     pub fn new() -> Result<WorkProcessor> {
         Ok(WorkProcessor {
+            redirects: Redirects::load(REDIRECT_FILE)?,
+            author_repairs: LinkRepairs::default(),
             rec_writer: TableWriter::open("works.parquet")?,
             author_writer: TableWriter::open("work-authors.parquet")?,
             subject_writer: TableWriter::open("work-subjects.parquet")?,
@@ -76,8 +82,10 @@ impl ObjectWriter<Row<OLWorkRecord>> for WorkProcessor {
         })?;
 
         for pos in 0..row.record.authors.len() {
-            let akey = row.record.authors[pos].id()?;
-            if let Some(aid) = akey {
+            let key = row.record.authors[pos].key();
+            let key = key.and_then(|k| self.author_repairs.resolve(&self.redirects, k));
+            if let Some(key) = key {
+                let aid = parse_ol_key(key, KS_AUTHOR)?;
                 let pos = pos as i16;
                 self.author_writer.write_object(WorkAuthorRec {
                     id,
@@ -101,6 +109,11 @@ impl ObjectWriter<Row<OLWorkRecord>> for WorkProcessor {
         info!("wrote {} work-author records", scalar(na));
         let ns = self.subject_writer.finish()?;
         info!("wrote {} work-subject records", scalar(ns));
+        info!(
+            "repaired {} author links through redirects, dropped {} to deleted records",
+            scalar(self.author_repairs.repaired),
+            scalar(self.author_repairs.dropped)
+        );
         Ok(nr)
     }
 }